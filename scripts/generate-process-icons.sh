@@ -62,6 +62,49 @@ cat > "$SOURCES_DIR/generic.svg" << 'EOF'
 </svg>
 EOF
 
+# Simple Meilisearch mark
+echo "  Creating meilisearch icon..."
+cat > "$SOURCES_DIR/meilisearch.svg" << 'EOF'
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 32 32">
+  <polygon fill="#FF5CAA" points="2,26 10,6 15,6 7,26"/>
+  <polygon fill="#FF5CAA" points="10,26 18,6 23,6 15,26"/>
+  <polygon fill="#FF5CAA" points="18,26 26,6 31,6 23,26"/>
+</svg>
+EOF
+
+# Simple Typesense mark
+echo "  Creating typesense icon..."
+cat > "$SOURCES_DIR/typesense.svg" << 'EOF'
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 32 32">
+  <rect x="2" y="2" width="28" height="28" rx="6" fill="#1035BC"/>
+  <rect x="9" y="9" width="14" height="4" fill="#FFFFFF"/>
+  <rect x="14" y="9" width="4" height="15" fill="#FFFFFF"/>
+</svg>
+EOF
+
+# Simple Qdrant mark
+echo "  Creating qdrant icon..."
+cat > "$SOURCES_DIR/qdrant.svg" << 'EOF'
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 32 32">
+  <polygon fill="#DC244C" points="16,2 28.12,9 28.12,23 16,30 3.88,23 3.88,9"/>
+  <line x1="16" y1="16" x2="16" y2="27" stroke="#FFFFFF" stroke-width="2.5" stroke-linecap="round"/>
+  <line x1="16" y1="16" x2="6.5" y2="10.5" stroke="#FFFFFF" stroke-width="2.5" stroke-linecap="round"/>
+  <line x1="16" y1="16" x2="25.5" y2="10.5" stroke="#FFFFFF" stroke-width="2.5" stroke-linecap="round"/>
+</svg>
+EOF
+
+# Simple ClickHouse mark
+echo "  Creating clickhouse icon..."
+cat > "$SOURCES_DIR/clickhouse.svg" << 'EOF'
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 32 32">
+  <rect x="5" y="7" width="3" height="18" fill="#FFCC01"/>
+  <rect x="10" y="7" width="3" height="18" fill="#FFCC01"/>
+  <rect x="15" y="7" width="3" height="18" fill="#FFCC01"/>
+  <rect x="20" y="7" width="3" height="18" fill="#FFCC01"/>
+  <rect x="25" y="13" width="3" height="6" fill="#FFCC01"/>
+</svg>
+EOF
+
 echo ""
 echo "Converting SVGs to PNG (32x32 @2x)..."
 
@@ -1,11 +1,11 @@
 use std::collections::{HashMap, HashSet};
 use std::process::Command;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 use log::{error, warn};
 use nix::errno::Errno;
 use notify::{Event as NotifyEvent, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
@@ -21,9 +21,10 @@ use crate::config::{
 use crate::integrations::brew::{query_brew_services_map, run_brew_stop};
 use crate::integrations::docker::{query_docker_port_map, run_docker_stop};
 use crate::knowledge::{
-    enrich_context, load_knowledge_base, record_sighting, save_knowledge_base,
-    spawn_learning_worker, store_result, AnalysisContext, AnalysisRequest, AnalysisResult,
-    ProcessFingerprint,
+    AnalysisContext, AnalysisRequest, AuditLog, DebouncedSaver, FileStore, KnowledgeEvent,
+    KnowledgeStore, LayeredStore, ProcessFingerprint, PromptHistory, RateLimitState, apply_result,
+    attach_siblings, cleanup_stale_pending, detect_framework, enrich_context, group_process_tree,
+    record_snapshot, shutdown as shutdown_knowledge, spawn_learning_worker,
 };
 use crate::model::*;
 use crate::notify::{maybe_notify_changes, notify_update_available};
@@ -34,12 +35,19 @@ use crate::ui::menu::{
     build_menu_with_context, build_tooltip, collect_targets_for_all, format_command_label,
     parse_menu_action,
 };
+use crate::ui::process_icons::clear_command_cache;
 use crate::update::check_for_update;
 
 const IDLE_THRESHOLD: Duration = Duration::from_secs(30);
 const IDLE_MULTIPLIER: u64 = 2; // Idle poll interval = base * IDLE_MULTIPLIER
 const INTEGRATION_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
 const MENU_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long quitting waits for the learning worker to stop
+const LEARNING_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the result forwarder checks whether the app is exiting
+const FORWARDER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const KB_SAVE_INTERVAL: Duration = Duration::from_secs(2);
+const STALE_PENDING_SECS: i64 = 24 * 60 * 60;
 const UPDATE_CHECK_DELAY: Duration = Duration::from_secs(5);
 const DOWNLOAD_URL: &str =
     "https://github.com/gupsammy/PortKiller/releases/latest/download/PortKiller.dmg";
@@ -63,10 +71,18 @@ pub fn run() -> Result<()> {
     let shared_config = Arc::new(RwLock::new(config.clone()));
 
     // Load knowledge base
-    let knowledge_base = load_knowledge_base().unwrap_or_else(|e| {
+    let global_store = FileStore::default()
+        .with_compression(config.learning.compress)
+        .with_max_bytes(config.learning.max_knowledge_bytes);
+    let knowledge_store = match std::env::current_dir() {
+        Ok(cwd) => LayeredStore::discover(global_store, &cwd),
+        Err(_) => LayeredStore::new(global_store, None),
+    };
+    let mut knowledge_base = knowledge_store.load().unwrap_or_else(|e| {
         log::warn!("Failed to load knowledge base, using defaults: {}", e);
         crate::knowledge::KnowledgeBase::default()
     });
+    knowledge_base.audit_log = AuditLog::from_config(&config.learning);
 
     let mut state = AppState {
         processes: Vec::new(),
@@ -77,6 +93,12 @@ pub fn run() -> Result<()> {
         brew_services_map: HashMap::new(),
         available_update: None,
         knowledge_base,
+        process_masters: HashMap::new(),
+        process_contexts: HashMap::new(),
+        learning_rate_limit: RateLimitState::new(),
+        prompt_history: Arc::new(Mutex::new(PromptHistory::new(
+            config.learning.prompt_history_size,
+        ))),
     };
 
     let event_loop = EventLoop::<UserEvent>::with_user_event()
@@ -87,7 +109,7 @@ pub fn run() -> Result<()> {
 
     // Learning worker channels
     let (learning_tx, learning_rx) = crossbeam_channel::unbounded::<AnalysisRequest>();
-    let (learning_result_tx, learning_result_rx) = crossbeam_channel::unbounded::<AnalysisResult>();
+    let (learning_result_tx, learning_result_rx) = crossbeam_channel::unbounded::<KnowledgeEvent>();
 
     let _monitor_thread = spawn_monitor_thread(proxy.clone(), shared_config.clone());
     let _config_watcher = spawn_config_watcher(proxy.clone(), shared_config.clone());
@@ -96,30 +118,62 @@ pub fn run() -> Result<()> {
 
     // Spawn learning worker if enabled
     let learning_config = Arc::new(config.learning.clone());
-    let _learning_worker = if config.learning.enabled {
-        Some(spawn_learning_worker(
+    // Kept to discard requests the worker hasn't started when quitting
+    let learning_requests = learning_rx.clone();
+    let mut learning_worker = config.learning.enabled.then(|| {
+        spawn_learning_worker(
             learning_config,
             learning_rx,
             learning_result_tx,
-        ))
-    } else {
-        None
-    };
+            state.learning_rate_limit.clone(),
+            state.prompt_history.clone(),
+        )
+    });
 
-    // Spawn thread to forward learning results to event loop
-    let _learning_result_forwarder = {
+    // Results still queued at exit are drained from here during shutdown
+    let learning_results = learning_result_rx.clone();
+
+    // Spawn thread to forward learning results to event loop. Once the gate
+    // is closed on exit it stops, handing back any result it took, so the
+    // rest are drained from `learning_results` rather than sent to a loop
+    // that will never handle them.
+    let forwarding = Arc::new(Mutex::new(true));
+    let mut learning_result_forwarder = Some({
         let proxy = proxy.clone();
+        let forwarding = forwarding.clone();
         thread::spawn(move || {
-            for result in learning_result_rx {
-                if proxy
-                    .send_event(UserEvent::KnowledgeUpdated(result))
-                    .is_err()
-                {
-                    break;
+            loop {
+                let event = match learning_result_rx.recv_timeout(FORWARDER_POLL_INTERVAL) {
+                    Ok(event) => event,
+                    // A worker abandoned at exit keeps the channel open, so
+                    // the gate is checked while idle too
+                    Err(RecvTimeoutError::Timeout) => {
+                        if !*forwarding.lock().unwrap() {
+                            return None;
+                        }
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                };
+                let open = forwarding.lock().unwrap();
+                if !*open {
+                    return Some(event);
+                }
+                let event = match event {
+                    KnowledgeEvent::AnalysisComplete(result) => {
+                        UserEvent::KnowledgeUpdated(*result)
+                    }
+                    KnowledgeEvent::Maintenance => UserEvent::KnowledgeMaintenance,
+                    KnowledgeEvent::SaveKnowledgeBase | KnowledgeEvent::EntryChanged(_) => {
+                        continue;
+                    }
+                };
+                if proxy.send_event(event).is_err() {
+                    return None;
                 }
             }
         })
-    };
+    });
 
     let menu_receiver = MenuEvent::receiver().clone();
 
@@ -139,16 +193,15 @@ pub fn run() -> Result<()> {
 
     update_tray_display(&tray_icon, &state);
     let mut worker_sender: Option<Sender<WorkerCommand>> = Some(worker_tx);
-    let learning_sender: Option<Sender<AnalysisRequest>> = if config.learning.enabled {
+    let mut learning_sender: Option<Sender<AnalysisRequest>> = if config.learning.enabled {
         Some(learning_tx)
     } else {
         None
     };
     // Initialize to past time to force first integration refresh
     let mut last_integration_refresh = Instant::now() - INTEGRATION_REFRESH_INTERVAL;
-    // Track when we last saved the knowledge base
-    let mut last_kb_save = Instant::now();
-    const KB_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+    // Coalesce knowledge base writes from bursts of completed analyses
+    let mut kb_saver = DebouncedSaver::new(knowledge_store, KB_SAVE_INTERVAL);
     // Clone shared_config for use in event loop (for manual reload)
     let shared_config_for_loop = shared_config.clone();
 
@@ -166,6 +219,10 @@ pub fn run() -> Result<()> {
                     let _ = proxy.send_event(UserEvent::MenuAction(action));
                 }
             }
+            // Write knowledge learned during the last debounce window
+            if let Err(e) = kb_saver.tick(&state.knowledge_base) {
+                log::warn!("Failed to save knowledge base: {}", e);
+            }
             event_loop
                 .set_control_flow(ControlFlow::WaitUntil(Instant::now() + MENU_POLL_INTERVAL));
         }
@@ -301,7 +358,9 @@ pub fn run() -> Result<()> {
                     update_tray_display(&tray_icon, &state);
                 }
                 MenuAction::KillPid { pid, .. } => {
-                    if let Some(target) = describe_pid(pid, &state.processes) {
+                    if let Some(target) =
+                        describe_pid(pid, &state.processes, &state.process_masters)
+                    {
                         // Show confirmation dialog
                         if confirm_stop("Stop Process?", &format!("Stop {}?", target.label)) {
                             if let Some(sender) = worker_sender.as_ref() {
@@ -358,7 +417,8 @@ pub fn run() -> Result<()> {
                             .cloned()
                             .collect();
 
-                        let targets = collect_targets_for_all(&regular_processes);
+                        let targets =
+                            collect_targets_for_all(&regular_processes, &state.process_masters);
                         if targets.is_empty() {
                             state.last_feedback = Some(KillFeedback::info(
                                 "No dev port listeners to terminate.".to_string(),
@@ -463,7 +523,10 @@ pub fn run() -> Result<()> {
                             if let Ok(mut cfg) = shared_config_for_loop.write() {
                                 *cfg = new_config.clone();
                             }
+                            state.knowledge_base.audit_log =
+                                AuditLog::from_config(&new_config.learning);
                             state.config = new_config;
+                            clear_command_cache();
                             state.last_feedback =
                                 Some(KillFeedback::info("Configuration reloaded".to_string()));
                         }
@@ -541,7 +604,9 @@ pub fn run() -> Result<()> {
                 update_tray_display(&tray_icon, &state);
             }
             UserEvent::ConfigReloaded(new_config) => {
+                state.knowledge_base.audit_log = AuditLog::from_config(&new_config.learning);
                 state.config = new_config;
+                clear_command_cache();
                 state.last_feedback =
                     Some(KillFeedback::info("Configuration reloaded".to_string()));
                 sync_menu_with_context(&tray_icon, &state);
@@ -578,26 +643,50 @@ pub fn run() -> Result<()> {
             }
             UserEvent::KnowledgeUpdated(result) => {
                 // Store the analysis result in the knowledge base
-                store_result(
-                    &mut state.knowledge_base,
-                    result.fingerprint,
-                    result.response,
-                    result.source,
-                );
-                // Periodically save knowledge base
-                if last_kb_save.elapsed() >= KB_SAVE_INTERVAL {
-                    if let Err(e) = save_knowledge_base(&state.knowledge_base) {
-                        log::warn!("Failed to save knowledge base: {}", e);
-                    }
-                    last_kb_save = Instant::now();
+                apply_result(&mut state.knowledge_base, result);
+                // Save knowledge base, coalescing bursts of results
+                if let Err(e) =
+                    kb_saver.handle_event(&KnowledgeEvent::SaveKnowledgeBase, &state.knowledge_base)
+                {
+                    log::warn!("Failed to save knowledge base: {}", e);
                 }
                 // Refresh menu to show new names
                 sync_menu_with_context(&tray_icon, &state);
             }
+            UserEvent::KnowledgeMaintenance => {
+                cleanup_stale_pending(&mut state.knowledge_base, STALE_PENDING_SECS);
+                if let Err(e) =
+                    kb_saver.handle_event(&KnowledgeEvent::Maintenance, &state.knowledge_base)
+                {
+                    log::warn!("Failed to save knowledge base: {}", e);
+                }
+            }
         },
         Event::LoopExiting => {
-            // Save knowledge base on exit
-            if let Err(e) = save_knowledge_base(&state.knowledge_base) {
+            // Stop forwarding results to the exiting loop
+            *forwarding.lock().unwrap() = false;
+            // Stop the learning worker, cutting short any rate-limit wait.
+            // Processes it hasn't started are still pending and are queued
+            // again next launch; an analysis still in flight at the deadline
+            // is abandoned the same way
+            for _ in learning_requests.try_iter() {}
+            learning_sender.take();
+            if let Some(worker) = learning_worker.take()
+                && !worker.shutdown(LEARNING_SHUTDOWN_TIMEOUT)
+            {
+                log::warn!("Learning worker still busy at exit, saving results so far");
+            }
+            // The forwarder sees the closed gate within a poll interval
+            let held = learning_result_forwarder
+                .take()
+                .and_then(|forwarder| forwarder.join().ok().flatten());
+            if let Some(KnowledgeEvent::AnalysisComplete(result)) = held {
+                apply_result(&mut state.knowledge_base, *result);
+            }
+            // Persist every delivered result along with pending changes
+            if let Err(e) =
+                shutdown_knowledge(&mut state.knowledge_base, &mut kb_saver, &learning_results)
+            {
                 log::warn!("Failed to save knowledge base on exit: {}", e);
             }
             worker_sender.take();
@@ -956,10 +1045,19 @@ fn handle_batch_kill(proxy: &EventLoopProxy<UserEvent>, targets: Vec<KillTarget>
 
 // collect_targets_for_all now in ui::menu
 
-fn describe_pid(pid: i32, processes: &[ProcessInfo]) -> Option<KillTarget> {
+/// Kill target for `pid`, labelled with its ports and those of the workers
+/// it is the master of (see `AppState::process_masters`)
+fn describe_pid(
+    pid: i32,
+    processes: &[ProcessInfo],
+    masters: &HashMap<i32, i32>,
+) -> Option<KillTarget> {
     let mut ports = Vec::new();
     let mut command: Option<String> = None;
-    for process in processes.iter().filter(|p| p.pid == pid) {
+    for process in processes
+        .iter()
+        .filter(|p| p.pid == pid || masters.get(&p.pid) == Some(&pid))
+    {
         if !ports.contains(&process.port) {
             ports.push(process.port);
         }
@@ -1011,9 +1109,11 @@ fn update_tray_display(tray_icon: &TrayIcon, state: &AppState) {
 
 fn refresh_projects_for(state: &mut AppState) {
     let mut missing: HashSet<i32> = HashSet::new();
-    for p in &state.processes {
-        if !state.project_cache.contains_key(&p.pid) {
-            missing.insert(p.pid);
+    // Masters are listed in place of their workers, even if not listening
+    let masters = state.process_masters.values().copied();
+    for pid in state.processes.iter().map(|p| p.pid).chain(masters) {
+        if !state.project_cache.contains_key(&pid) {
+            missing.insert(pid);
         }
     }
     for pid in missing {
@@ -1033,7 +1133,12 @@ fn resolve_project_info(pid: i32) -> Option<ProjectInfo> {
     let name = get_git_repo_name(&path)
         .or_else(|| dir_name(&path))
         .unwrap_or_else(|| "(unknown)".to_string());
-    Some(ProjectInfo { name, path })
+    let framework = detect_framework(&path).map(|detected| detected.framework.to_string());
+    Some(ProjectInfo {
+        name,
+        path,
+        framework,
+    })
 }
 
 fn get_process_cwd(pid: i32) -> Option<std::path::PathBuf> {
@@ -1108,10 +1213,8 @@ fn queue_processes_for_learning(
     state: &mut AppState,
     sender: &Sender<AnalysisRequest>,
 ) {
+    let mut contexts = Vec::with_capacity(state.processes.len());
     for process in &state.processes {
-        // Build fingerprint for this process
-        let mut fingerprint = ProcessFingerprint::new(&process.command);
-
         // Check if this is a Docker container
         let (container_name, container_prefix) =
             if let Some(container) = state.docker_port_map.get(&process.port) {
@@ -1121,10 +1224,6 @@ fn queue_processes_for_learning(
                 (None, None)
             };
 
-        if let Some(ref prefix) = container_prefix {
-            fingerprint = fingerprint.with_container_prefix(prefix);
-        }
-
         // Get project name if available
         let project_name = state
             .project_cache
@@ -1135,6 +1234,7 @@ fn queue_processes_for_learning(
         let mut context = AnalysisContext {
             command: process.command.clone(),
             port: Some(process.port),
+            protocol: Some(process.protocol),
             project_name: project_name.clone(),
             container_name,
             container_prefix: container_prefix.clone(),
@@ -1143,23 +1243,51 @@ fn queue_processes_for_learning(
         };
 
         // Enrich context with system information (executable path, cwd, docker labels, etc.)
-        enrich_context(&mut context);
+        enrich_context(&mut context, &state.config.learning.gatherers);
 
-        // Record sighting and check if analysis is needed
-        if let Some(ctx) = record_sighting(
-            &mut state.knowledge_base,
-            fingerprint.clone(),
+        contexts.push(context);
+    }
+
+    // Forked workers are learned, and listed, as part of their master
+    // (even when the master itself doesn't listen, e.g. a gunicorn arbiter)
+    let leaders = group_process_tree(&mut contexts);
+    state.process_masters = contexts
+        .iter()
+        .filter_map(|ctx| Some((ctx.pid? as i32, ctx.master_pid? as i32)))
+        .collect();
+
+    let config = &state.config.learning;
+    if config.include_siblings {
+        attach_siblings(&mut contexts);
+    }
+
+    // Fingerprint after enrichment so compose labels can be used
+    let fingerprints: Vec<_> = contexts
+        .iter()
+        .map(|context| ProcessFingerprint::from_config(context, config))
+        .collect();
+    state.process_contexts = contexts
+        .iter()
+        .zip(&leaders)
+        .filter_map(|(context, &leader)| {
+            let key = (context.pid? as i32, context.port?);
+            Some((key, contexts[leader].clone()))
+        })
+        .collect();
+    let sightings: Vec<_> = contexts
+        .into_iter()
+        .zip(leaders)
+        .map(|(context, leader)| (fingerprints[leader].clone(), context))
+        .collect();
+
+    // Record the whole refresh at once and queue everything due for analysis
+    for (fingerprint, context) in record_snapshot(&mut state.knowledge_base, &sightings, config) {
+        let request = AnalysisRequest {
+            fingerprint,
             context,
-            &state.config.learning,
-        ) {
-            // Queue for analysis
-            let request = AnalysisRequest {
-                fingerprint,
-                context: ctx,
-            };
-            if let Err(e) = sender.send(request) {
-                log::warn!("Failed to queue process for learning: {}", e);
-            }
+        };
+        if let Err(e) = sender.send(request) {
+            log::warn!("Failed to queue process for learning: {}", e);
         }
     }
 }
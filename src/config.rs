@@ -19,6 +19,8 @@ pub struct Config {
     pub updates: UpdateConfig,
     #[serde(default)]
     pub learning: crate::knowledge::LearningConfig,
+    #[serde(default)]
+    pub theme: crate::ui::theme::CategoryTheme,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -181,5 +183,6 @@ fn validate_config(config: &Config) -> Result<()> {
             anyhow::bail!("invalid port range: start ({}) > end ({})", start, end);
         }
     }
+    config.theme.validate()?;
     Ok(())
 }
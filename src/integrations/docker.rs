@@ -10,7 +10,11 @@ use crate::utils::find_command;
 pub fn query_docker_port_map() -> Result<HashMap<u16, DockerContainerInfo>> {
     let mut map = HashMap::new();
     let out = Command::new(find_command("docker"))
-        .args(["ps", "--format", "{{.ID}}\t{{.Names}}\t{{.Ports}}"])
+        .args([
+            "ps",
+            "--format",
+            "{{.ID}}\t{{.Names}}\t{{.Ports}}\t{{.Status}}",
+        ])
         .output();
     let out = match out {
         Ok(o) => o,
@@ -35,6 +39,7 @@ pub fn query_docker_port_map() -> Result<HashMap<u16, DockerContainerInfo>> {
         let id = parts[0].to_string();
         let name = parts[1].to_string();
         let ports = parts[2];
+        let health = parts.get(3).and_then(|status| parse_health_status(status));
         for seg in ports.split(',') {
             let seg = seg.trim();
             if seg.is_empty() {
@@ -52,6 +57,7 @@ pub fn query_docker_port_map() -> Result<HashMap<u16, DockerContainerInfo>> {
                         DockerContainerInfo {
                             name: name.clone(),
                             id: id.clone(),
+                            health: health.clone(),
                         },
                     );
                 }
@@ -61,6 +67,15 @@ pub fn query_docker_port_map() -> Result<HashMap<u16, DockerContainerInfo>> {
     Ok(map)
 }
 
+/// Health check status from a `docker ps` status such as "Up 5 minutes
+/// (unhealthy)" or "Up 3 seconds (health: starting)"; `None` without a check
+pub fn parse_health_status(status: &str) -> Option<String> {
+    let (_, rest) = status.rsplit_once('(')?;
+    let health = rest.strip_suffix(')')?;
+    let health = health.strip_prefix("health: ").unwrap_or(health);
+    matches!(health, "healthy" | "unhealthy" | "starting").then(|| health.to_string())
+}
+
 pub fn run_docker_stop(container: &str) -> KillFeedback {
     let res = Command::new(find_command("docker"))
         .args(["stop", container])
@@ -77,3 +92,22 @@ pub fn run_docker_stop(container: &str) -> KillFeedback {
         Err(err) => KillFeedback::error(format!("docker stop error: {}", err)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_status_from_ps_status() {
+        assert_eq!(
+            parse_health_status("Up 5 minutes (unhealthy)"),
+            Some("unhealthy".into())
+        );
+        assert_eq!(
+            parse_health_status("Up 3 seconds (health: starting)"),
+            Some("starting".into())
+        );
+        assert_eq!(parse_health_status("Up 2 hours"), None);
+        assert_eq!(parse_health_status("Up 2 hours (Paused)"), None);
+    }
+}
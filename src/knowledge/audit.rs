@@ -0,0 +1,145 @@
+//! Append-only JSON-lines log of the names the learning system assigns.
+//!
+//! Each stored result becomes one line recording what was chosen and by which
+//! source. When the file would grow past its cap it is moved aside to
+//! `<path>.1`, replacing any older rotation, and a fresh file is started.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::types::{Confidence, KnowledgeEntry, KnowledgeSource, LearningConfig, ProcessCategory};
+
+/// Size at which the audit log is rotated
+pub const AUDIT_LOG_MAX_BYTES: u64 = 1024 * 1024;
+
+/// One learning decision as written to the audit log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Unix timestamp of the decision
+    pub timestamp: i64,
+    /// Fingerprint hash key of the process
+    pub fingerprint: String,
+    pub command: String,
+    pub display_name: String,
+    pub category: ProcessCategory,
+    pub source: KnowledgeSource,
+    pub confidence: Confidence,
+}
+
+impl AuditRecord {
+    pub fn from_entry(hash_key: &str, entry: &KnowledgeEntry) -> Self {
+        Self {
+            timestamp: entry.updated_at,
+            fingerprint: hash_key.to_string(),
+            command: entry.fingerprint.command.clone(),
+            display_name: entry.display_name.clone(),
+            category: entry.category.clone(),
+            source: entry.source.clone(),
+            confidence: entry.confidence,
+        }
+    }
+}
+
+/// Size-capped JSON-lines audit log
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: AUDIT_LOG_MAX_BYTES,
+        }
+    }
+
+    /// Set the size at which the log is rotated
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Audit log configured by `audit_log_path`, if any
+    pub fn from_config(config: &LearningConfig) -> Option<Self> {
+        config.audit_log_path.as_ref().map(Self::new)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path the log is moved to when rotated
+    pub fn rotated_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".1");
+        PathBuf::from(name)
+    }
+
+    /// Append one record as a JSON line, rotating first if it would not fit
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record).context("failed to serialize audit record")?;
+        line.push('\n');
+
+        let current = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if current > 0 && current + line.len() as u64 > self.max_bytes {
+            fs::rename(&self.path, self.rotated_path())
+                .with_context(|| format!("failed to rotate {}", self.path.display()))?;
+        }
+
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(display_name: &str) -> AuditRecord {
+        AuditRecord {
+            timestamp: 1_700_000_000,
+            fingerprint: "abc123".to_string(),
+            command: "node".to_string(),
+            display_name: display_name.to_string(),
+            category: ProcessCategory::Backend,
+            source: KnowledgeSource::ApiLearned,
+            confidence: Confidence::new(0.8),
+        }
+    }
+
+    #[test]
+    fn test_rotates_when_cap_exceeded() {
+        let dir = std::env::temp_dir().join(format!("portkiller-audit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let log = AuditLog::new(dir.join("audit.jsonl")).with_max_bytes(200);
+
+        log.append(&record("First")).unwrap();
+        log.append(&record("Second")).unwrap();
+
+        let rotated = fs::read_to_string(log.rotated_path()).unwrap();
+        let current = fs::read_to_string(log.path()).unwrap();
+        assert_eq!(rotated.lines().count(), 1);
+        assert_eq!(current.lines().count(), 1);
+        let latest: AuditRecord = serde_json::from_str(current.trim_end()).unwrap();
+        assert_eq!(latest.display_name, "Second");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
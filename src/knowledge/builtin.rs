@@ -1,17 +1,63 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashSet;
 
+use super::learning::notify;
 use super::types::{
-    KnowledgeBase, KnowledgeEntry, KnowledgeSource, ProcessCategory, ProcessFingerprint,
+    Confidence, KnowledgeBase, KnowledgeEntry, KnowledgeSource, ProcessCategory,
+    ProcessFingerprint, now_timestamp,
 };
+use super::worker::KnowledgeChange;
 
-/// Populate the knowledge base with builtin entries for common processes
-pub fn populate_builtins(kb: &mut KnowledgeBase) {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs() as i64;
+/// Populate the knowledge base with builtin entries for common processes.
+///
+/// Safe to run on an existing knowledge base: missing builtins are added and
+/// builtin entries get this release's name, description, and category, while
+/// learned and user-defined entries (even ones that replaced a builtin) are
+/// left alone. Subscribers are told of each builtin added or updated.
+/// Returns whether anything changed.
+pub fn populate_builtins(kb: &mut KnowledgeBase) -> bool {
+    let builtins = builtin_entries(now_timestamp());
+    // Two builtins with the same key would silently overwrite each other
+    debug_assert!(
+        colliding_commands(&builtins).is_empty(),
+        "builtins share a hash key: {:?}",
+        colliding_commands(&builtins)
+    );
 
-    let builtins = vec![
+    let mut changes = Vec::new();
+    for builtin in builtins {
+        let key = builtin.hash_key();
+        match kb.entries.get_mut(&key) {
+            None => {
+                kb.entries.insert(key.clone(), builtin);
+                changes.push(KnowledgeChange::Inserted(key));
+            }
+            Some(existing)
+                if existing.source == KnowledgeSource::Builtin
+                    && (existing.display_name != builtin.display_name
+                        || existing.description != builtin.description
+                        || existing.category != builtin.category) =>
+            {
+                // Keep sighting history; only the builtin's definition changes
+                existing.display_name = builtin.display_name;
+                existing.description = builtin.description;
+                existing.category = builtin.category;
+                existing.updated_at = builtin.updated_at;
+                changes.push(KnowledgeChange::Updated(key));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let changed = !changes.is_empty();
+    for change in changes {
+        notify(kb, change);
+    }
+    changed
+}
+
+/// Every builtin entry, stamped with `now`
+fn builtin_entries(now: i64) -> Vec<KnowledgeEntry> {
+    vec![
         // Docker/Container tools
         builtin_entry(
             "com.docker.backend",
@@ -70,6 +116,35 @@ pub fn populate_builtins(kb: &mut KnowledgeBase) {
             ProcessCategory::Cache,
             now,
         ),
+        builtin_entry(
+            "clickhouse-server",
+            "ClickHouse",
+            "ClickHouse column-oriented analytics database",
+            ProcessCategory::Database,
+            now,
+        ),
+        builtin_entry(
+            "qdrant",
+            "Qdrant",
+            "Qdrant vector database",
+            ProcessCategory::Database,
+            now,
+        ),
+        // Search engines
+        builtin_entry(
+            "meilisearch",
+            "Meilisearch",
+            "Meilisearch full-text search engine",
+            ProcessCategory::Search,
+            now,
+        ),
+        builtin_entry(
+            "typesense-server",
+            "Typesense",
+            "Typesense typo-tolerant search engine",
+            ProcessCategory::Search,
+            now,
+        ),
         // Web servers
         builtin_entry(
             "nginx",
@@ -264,11 +339,32 @@ pub fn populate_builtins(kb: &mut KnowledgeBase) {
             ProcessCategory::DevTool,
             now,
         ),
-    ];
+    ]
+}
+
+/// Commands of entries whose hash key was already taken by an earlier entry
+fn colliding_commands(entries: &[KnowledgeEntry]) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .filter(|entry| !seen.insert(entry.hash_key()))
+        .map(|entry| entry.fingerprint.command.as_str())
+        .collect()
+}
 
-    for entry in builtins {
-        let key = entry.hash_key();
-        kb.entries.insert(key, entry);
+/// Resolve alternate spellings of a command to the name its builtin is keyed by
+pub fn canonical_command(command: &str) -> &str {
+    match command {
+        "postgresql" | "pg_ctl" | "postmaster" => "postgres",
+        "mysql" | "mariadb" | "mariadbd" => "mysqld",
+        "mongodb" => "mongod",
+        "redis" => "redis-server",
+        "clickhouse" => "clickhouse-server",
+        "typesense" => "typesense-server",
+        "rabbitmq" => "rabbitmq-server",
+        "apache" | "apache2" => "httpd",
+        "nodejs" => "node",
+        other => other,
     }
 }
 
@@ -285,9 +381,133 @@ fn builtin_entry(
         description: description.to_string(),
         category,
         group_id: None,
-        confidence: 1.0,
+        confidence: Confidence::FULL,
         source: KnowledgeSource::Builtin,
         sightings: 0,
         updated_at: timestamp,
+        first_seen: 0,
+        last_seen: 0,
+        reason: None,
+        project_dir: None,
+        category_confidence: None,
+        hidden: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_command_aliases() {
+        for alias in ["postgres", "postgresql", "pg_ctl", "postmaster"] {
+            assert_eq!(canonical_command(alias), "postgres");
+        }
+        for alias in ["mongod", "mongodb"] {
+            assert_eq!(canonical_command(alias), "mongod");
+        }
+        for alias in ["redis-server", "redis"] {
+            assert_eq!(canonical_command(alias), "redis-server");
+        }
+        for alias in ["mysqld", "mysql", "mariadbd"] {
+            assert_eq!(canonical_command(alias), "mysqld");
+        }
+        assert_eq!(canonical_command("myapp"), "myapp");
+    }
+
+    #[test]
+    fn test_builtin_keys_are_unique() {
+        let builtins = builtin_entries(0);
+        assert_eq!(colliding_commands(&builtins), Vec::<&str>::new());
+
+        // A repeated command is caught
+        let mut duplicated = builtins.clone();
+        duplicated.push(builtins[0].clone());
+        assert_eq!(
+            colliding_commands(&duplicated),
+            vec![builtins[0].fingerprint.command.as_str()]
+        );
+    }
+
+    #[test]
+    fn test_populate_builtins_updates_only_builtins() {
+        let mut kb = KnowledgeBase::default();
+        populate_builtins(&mut kb);
+        assert!(!populate_builtins(&mut kb));
+
+        let postgres = ProcessFingerprint::new("postgres").hash_key();
+        let redis = ProcessFingerprint::new("redis-server").hash_key();
+        let node = ProcessFingerprint::new("node").hash_key();
+        // An outdated builtin, a learned entry that replaced one, and a
+        // builtin added in a later release
+        let stale = kb.entries.get_mut(&postgres).unwrap();
+        stale.description = "Old description".to_string();
+        stale.sightings = 7;
+        let learned = kb.entries.get_mut(&redis).unwrap();
+        learned.source = KnowledgeSource::ApiLearned;
+        learned.display_name = "Shop Cache".to_string();
+        kb.entries.remove(&node);
+
+        let events = super::super::learning::subscribe(&mut kb);
+        assert!(populate_builtins(&mut kb));
+        assert_ne!(kb.entries[&postgres].description, "Old description");
+        assert_eq!(kb.entries[&postgres].sightings, 7);
+        assert_eq!(kb.entries[&redis].display_name, "Shop Cache");
+        assert!(kb.entries.contains_key(&node));
+
+        // Subscribers hear about the updated and restored builtins only
+        let changes: HashSet<KnowledgeChange> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                super::super::worker::KnowledgeEvent::EntryChanged(change) => Some(change),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            HashSet::from([
+                KnowledgeChange::Updated(postgres),
+                KnowledgeChange::Inserted(node),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_aliases_resolve_to_builtins() {
+        let mut kb = KnowledgeBase::default();
+        populate_builtins(&mut kb);
+        for alias in [
+            "postgresql",
+            "mongodb",
+            "redis",
+            "mariadb",
+            "apache2",
+            "clickhouse",
+            "typesense",
+        ] {
+            let fingerprint = ProcessFingerprint::new(canonical_command(alias));
+            assert!(
+                kb.entries.contains_key(&fingerprint.hash_key()),
+                "{} has no builtin",
+                alias
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_and_analytics_builtins() {
+        let mut kb = KnowledgeBase::default();
+        populate_builtins(&mut kb);
+        let cases = [
+            ("meilisearch", ProcessCategory::Search),
+            ("typesense", ProcessCategory::Search),
+            ("qdrant", ProcessCategory::Database),
+            ("clickhouse", ProcessCategory::Database),
+            ("clickhouse-server", ProcessCategory::Database),
+        ];
+        for (command, category) in cases {
+            let key = ProcessFingerprint::new(canonical_command(command)).hash_key();
+            assert_eq!(kb.entries[&key].category, category, "{}", command);
+        }
     }
 }
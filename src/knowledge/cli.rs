@@ -0,0 +1,88 @@
+//! One-shot analysis for scripts: `portkiller analyze --command node --port 3000 --cwd /x`
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+use super::context_gatherer::enrich_context;
+use super::types::{AnalysisContext, LearningConfig};
+use super::worker::analyze_now;
+
+/// Analyze the process described by `args` (everything after `analyze`) and
+/// return the response as JSON.
+///
+/// Accepts `--command <name>` (required), `--port <port>`, `--cwd <dir>` and
+/// `--pid <pid>`.
+pub fn analyze_cli(args: &[String], config: &LearningConfig) -> Result<String> {
+    let mut context = parse_args(args)?;
+    enrich_context(&mut context, &config.gatherers);
+    let (response, _) = analyze_now(config, &context);
+    serde_json::to_string(&response).context("failed to serialize analysis")
+}
+
+fn parse_args(args: &[String]) -> Result<AnalysisContext> {
+    let mut context = AnalysisContext::default();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--command" => context.command = value.clone(),
+            "--port" => context.port = Some(parse_number(flag, value)?),
+            "--cwd" => context.working_directory = Some(value.clone()),
+            "--pid" => context.pid = Some(parse_number(flag, value)?),
+            other => anyhow::bail!("unknown argument: {}", other),
+        }
+    }
+    if context.command.is_empty() {
+        anyhow::bail!("--command is required");
+    }
+    Ok(context)
+}
+
+fn parse_number<T: FromStr>(flag: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid value for {}: {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_analyze_cli_returns_json_without_ica() {
+        let config = LearningConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let output = analyze_cli(
+            &args("--command vite --port 5173 --cwd /nonexistent/shop"),
+            &config,
+        )
+        .unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(!json["display_name"].as_str().unwrap().is_empty());
+        assert_eq!(json["category"], "frontend");
+        assert_eq!(json["confidence"], 0.5);
+    }
+
+    #[test]
+    fn test_analyze_cli_rejects_bad_arguments() {
+        let config = LearningConfig::default();
+        for line in [
+            "--port 80",
+            "--command node --port x",
+            "--command",
+            "--command node --verbose 1",
+        ] {
+            assert!(analyze_cli(&args(line), &config).is_err(), "{}", line);
+        }
+    }
+}
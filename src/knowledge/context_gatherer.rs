@@ -3,87 +3,359 @@
 //! This module collects additional context about processes to help ICA
 //! provide better names and descriptions.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use super::types::AnalysisContext;
+use serde::Deserialize;
+
+use super::builtin::canonical_command;
+use super::fallback::parse_runtime_version;
+use super::project_config::enrich_from_project_config;
+use super::proxy_config::enrich_from_proxy_config;
+use super::types::{AnalysisContext, GathererFlags};
+use crate::utils::{decode_lossy, display_path, find_command, output_with_timeout};
+
+/// Longest a gatherer's external command may run before it is killed
+const SYSTEM_COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long one `brew services list` is reused for every Homebrew process
+const BREW_SERVICES_TTL: Duration = Duration::from_secs(30);
+
+/// Last `brew services list` result and when it was taken
+type CachedBrewServices = Option<(Instant, Vec<BrewService>)>;
+
+static BREW_SERVICES_CACHE: Mutex<CachedBrewServices> = Mutex::new(None);
+
+/// Runs external commands on behalf of the gatherers
+pub trait CommandRunner {
+    /// Run `program` with `args`, returning stdout if it exited successfully
+    fn run(&self, program: &str, args: &[&str]) -> Option<String>;
+
+    /// Like [`run`](Self::run), but returns stdout undecoded so paths with
+    /// non-UTF-8 bytes survive intact
+    fn run_bytes(&self, program: &str, args: &[&str]) -> Option<Vec<u8>> {
+        self.run(program, args).map(String::into_bytes)
+    }
+}
+
+/// Runs commands on the local system, bounded by a timeout
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Option<String> {
+        let stdout = self.run_bytes(program, args)?;
+        Some(decode_lossy(&stdout, &format!("{} output", program)))
+    }
+
+    fn run_bytes(&self, program: &str, args: &[&str]) -> Option<Vec<u8>> {
+        let Some(output) =
+            output_with_timeout(Command::new(program).args(args), SYSTEM_COMMAND_TIMEOUT).ok()?
+        else {
+            log::debug!("{} timed out after {:?}", program, SYSTEM_COMMAND_TIMEOUT);
+            return None;
+        };
+        if output.status.success() {
+            Some(output.stdout)
+        } else {
+            log::debug!(
+                "{} failed: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+    }
+}
 
 /// Enrich an AnalysisContext with additional system information
-pub fn enrich_context(ctx: &mut AnalysisContext) {
-    // Get process info if we have a PID
-    if let Some(pid) = ctx.pid {
-        enrich_from_pid(ctx, pid);
+pub fn enrich_context(ctx: &mut AnalysisContext, flags: &GathererFlags) {
+    enrich_context_with(ctx, flags, &SystemRunner);
+}
+
+/// Enrich an AnalysisContext, running only the enabled gatherers through `runner`
+pub fn enrich_context_with(
+    ctx: &mut AnalysisContext,
+    flags: &GathererFlags,
+    runner: &impl CommandRunner,
+) {
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::debug_span!("enrich_context", command = %ctx.command, port = ?ctx.port).entered();
+
+    // Get process info if we have a PID; the raw cwd keeps any non-UTF-8 bytes
+    let raw_cwd = ctx
+        .pid
+        .and_then(|pid| enrich_from_pid(ctx, pid, flags, runner));
+
+    if let Some(cwd) = raw_cwd.or_else(|| ctx.working_directory.clone().map(PathBuf::from)) {
+        // Project overrides take precedence over anything derived from the directory
+        enrich_from_project_config(ctx, &cwd);
+        // Detect the project framework from files in the working directory
+        enrich_from_working_directory(ctx, &cwd);
+    }
+
+    // Interpreter version from the command name or executable path
+    if flags.runtime_version && ctx.runtime_version.is_none() {
+        ctx.runtime_version = parse_runtime_version(&ctx.command, ctx.full_command.as_deref());
     }
 
     // Get macOS app metadata if we have an executable path
-    if let Some(ref path) = ctx.executable_path.clone() {
-        enrich_from_macos_app(ctx, path);
+    if flags.macos_mdls
+        && let Some(ref path) = ctx.executable_path.clone()
+    {
+        enrich_from_macos_app(ctx, path, runner);
+    }
+
+    // Get Docker container info if we have a container name or ID
+    if flags.docker_inspect {
+        if let Some(ref container) = ctx.container_name.clone() {
+            enrich_from_docker(ctx, container, runner);
+        } else if let Some(ref id) = ctx.container_id.clone() {
+            // `docker inspect` accepts ID prefixes; learn the name from it too
+            ctx.container_name = get_docker_name(runner, id);
+            enrich_from_docker(ctx, id, runner);
+        }
     }
 
-    // Get Docker container info if we have a container name
-    if let Some(ref container) = ctx.container_name.clone() {
-        enrich_from_docker(ctx, container);
+    // Match Homebrew-installed executables to the service that runs them
+    if flags.brew_services
+        && ctx.brew_service.is_none()
+        && ctx.executable_path.as_deref().is_some_and(is_homebrew_path)
+    {
+        enrich_from_brew(ctx, runner);
     }
-}
 
-/// Gather context from process ID using ps and lsof
-fn enrich_from_pid(ctx: &mut AnalysisContext, pid: u32) {
-    // Get full command line
-    if let Some(full_cmd) = get_process_command(pid) {
-        ctx.full_command = Some(full_cmd.clone());
+    // A listening ssh is a port forward; its command line names the target
+    if ctx.command == "ssh"
+        && ctx.ssh_forward.is_none()
+        && let Some(ref full_cmd) = ctx.full_command
+    {
+        ctx.ssh_forward = parse_ssh_forward(full_cmd, ctx.port);
+    }
+
+    // A listening reverse proxy is described by what it proxies to
+    if flags.proxy_config && ctx.proxy_upstreams.is_empty() {
+        enrich_from_proxy_config(ctx);
+    }
+}
 
-        // Extract executable path from full command
-        if ctx.executable_path.is_none() {
-            if let Some(path) = extract_executable_path(&full_cmd) {
-                ctx.executable_path = Some(path);
+/// Gather context from process ID using ps and lsof, returning the working
+/// directory as found if this looked it up
+fn enrich_from_pid(
+    ctx: &mut AnalysisContext,
+    pid: u32,
+    flags: &GathererFlags,
+    runner: &impl CommandRunner,
+) -> Option<PathBuf> {
+    if flags.pid_ps
+        && let Some(status) = get_process_status(runner, pid)
+    {
+        // Get full command line
+        if let Some(full_cmd) = status.command {
+            // Extract executable path from full command
+            if ctx.executable_path.is_none() {
+                if let Some(path) = extract_executable_path(&full_cmd) {
+                    ctx.executable_path = Some(path);
+                }
             }
+            ctx.full_command = Some(full_cmd);
         }
+
+        // Owner and age tell system daemons from freshly started dev servers
+        ctx.process_started_at = status.started_at;
+        ctx.process_user = Some(status.user);
+        ctx.parent_pid = status.parent_pid;
     }
 
     // Get working directory
-    if ctx.working_directory.is_none() {
-        if let Some(cwd) = get_process_cwd(pid) {
-            ctx.working_directory = Some(cwd);
-        }
+    if flags.lsof_cwd && ctx.working_directory.is_none() {
+        let cwd = get_process_cwd(runner, pid)?;
+        ctx.working_directory = Some(display_path(&cwd));
+        return Some(cwd);
     }
+    None
 }
 
-/// Get full command line for a process
-fn get_process_command(pid: u32) -> Option<String> {
-    let output = Command::new("ps")
-        .args(["-p", &pid.to_string(), "-o", "command=", "-ww"])
-        .output()
-        .ok()?;
+/// Framework detected from the project files in a directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DetectedFramework {
+    /// Short framework/language identifier (e.g., "rust")
+    pub framework: &'static str,
+    /// Package name declared by the project manifest, if any
+    pub package_name: Option<String>,
+}
 
-    if output.status.success() {
-        let cmd = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !cmd.is_empty() {
-            return Some(cmd);
+/// Detect the framework of the project rooted at `dir`
+pub fn detect_framework(dir: &Path) -> Option<DetectedFramework> {
+    let manifest = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    Some(DetectedFramework {
+        framework: "rust",
+        package_name: parse_cargo_package_name(&manifest),
+    })
+}
+
+/// Gather context from project files in the working directory
+fn enrich_from_working_directory(ctx: &mut AnalysisContext, cwd: &Path) {
+    if let Some(detected) = detect_framework(cwd) {
+        if ctx.project_name.is_none() {
+            ctx.project_name = detected.package_name;
+        }
+        ctx.detected_framework = Some(detected.framework.to_string());
+    }
+}
+
+/// Extract `name` from the `[package]` table of a Cargo.toml
+fn parse_cargo_package_name(manifest: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in manifest.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=')
+            && key.trim() == "name"
+        {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            if !value.is_empty() {
+                return Some(value.to_string());
+            }
         }
     }
     None
 }
 
+/// What one `ps` reports about a process
+#[derive(Debug, PartialEq)]
+struct ProcessStatus {
+    user: String,
+    parent_pid: Option<u32>,
+    /// Unix timestamp the process started at
+    started_at: Option<i64>,
+    /// Full command line
+    command: Option<String>,
+}
+
+/// Owner, parent PID, start time, and full command line of a process.
+/// `lstart` is printed in local time without a zone, so ps runs in UTC (and
+/// the C locale, for English month names).
+fn get_process_status(runner: &impl CommandRunner, pid: u32) -> Option<ProcessStatus> {
+    let output = runner.run(
+        "env",
+        &[
+            "TZ=UTC",
+            "LC_ALL=C",
+            "ps",
+            "-p",
+            &pid.to_string(),
+            "-o",
+            "user=,ppid=,lstart=,command=",
+            "-ww",
+        ],
+    )?;
+    parse_ps_status(&output)
+}
+
+/// Parse `user ppid lstart command` as printed by
+/// `ps -o user=,ppid=,lstart=,command=`, where `lstart` is five fields
+/// (e.g. `Fri Oct 16 01:08:26 2026`) and the command keeps its spacing
+fn parse_ps_status(output: &str) -> Option<ProcessStatus> {
+    let (user, rest) = next_field(output)?;
+    let (ppid, mut rest) = next_field(rest)?;
+    let mut lstart = Vec::with_capacity(5);
+    for _ in 0..5 {
+        let (field, remainder) = next_field(rest)?;
+        lstart.push(field);
+        rest = remainder;
+    }
+    let command = rest.trim();
+    Some(ProcessStatus {
+        user: user.to_string(),
+        parent_pid: ppid.parse().ok(),
+        started_at: parse_lstart(&lstart),
+        command: (!command.is_empty()).then(|| command.to_string()),
+    })
+}
+
+/// The first whitespace-separated field of `text` and what follows it
+fn next_field(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    Some(text.split_at(end))
+}
+
+/// Unix timestamp of a UTC `lstart`: `[weekday, month, day, hh:mm:ss, year]`
+fn parse_lstart(fields: &[&str]) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let [_, month, day, clock, year] = fields else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|name| name == month)? as i64 + 1;
+    let day: i64 = day.parse().ok()?;
+    let year: i64 = year.parse().ok()?;
+    let mut secs = 0;
+    let parts: Vec<&str> = clock.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    for part in parts {
+        secs = secs * 60 + part.parse::<i64>().ok()?;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + secs)
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's
+/// `days_from_civil`)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
 /// Get working directory for a process using lsof
-fn get_process_cwd(pid: u32) -> Option<String> {
-    let output = Command::new("lsof")
-        .args(["-p", &pid.to_string(), "-Fn"])
-        .output()
-        .ok()?;
-
-    if output.status.success() {
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        // lsof -Fn outputs: p<pid>\nf<fd>\nn<name>
-        // We look for "cwd" file descriptor
-        let mut in_cwd = false;
-        for line in output_str.lines() {
-            if line == "fcwd" {
-                in_cwd = true;
-            } else if in_cwd && line.starts_with('n') {
-                return Some(line[1..].to_string());
-            } else if line.starts_with('f') {
-                in_cwd = false;
+fn get_process_cwd(runner: &impl CommandRunner, pid: u32) -> Option<PathBuf> {
+    let output = runner.run_bytes("lsof", &["-p", &pid.to_string(), "-Fn"])?;
+    parse_lsof_cwd(&output)
+}
+
+/// Find the name of the `cwd` file descriptor in `lsof -F` output.
+///
+/// Each FD section starts with an `f<fd>` line and may carry other fields
+/// (`a` access mode, `l` lock, `t` type, ...) before its `n<name>` line, so
+/// only a name inside the `cwd` section is returned. Names are raw bytes, so
+/// the path is returned exactly as the kernel reported it.
+fn parse_lsof_cwd(output: &[u8]) -> Option<PathBuf> {
+    let mut current_fd: Option<&[u8]> = None;
+    for line in output.split(|&b| b == b'\n') {
+        let Some((&tag, value)) = line.split_first() else {
+            continue;
+        };
+        match tag {
+            // A new process set starts; no FD section is open
+            b'p' => current_fd = None,
+            b'f' => current_fd = Some(value),
+            b'n' if current_fd == Some(b"cwd") => {
+                return Some(PathBuf::from(OsStr::from_bytes(value)));
             }
+            _ => {}
         }
     }
     None
@@ -110,10 +382,14 @@ fn extract_executable_path(full_cmd: &str) -> Option<String> {
 }
 
 /// Enrich context from macOS app bundle metadata
-fn enrich_from_macos_app(ctx: &mut AnalysisContext, executable_path: &str) {
+fn enrich_from_macos_app(
+    ctx: &mut AnalysisContext,
+    executable_path: &str,
+    runner: &impl CommandRunner,
+) {
     // Check if this is a .app bundle
     if let Some(app_path) = extract_app_bundle_path(executable_path) {
-        if let Some(metadata) = get_macos_app_metadata(&app_path) {
+        if let Some(metadata) = get_macos_app_metadata(runner, &app_path) {
             ctx.macos_app_name = metadata.get("kMDItemDisplayName").cloned();
             ctx.macos_app_kind = metadata.get("kMDItemKind").cloned();
         }
@@ -133,9 +409,13 @@ fn extract_app_bundle_path(path: &str) -> Option<String> {
 }
 
 /// Get macOS app metadata using mdls
-fn get_macos_app_metadata(app_path: &str) -> Option<HashMap<String, String>> {
-    let output = Command::new("mdls")
-        .args([
+fn get_macos_app_metadata(
+    runner: &impl CommandRunner,
+    app_path: &str,
+) -> Option<HashMap<String, String>> {
+    let output_str = runner.run(
+        "mdls",
+        &[
             "-name",
             "kMDItemDisplayName",
             "-name",
@@ -143,29 +423,49 @@ fn get_macos_app_metadata(app_path: &str) -> Option<HashMap<String, String>> {
             "-name",
             "kMDItemCFBundleIdentifier",
             app_path,
-        ])
-        .output()
-        .ok()?;
+        ],
+    )?;
+    let metadata = parse_mdls_output(&output_str);
 
-    if !output.status.success() {
-        return None;
+    if metadata.is_empty() {
+        None
+    } else {
+        Some(metadata)
     }
+}
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
+/// Parse full mdls output, including array values spanning several lines.
+/// Arrays are reduced to their first element.
+fn parse_mdls_output(output: &str) -> HashMap<String, String> {
     let mut metadata = HashMap::new();
+    let mut lines = output.lines();
+
+    while let Some(line) = lines.next() {
+        // Multi-line array:
+        //   kMDItemContentTypeTree = (
+        //       "com.apple.application-bundle",
+        //       ...
+        //   )
+        if let Some(key) = line.trim_end().strip_suffix(" = (") {
+            let first = lines
+                .by_ref()
+                .map(str::trim)
+                .take_while(|item| *item != ")")
+                .filter_map(parse_mdls_value)
+                .collect::<Vec<_>>();
+            if let Some(value) = first.into_iter().next() {
+                metadata.insert(key.trim().to_string(), value);
+            }
+            continue;
+        }
 
-    for line in output_str.lines() {
         // Format: kMDItemDisplayName = "Control Center"
         if let Some((key, value)) = parse_mdls_line(line) {
             metadata.insert(key, value);
         }
     }
 
-    if metadata.is_empty() {
-        None
-    } else {
-        Some(metadata)
-    }
+    metadata
 }
 
 /// Parse a line from mdls output
@@ -178,25 +478,42 @@ fn parse_mdls_line(line: &str) -> Option<(String, String)> {
     let key = parts[0].trim().to_string();
     let value = parts[1].trim();
 
+    // Single-line array: ("com.apple.application", "public.item")
+    let value = match value.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+        Some(items) if value != "(null)" => items.split(',').find_map(parse_mdls_value)?,
+        _ => parse_mdls_value(value)?,
+    };
+
+    Some((key, value))
+}
+
+/// Parse a scalar mdls value: a quoted string, a number, or `(null)`
+fn parse_mdls_value(value: &str) -> Option<String> {
+    let value = value.trim().trim_end_matches(',').trim();
+
     // Handle "(null)" values
-    if value == "(null)" {
+    if value.is_empty() || value == "(null)" {
         return None;
     }
 
-    // Remove surrounding quotes
-    let value = if value.starts_with('"') && value.ends_with('"') {
+    // Remove surrounding quotes; numbers and dates are kept verbatim
+    let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
         value[1..value.len() - 1].to_string()
     } else {
         value.to_string()
     };
 
-    Some((key, value))
+    Some(value)
 }
 
 /// Enrich context from Docker container inspection
-fn enrich_from_docker(ctx: &mut AnalysisContext, container_name: &str) {
+fn enrich_from_docker(
+    ctx: &mut AnalysisContext,
+    container_name: &str,
+    runner: &impl CommandRunner,
+) {
     // Get Docker labels (compose info)
-    if let Some(labels) = get_docker_labels(container_name) {
+    if let Some(labels) = get_docker_labels(runner, container_name) {
         ctx.docker_service = labels.get("com.docker.compose.service").cloned();
         ctx.docker_project = labels.get("com.docker.compose.project").cloned();
 
@@ -214,75 +531,444 @@ fn enrich_from_docker(ctx: &mut AnalysisContext, container_name: &str) {
         }
     }
 
-    // Get Docker config (workdir, cmd)
-    if let Some(config) = get_docker_config(container_name) {
+    // Get Docker config (workdir, cmd, health, image digest)
+    if let Some(config) = get_docker_config(runner, container_name) {
         ctx.docker_workdir = config.workdir;
         ctx.docker_cmd = config.cmd;
+        ctx.docker_health = config.health;
+        ctx.docker_image_digest = config.image_digest;
     }
 }
 
-/// Get Docker container labels
-fn get_docker_labels(container_name: &str) -> Option<HashMap<String, String>> {
-    let output = Command::new("docker")
-        .args(["inspect", container_name, "--format", "{{json .Config.Labels}}"])
-        .output()
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
+/// Get a container's name (without the leading `/`) from its ID or ID prefix
+fn get_docker_name(runner: &impl CommandRunner, container_id: &str) -> Option<String> {
+    let output = runner.run(
+        "docker",
+        &["inspect", container_id, "--format", "{{.Name}}"],
+    )?;
+    let name = output.trim().trim_start_matches('/');
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
     }
+}
 
-    let output_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+/// Get Docker container labels
+fn get_docker_labels(
+    runner: &impl CommandRunner,
+    container_name: &str,
+) -> Option<HashMap<String, String>> {
+    let output = runner.run(
+        "docker",
+        &[
+            "inspect",
+            container_name,
+            "--format",
+            "{{json .Config.Labels}}",
+        ],
+    )?;
 
     // Parse JSON labels
-    serde_json::from_str(&output_str).ok()
+    serde_json::from_str(output.trim()).ok()
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, PartialEq)]
 struct DockerConfig {
     workdir: Option<String>,
     cmd: Option<String>,
+    health: Option<String>,
+    image_digest: Option<String>,
 }
 
-/// Get Docker container config (workdir, cmd)
-fn get_docker_config(container_name: &str) -> Option<DockerConfig> {
-    let output = Command::new("docker")
-        .args([
+/// Get Docker container config (workdir, cmd, health status, image digest)
+fn get_docker_config(runner: &impl CommandRunner, container_name: &str) -> Option<DockerConfig> {
+    let output = runner.run(
+        "docker",
+        &[
             "inspect",
             container_name,
             "--format",
-            "{{.Config.WorkingDir}}|{{.Config.Cmd}}",
-        ])
-        .output()
-        .ok()?;
+            "{{.Config.WorkingDir}}|{{json .Config.Cmd}}|\
+             {{if .State.Health}}{{.State.Health.Status}}{{end}}|{{.Image}}",
+        ],
+    )?;
+    Some(parse_docker_config(&output))
+}
 
-    if !output.status.success() {
+/// Parse `<workdir>|<json cmd array>|<health status>|<image digest>` output
+/// from `docker inspect`. The health field is empty without a health check.
+fn parse_docker_config(output: &str) -> DockerConfig {
+    let (workdir, rest) = output.trim().split_once('|').unwrap_or((output.trim(), ""));
+    // The command may contain `|` but the status and digest never do
+    let (rest, image_digest) = rest.rsplit_once('|').unwrap_or((rest, ""));
+    let (cmd_json, health) = rest.rsplit_once('|').unwrap_or((rest, ""));
+
+    let mut config = DockerConfig::default();
+
+    if !workdir.is_empty() {
+        config.workdir = Some(workdir.to_string());
+    }
+    // Cmd is `null` when the image has no default command
+    if let Ok(Some(args)) = serde_json::from_str::<Option<Vec<String>>>(cmd_json)
+        && !args.is_empty()
+    {
+        config.cmd = Some(format_command_args(&args));
+    }
+    if !health.is_empty() {
+        config.health = Some(health.to_string());
+    }
+    if !image_digest.is_empty() {
+        config.image_digest = Some(image_digest.to_string());
+    }
+
+    config
+}
+
+/// Whether an executable lives under a Homebrew prefix
+fn is_homebrew_path(path: &str) -> bool {
+    path.starts_with("/opt/homebrew/")
+        || path.starts_with("/usr/local/opt/")
+        || path.starts_with("/usr/local/Cellar/")
+}
+
+/// One entry of `brew services list --json`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct BrewService {
+    name: String,
+    #[serde(default)]
+    status: Option<String>,
+}
+
+fn enrich_from_brew(ctx: &mut AnalysisContext, runner: &impl CommandRunner) {
+    let services = cached_brew_services(&BREW_SERVICES_CACHE, runner);
+    ctx.brew_service = match_brew_service(&services, ctx).map(|service| service.name.clone());
+}
+
+/// Services from `cache` if queried within [`BREW_SERVICES_TTL`], else from
+/// a fresh `brew services list`. A failed query is cached as no services so
+/// a missing or broken brew isn't retried for every process.
+fn cached_brew_services(
+    cache: &Mutex<CachedBrewServices>,
+    runner: &impl CommandRunner,
+) -> Vec<BrewService> {
+    let Ok(mut cache) = cache.lock() else {
+        return Vec::new();
+    };
+    if let Some((queried, services)) = cache.as_ref()
+        && queried.elapsed() < BREW_SERVICES_TTL
+    {
+        return services.clone();
+    }
+    let services = runner
+        .run(find_command("brew"), &["services", "list", "--json"])
+        .map(|output| parse_brew_services(&output))
+        .unwrap_or_default();
+    *cache = Some((Instant::now(), services.clone()));
+    services
+}
+
+/// Parse `brew services list --json` output
+fn parse_brew_services(output: &str) -> Vec<BrewService> {
+    serde_json::from_str(output.trim()).unwrap_or_else(|e| {
+        log::debug!("Unparseable brew services output: {}", e);
+        Vec::new()
+    })
+}
+
+/// The running service whose keg holds the executable, or failing that,
+/// whose name (without `@version`) shares a prefix with the command
+fn match_brew_service<'a>(
+    services: &'a [BrewService],
+    ctx: &AnalysisContext,
+) -> Option<&'a BrewService> {
+    let started = || {
+        services
+            .iter()
+            .filter(|service| service.status.as_deref() == Some("started"))
+    };
+    let path = ctx.executable_path.as_deref().unwrap_or_default();
+    if let Some(service) = started().find(|service| path.contains(&format!("/{}/", service.name))) {
+        return Some(service);
+    }
+
+    let command = ctx.command.rsplit('/').next().unwrap_or_default();
+    let command = command.to_lowercase();
+    if command.is_empty() {
         return None;
     }
+    started().find(|service| {
+        let (formula, _) = service.name.split_once('@').unwrap_or((&service.name, ""));
+        let formula = formula.to_lowercase();
+        !formula.is_empty() && (formula.starts_with(&command) || command.starts_with(&formula))
+    })
+}
 
-    let output_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    let parts: Vec<&str> = output_str.splitn(2, '|').collect();
+/// Stack a process belongs to: its compose project or container prefix, else
+/// its working directory
+fn sibling_group(ctx: &AnalysisContext) -> Option<&str> {
+    ctx.docker_project
+        .as_deref()
+        .or(ctx.container_prefix.as_deref())
+        .or(ctx.working_directory.as_deref())
+}
 
-    let mut config = DockerConfig::default();
+/// How a process is listed among its siblings, e.g. "api :3000"
+fn sibling_label(ctx: &AnalysisContext) -> String {
+    let name = ctx.docker_service.as_deref().unwrap_or(&ctx.command);
+    match ctx.port {
+        Some(port) => format!("{} :{}", name, port),
+        None => name.to_string(),
+    }
+}
+
+/// Fill each context's `siblings` with the other processes of its stack (see
+/// `LearningConfig::include_siblings`). Processes without a stack get none.
+pub fn attach_siblings(contexts: &mut [AnalysisContext]) {
+    let groups: Vec<Option<String>> = contexts
+        .iter()
+        .map(|ctx| sibling_group(ctx).map(str::to_string))
+        .collect();
+    let labels: Vec<String> = contexts.iter().map(sibling_label).collect();
+
+    for (i, ctx) in contexts.iter_mut().enumerate() {
+        let Some(ref group) = groups[i] else {
+            continue;
+        };
+        ctx.siblings = (0..labels.len())
+            .filter(|&j| j != i && groups[j].as_ref() == Some(group))
+            .map(|j| labels[j].clone())
+            .filter(|label| *label != labels[i])
+            .collect();
+        ctx.siblings.sort();
+        ctx.siblings.dedup();
+    }
+}
+
+/// Group forked workers with the process that started them, using
+/// `parent_pid`.
+///
+/// A process whose parent (or a further ancestor) is also listed gets the
+/// topmost listed ancestor as `master_pid`. Processes with the same command
+/// and working directory whose common parent isn't listed get that parent
+/// only if it runs the same program (a gunicorn arbiter, a php-fpm master),
+/// never a shell or task runner that merely started them. Returns, for each
+/// context, the index of the context leading its group, whose fingerprint
+/// the whole group shares.
+pub fn group_process_tree(contexts: &mut [AnalysisContext]) -> Vec<usize> {
+    group_process_tree_with(contexts, &SystemRunner)
+}
+
+/// [`group_process_tree`], looking up unlisted parents through `runner`
+pub fn group_process_tree_with(
+    contexts: &mut [AnalysisContext],
+    runner: &impl CommandRunner,
+) -> Vec<usize> {
+    // First context of each pid; a process listening on several ports is
+    // listed once per port
+    let mut index_of: HashMap<u32, usize> = HashMap::new();
+    let mut parents: HashMap<u32, u32> = HashMap::new();
+    for (i, ctx) in contexts.iter().enumerate() {
+        if let Some(pid) = ctx.pid {
+            index_of.entry(pid).or_insert(i);
+            if let Some(parent) = ctx.parent_pid {
+                parents.insert(pid, parent);
+            }
+        }
+    }
+
+    let mut leaders: Vec<usize> = (0..contexts.len()).collect();
+    let mut masters: Vec<Option<u32>> = vec![None; contexts.len()];
+
+    for (i, ctx) in contexts.iter().enumerate() {
+        let Some(mut pid) = ctx.pid else {
+            continue;
+        };
+        // Guards against cycles from reused pids
+        let mut visited = HashSet::from([pid]);
+        while let Some(&parent) = parents.get(&pid)
+            && index_of.contains_key(&parent)
+            && visited.insert(parent)
+        {
+            masters[i] = Some(parent);
+            leaders[i] = index_of[&parent];
+            pid = parent;
+        }
+    }
+
+    let mut families: HashMap<(u32, &str, Option<&str>), Vec<usize>> = HashMap::new();
+    for (i, ctx) in contexts.iter().enumerate() {
+        if masters[i].is_none()
+            && let Some(parent) = ctx.parent_pid
+            && parent > 1
+            && !index_of.contains_key(&parent)
+        {
+            let key = (
+                parent,
+                ctx.command.as_str(),
+                ctx.working_directory.as_deref(),
+            );
+            families.entry(key).or_default().push(i);
+        }
+    }
+    for ((parent, command, _), members) in families {
+        let pids: HashSet<Option<u32>> = members.iter().map(|&i| contexts[i].pid).collect();
+        if pids.len() < 2
+            || !get_process_name(runner, parent).is_some_and(|name| same_program(&name, command))
+        {
+            continue;
+        }
+        for &i in &members {
+            masters[i] = Some(parent);
+            leaders[i] = members[0];
+        }
+    }
 
-    if parts.len() >= 1 && !parts[0].is_empty() {
-        config.workdir = Some(parts[0].to_string());
+    // Workers of a family member follow it to the family's leader
+    for i in 0..leaders.len() {
+        leaders[i] = leaders[leaders[i]];
     }
-    if parts.len() >= 2 && !parts[1].is_empty() && parts[1] != "[]" {
-        // Clean up the command array format
-        let cmd = parts[1]
-            .trim_start_matches('[')
-            .trim_end_matches(']')
-            .to_string();
-        config.cmd = Some(cmd);
+    for (ctx, master) in contexts.iter_mut().zip(masters) {
+        ctx.master_pid = master;
     }
+    leaders
+}
 
-    Some(config)
+/// Executable name of a process, from `ps -o comm=` (a full path on macOS)
+fn get_process_name(runner: &impl CommandRunner, pid: u32) -> Option<String> {
+    let output = runner.run("ps", &["-p", &pid.to_string(), "-o", "comm=", "-ww"])?;
+    let comm = output.trim();
+    let name = comm.rsplit('/').next().unwrap_or(comm);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Whether a parent's executable name and a listener's command (as listed
+/// by lsof, which truncates names to 9 characters) name the same program
+fn same_program(parent: &str, command: &str) -> bool {
+    let parent = canonical_command(parent);
+    let command = canonical_command(command);
+    parent.eq_ignore_ascii_case(command) || (command.len() >= 9 && parent.starts_with(command))
+}
+
+/// ssh options that take an argument, per ssh(1)
+const SSH_ARG_FLAGS: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// One `-L`, `-R` or `-D` forward from an ssh command line
+#[derive(Debug, PartialEq)]
+struct SshForward {
+    /// Whether the forward listens on this machine (`-L`, `-D`)
+    local: bool,
+    listen_port: Option<u16>,
+    /// `host:port` for `-L`/`-R`; "SOCKS proxy" for `-D`
+    target: String,
+}
+
+/// Target of the ssh forward listening on `port` (or the first forward when
+/// none matches), e.g. "db:5432" for `ssh -L 8080:db:5432 host`
+pub fn parse_ssh_forward(full_cmd: &str, port: Option<u16>) -> Option<String> {
+    let forwards = parse_ssh_forwards(full_cmd);
+    let listening = forwards
+        .iter()
+        .find(|forward| forward.local && port.is_some() && forward.listen_port == port);
+    listening
+        .or_else(|| forwards.first())
+        .map(|forward| forward.target.clone())
+}
+
+/// All forwards on an ssh command line. Options may be bundled (`-fNL`) and
+/// their value attached (`-L8080:db:5432`) or in the next argument.
+fn parse_ssh_forwards(full_cmd: &str) -> Vec<SshForward> {
+    let mut forwards = Vec::new();
+    let mut args = full_cmd.split_whitespace().skip(1);
+    while let Some(arg) = args.next() {
+        // Long options and the `--` separator carry no forwards
+        let Some(flags) = arg.strip_prefix('-') else {
+            continue;
+        };
+        if flags.starts_with('-') {
+            continue;
+        }
+        for (i, flag) in flags.char_indices() {
+            if !SSH_ARG_FLAGS.contains(flag) {
+                continue;
+            }
+            let attached = &flags[i + flag.len_utf8()..];
+            let value = if attached.is_empty() {
+                args.next()
+            } else {
+                Some(attached)
+            };
+            if let Some(value) = value
+                && let Some(forward) = parse_forward_spec(flag, value)
+            {
+                forwards.push(forward);
+            }
+            break;
+        }
+    }
+    forwards
+}
+
+/// Parse `[bind:]port:host:hostport` (`-L`/`-R`) or `[bind:]port` (`-D`)
+fn parse_forward_spec(flag: char, spec: &str) -> Option<SshForward> {
+    let parts = split_forward_spec(spec);
+    let port_at = |index: usize| parts.get(index).and_then(|part| part.parse().ok());
+    match (flag, parts.len()) {
+        ('L' | 'R', 3 | 4) => {
+            let n = parts.len();
+            Some(SshForward {
+                local: flag == 'L',
+                listen_port: port_at(n - 3),
+                target: format!("{}:{}", parts[n - 2], parts[n - 1]),
+            })
+        }
+        ('D', 1 | 2) => Some(SshForward {
+            local: true,
+            listen_port: port_at(parts.len() - 1),
+            target: "SOCKS proxy".to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Split a forward spec on colons outside `[...]` (IPv6 addresses)
+fn split_forward_spec(spec: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_brackets = false;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            ':' if !in_brackets => {
+                parts.push(&spec[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&spec[start..]);
+    parts
+}
+
+/// Join command arguments for display, quoting any that contain whitespace
+fn format_command_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("{:?}", arg)
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
 
     #[test]
     fn test_extract_app_bundle_path() {
@@ -316,5 +1002,653 @@ mod tests {
             Some(("kMDItemDisplayName".to_string(), "Control Center".to_string()))
         );
         assert_eq!(parse_mdls_line("kMDItemFoo = (null)"), None);
+        assert_eq!(
+            parse_mdls_line("kMDItemFSSize = 48213"),
+            Some(("kMDItemFSSize".to_string(), "48213".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_mdls_output_with_arrays() {
+        let output = "kMDItemCFBundleIdentifier = \"com.apple.controlcenter\"\n\
+                      kMDItemContentTypeTree = (\n\
+                      \x20   \"com.apple.application-bundle\",\n\
+                      \x20   \"com.apple.application\",\n\
+                      \x20   \"public.item\"\n\
+                      )\n\
+                      kMDItemDisplayName = \"Control Center\"\n\
+                      kMDItemFSSize = 48213\n\
+                      kMDItemKeywords = (null)\n\
+                      kMDItemLanguages = (\"en\", \"de\")\n";
+        let metadata = parse_mdls_output(output);
+
+        assert_eq!(
+            metadata.get("kMDItemContentTypeTree").map(String::as_str),
+            Some("com.apple.application-bundle")
+        );
+        assert_eq!(
+            metadata.get("kMDItemDisplayName").map(String::as_str),
+            Some("Control Center")
+        );
+        assert_eq!(
+            metadata
+                .get("kMDItemCFBundleIdentifier")
+                .map(String::as_str),
+            Some("com.apple.controlcenter")
+        );
+        assert_eq!(
+            metadata.get("kMDItemFSSize").map(String::as_str),
+            Some("48213")
+        );
+        assert_eq!(
+            metadata.get("kMDItemLanguages").map(String::as_str),
+            Some("en")
+        );
+        assert!(!metadata.contains_key("kMDItemKeywords"));
+    }
+
+    #[test]
+    fn test_parse_lsof_cwd() {
+        let output = "p4242\n\
+                      fcwd\n\
+                      a \n\
+                      l \n\
+                      tDIR\n\
+                      n/Users/me/projects/dss\n\
+                      ftxt\n\
+                      a \n\
+                      tREG\n\
+                      n/usr/local/bin/node\n\
+                      f20\n\
+                      au\n\
+                      tIPv4\n\
+                      nlocalhost:3000\n";
+        assert_eq!(
+            parse_lsof_cwd(output.as_bytes()),
+            Some(PathBuf::from("/Users/me/projects/dss"))
+        );
+    }
+
+    #[test]
+    fn test_parse_ssh_local_forward() {
+        assert_eq!(
+            parse_ssh_forward("ssh -L 8080:db:5432 host", Some(8080)).as_deref(),
+            Some("db:5432")
+        );
+        assert_eq!(
+            parse_ssh_forwards("/usr/bin/ssh -fNL127.0.0.1:8080:db:5432 -p 2222 host"),
+            vec![SshForward {
+                local: true,
+                listen_port: Some(8080),
+                target: "db:5432".to_string(),
+            }]
+        );
+        assert_eq!(parse_ssh_forward("ssh -p 2222 host", Some(2222)), None);
+    }
+
+    #[test]
+    fn test_parse_ssh_forward_picks_listening_port() {
+        let cmd = "ssh -R 9000:localhost:3000 -L 6000:[::1]:6379 -D 1080 bastion";
+        assert_eq!(
+            parse_ssh_forward(cmd, Some(6000)).as_deref(),
+            Some("[::1]:6379")
+        );
+        assert_eq!(
+            parse_ssh_forward(cmd, Some(1080)).as_deref(),
+            Some("SOCKS proxy")
+        );
+        // Remote forwards don't listen here; fall back to the first forward
+        assert_eq!(
+            parse_ssh_forward(cmd, Some(9000)).as_deref(),
+            Some("localhost:3000")
+        );
+    }
+
+    #[test]
+    fn test_parse_lsof_cwd_keeps_non_utf8_bytes() {
+        let output = b"p4242\nfcwd\ntDIR\nn/Users/me/caf\xe9/shop-api\nftxt\n";
+        let cwd = parse_lsof_cwd(output).unwrap();
+
+        assert_eq!(cwd.as_os_str().as_bytes(), b"/Users/me/caf\xe9/shop-api");
+        assert_eq!(cwd.file_name(), Some(OsStr::new("shop-api")));
+        // Only the display form is lossy, and only in the invalid byte
+        assert_eq!(display_path(&cwd), "/Users/me/caf\u{FFFD}/shop-api");
+    }
+
+    #[test]
+    fn test_parse_lsof_cwd_ignores_other_fd_names() {
+        // The cwd section has no name; the following txt name must not be used
+        let output = "p4242\nfcwd\ntDIR\nftxt\ntREG\nn/usr/local/bin/node\n";
+        assert_eq!(parse_lsof_cwd(output.as_bytes()), None);
+    }
+
+    #[test]
+    fn test_ensure_enriched_skips_enriched_context() {
+        // Enrichment would fill in full_command from ps for our own pid
+        let mut context = AnalysisContext {
+            command: "node".to_string(),
+            pid: Some(std::process::id()),
+            working_directory: Some("/Users/me/projects/dss".to_string()),
+            ..Default::default()
+        };
+        assert!(context.is_enriched());
+        context.ensure_enriched(&GathererFlags::default());
+        assert_eq!(context.full_command, None);
+        assert_eq!(
+            context.working_directory.as_deref(),
+            Some("/Users/me/projects/dss")
+        );
+    }
+
+    #[test]
+    fn test_bare_context_is_not_enriched() {
+        let context = AnalysisContext {
+            command: "node".to_string(),
+            port: Some(3000),
+            project_name: Some("dss".to_string()),
+            ..Default::default()
+        };
+        assert!(!context.is_enriched());
+    }
+
+    #[test]
+    fn test_parse_cargo_package_name() {
+        let manifest = r#"[workspace]
+members = ["crates/*"]
+
+[package]
+name = "myapp"
+version = "0.1.0"
+edition = "2024"
+
+[dependencies]
+name-clash = { version = "1" }
+"#;
+        assert_eq!(
+            parse_cargo_package_name(manifest),
+            Some("myapp".to_string())
+        );
+        assert_eq!(
+            parse_cargo_package_name("[workspace]\nmembers = []\n"),
+            None
+        );
+    }
+
+    /// Records every command instead of running it
+    #[derive(Default)]
+    struct RecordingRunner {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl CommandRunner for RecordingRunner {
+        fn run(&self, program: &str, _args: &[&str]) -> Option<String> {
+            self.calls.borrow_mut().push(program.to_string());
+            None
+        }
+    }
+
+    #[test]
+    fn test_disabled_docker_gatherer_skips_docker() {
+        let context = AnalysisContext {
+            command: "node".to_string(),
+            pid: Some(4242),
+            container_name: Some("dss_app".to_string()),
+            ..Default::default()
+        };
+        let flags = GathererFlags {
+            docker_inspect: false,
+            ..Default::default()
+        };
+
+        let runner = RecordingRunner::default();
+        enrich_context_with(&mut context.clone(), &flags, &runner);
+        let calls = runner.calls.borrow();
+        assert!(!calls.iter().any(|program| program == "docker"));
+        assert!(calls.iter().any(|program| program == "env"));
+
+        let runner = RecordingRunner::default();
+        enrich_context_with(&mut context.clone(), &GathererFlags::default(), &runner);
+        let calls = runner.calls.borrow();
+        assert!(calls.iter().any(|program| program == "docker"));
+    }
+
+    #[test]
+    fn test_runtime_version_gatherer_is_optional() {
+        let context = AnalysisContext::new("python3.11");
+        let runner = RecordingRunner::default();
+
+        let mut enriched = context.clone();
+        enrich_context_with(&mut enriched, &GathererFlags::default(), &runner);
+        assert_eq!(enriched.runtime_version.as_deref(), Some("3.11"));
+
+        let flags = GathererFlags {
+            runtime_version: false,
+            ..Default::default()
+        };
+        let mut enriched = context;
+        enrich_context_with(&mut enriched, &flags, &runner);
+        assert_eq!(enriched.runtime_version, None);
+    }
+
+    #[test]
+    fn test_parse_docker_config_cmd_json() {
+        let output = r#"/app|["sh","-c","node server.js --flag a,b"]"#;
+        assert_eq!(
+            parse_docker_config(output),
+            DockerConfig {
+                workdir: Some("/app".to_string()),
+                cmd: Some(r#"sh -c "node server.js --flag a,b""#.to_string()),
+                health: None,
+                image_digest: None,
+            }
+        );
+
+        let no_cmd = parse_docker_config("|null\n");
+        assert_eq!(no_cmd, DockerConfig::default());
+    }
+
+    #[test]
+    fn test_parse_docker_config_health_status() {
+        let output = r#"/app|["sh","-c","echo a | grep a"]|unhealthy|sha256:4f2a"#;
+        assert_eq!(
+            parse_docker_config(output),
+            DockerConfig {
+                workdir: Some("/app".to_string()),
+                cmd: Some(r#"sh -c "echo a | grep a""#.to_string()),
+                health: Some("unhealthy".to_string()),
+                image_digest: Some("sha256:4f2a".to_string()),
+            }
+        );
+
+        // No health check configured
+        let output = "/app|[\"nginx\"]||sha256:4f2a\n";
+        assert_eq!(parse_docker_config(output).health, None);
+        assert_eq!(parse_docker_config(output).cmd.as_deref(), Some("nginx"));
+    }
+
+    /// Replies with canned stdout for known invocations
+    #[derive(Default)]
+    struct MockRunner {
+        /// (program, substring of the joined args, stdout)
+        responses: Vec<(&'static str, &'static str, &'static str)>,
+    }
+
+    impl MockRunner {
+        fn respond(
+            mut self,
+            program: &'static str,
+            args: &'static str,
+            stdout: &'static str,
+        ) -> Self {
+            self.responses.push((program, args, stdout));
+            self
+        }
+    }
+
+    impl CommandRunner for MockRunner {
+        fn run(&self, program: &str, args: &[&str]) -> Option<String> {
+            let joined = args.join(" ");
+            self.responses
+                .iter()
+                .find(|(p, needle, _)| *p == program && joined.contains(needle))
+                .map(|(_, _, stdout)| stdout.to_string())
+        }
+    }
+
+    #[test]
+    fn test_attach_siblings_by_stack() {
+        let in_dir = |command: &str, port: u16, dir: &str| AnalysisContext {
+            port: Some(port),
+            working_directory: Some(dir.to_string()),
+            ..AnalysisContext::new(command)
+        };
+        let mut contexts = vec![
+            in_dir("node", 3000, "/srv/shop"),
+            in_dir("celery", 5555, "/srv/shop"),
+            in_dir("redis-server", 6379, "/srv/shop"),
+            in_dir("node", 4000, "/srv/blog"),
+            AnalysisContext::new("postgres"),
+        ];
+
+        attach_siblings(&mut contexts);
+
+        assert_eq!(contexts[0].siblings, ["celery :5555", "redis-server :6379"]);
+        assert_eq!(contexts[2].siblings, ["celery :5555", "node :3000"]);
+        assert!(contexts[3].siblings.is_empty());
+        assert!(contexts[4].siblings.is_empty());
+    }
+
+    #[test]
+    fn test_group_process_tree() {
+        let process = |command: &str, pid: u32, parent_pid: u32| AnalysisContext {
+            pid: Some(pid),
+            parent_pid: Some(parent_pid),
+            working_directory: Some("/srv/shop".to_string()),
+            ..AnalysisContext::new(command)
+        };
+        let mut contexts = vec![
+            // Three workers forked by an arbiter that isn't listening itself
+            process("gunicorn", 201, 200),
+            process("gunicorn", 202, 200),
+            process("gunicorn", 203, 200),
+            // A listening master, its worker, and the worker's own child
+            process("node", 300, 50),
+            process("node", 301, 300),
+            process("esbuild", 302, 301),
+            // Unrelated processes started from the same shell or by launchd
+            process("python3", 400, 50),
+            process("postgres", 500, 1),
+            process("redis-server", 501, 1),
+        ];
+        let runner = MockRunner::default().respond(
+            "ps",
+            "-p 200 -o comm=",
+            "/srv/shop/.venv/bin/gunicorn\n",
+        );
+
+        let leaders = group_process_tree_with(&mut contexts, &runner);
+
+        assert_eq!(&leaders[..3], [0, 0, 0]);
+        assert!(contexts[..3].iter().all(|ctx| ctx.master_pid == Some(200)));
+        assert_eq!(&leaders[3..6], [3, 3, 3]);
+        assert_eq!(contexts[3].master_pid, None);
+        assert_eq!(contexts[4].master_pid, Some(300));
+        assert_eq!(contexts[5].master_pid, Some(300));
+        assert_eq!(&leaders[6..], [6, 7, 8]);
+        assert!(contexts[6..].iter().all(|ctx| ctx.master_pid.is_none()));
+    }
+
+    #[test]
+    fn test_shell_is_not_master_of_its_background_jobs() {
+        let server = |pid: u32| AnalysisContext {
+            pid: Some(pid),
+            parent_pid: Some(60),
+            working_directory: Some("/srv/shop".to_string()),
+            ..AnalysisContext::new("python3")
+        };
+        // Two `python -m http.server` jobs started from the same zsh
+        let mut contexts = vec![server(601), server(602)];
+        let runner = MockRunner::default().respond("ps", "-p 60 -o comm=", "-zsh\n");
+
+        let leaders = group_process_tree_with(&mut contexts, &runner);
+
+        assert_eq!(leaders, [0, 1]);
+        assert!(contexts.iter().all(|ctx| ctx.master_pid.is_none()));
+    }
+
+    #[test]
+    fn test_same_program() {
+        assert!(same_program("gunicorn", "gunicorn"));
+        assert!(same_program("postmaster", "postgres"));
+        // lsof truncates long command names
+        assert!(same_program("php-fpm8.3", "php-fpm8."));
+        assert!(!same_program("zsh", "python3"));
+        assert!(!same_program("npm", "node"));
+        assert!(!same_program("node", "no"));
+    }
+
+    #[test]
+    fn test_parse_ps_status() {
+        assert_eq!(
+            parse_ps_status(
+                "shaun   812 Fri Oct 16 01:08:26 2026     node  server.js --port 3000\n"
+            ),
+            Some(ProcessStatus {
+                user: "shaun".to_string(),
+                parent_pid: Some(812),
+                started_at: Some(1_792_112_906),
+                command: Some("node  server.js --port 3000".to_string()),
+            })
+        );
+        // Single-digit days are space-padded
+        let status = parse_ps_status("_postgres 1 Thu Feb 29 12:00:00 2024 postgres").unwrap();
+        assert_eq!(status.started_at, Some(1_709_208_000));
+        assert_eq!(
+            parse_lstart(&["Fri", "Dec", "31", "23:59:59", "1999"]),
+            Some(946_684_799)
+        );
+
+        // A garbled start time still yields the owner
+        let status = parse_ps_status("root 1 Fri Dec 31 23:59 1999 init").unwrap();
+        assert_eq!(status.user, "root");
+        assert_eq!(status.started_at, None);
+        assert_eq!(parse_ps_status("root 1"), None);
+        assert_eq!(parse_ps_status(""), None);
+    }
+
+    #[test]
+    fn test_enrich_from_pid_with_mock_runner() {
+        let runner = MockRunner::default()
+            .respond(
+                "env",
+                "-p 4242",
+                "shaun 812 Fri Oct 16 01:08:26 2026 /usr/local/bin/node /srv/dss/server.js --port 3000\n",
+            )
+            .respond(
+                "lsof",
+                "-p 4242",
+                "p4242\nfcwd\ntDIR\nn/srv/dss\nftxt\nn/usr/local/bin/node\n",
+            );
+        let mut context = AnalysisContext {
+            command: "node".to_string(),
+            pid: Some(4242),
+            ..Default::default()
+        };
+
+        enrich_context_with(&mut context, &GathererFlags::default(), &runner);
+
+        assert_eq!(
+            context.full_command.as_deref(),
+            Some("/usr/local/bin/node /srv/dss/server.js --port 3000")
+        );
+        assert_eq!(
+            context.executable_path.as_deref(),
+            Some("/usr/local/bin/node")
+        );
+        assert_eq!(context.working_directory.as_deref(), Some("/srv/dss"));
+        assert_eq!(context.process_user.as_deref(), Some("shaun"));
+        assert_eq!(context.parent_pid, Some(812));
+        assert_eq!(context.process_started_at, Some(1_792_112_906));
+    }
+
+    #[test]
+    fn test_enrich_ssh_forward_from_command_line() {
+        let runner = MockRunner::default().respond(
+            "env",
+            "-p 77",
+            "shaun 1 Fri Oct 16 01:08:26 2026 ssh -L 8080:db:5432 host\n",
+        );
+        let mut context = AnalysisContext {
+            command: "ssh".to_string(),
+            port: Some(8080),
+            pid: Some(77),
+            ..Default::default()
+        };
+
+        enrich_context_with(&mut context, &GathererFlags::default(), &runner);
+        assert_eq!(context.ssh_forward.as_deref(), Some("db:5432"));
+    }
+
+    const BREW_SERVICES_JSON: &str = r#"[
+  {"name":"mysql","status":"none","user":null,"file":"/opt/homebrew/opt/mysql/homebrew.mxcl.mysql.plist","exit_code":null},
+  {"name":"postgresql@14","status":"started","user":"me","file":"/Users/me/Library/LaunchAgents/homebrew.mxcl.postgresql@14.plist","exit_code":0},
+  {"name":"redis","status":"started","user":"me","file":"/Users/me/Library/LaunchAgents/homebrew.mxcl.redis.plist","exit_code":0}
+]"#;
+
+    #[test]
+    fn test_parse_brew_services_json() {
+        let services = parse_brew_services(BREW_SERVICES_JSON);
+        assert_eq!(services.len(), 3);
+        assert_eq!(
+            services[1],
+            BrewService {
+                name: "postgresql@14".to_string(),
+                status: Some("started".to_string()),
+            }
+        );
+        assert!(parse_brew_services("Error: brew not found").is_empty());
+
+        // Stopped services never match, even by name
+        let mysqld = AnalysisContext::new("mysqld");
+        assert_eq!(match_brew_service(&services, &mysqld), None);
+        let redis = AnalysisContext::new("redis-server");
+        assert_eq!(
+            match_brew_service(&services, &redis).map(|s| s.name.as_str()),
+            Some("redis")
+        );
+    }
+
+    #[test]
+    fn test_brew_services_cached_between_processes() {
+        let cache = Mutex::new(None);
+        let runner = MockRunner::default().respond(
+            find_command("brew"),
+            "services list --json",
+            BREW_SERVICES_JSON,
+        );
+        let services = cached_brew_services(&cache, &runner);
+        assert!(!services.is_empty());
+
+        // Within the TTL brew isn't run again
+        assert_eq!(
+            cached_brew_services(&cache, &MockRunner::default()),
+            services
+        );
+
+        // Once it expires the next call queries brew afresh
+        if let Some((queried, _)) = cache.lock().unwrap().as_mut()
+            && let Some(expired) = queried.checked_sub(BREW_SERVICES_TTL)
+        {
+            *queried = expired;
+        }
+        assert!(cached_brew_services(&cache, &MockRunner::default()).is_empty());
+    }
+
+    #[test]
+    fn test_enrich_from_brew_with_mock_runner() {
+        let runner = MockRunner::default().respond(
+            find_command("brew"),
+            "services list --json",
+            BREW_SERVICES_JSON,
+        );
+        let mut context = AnalysisContext {
+            command: "postgres".to_string(),
+            executable_path: Some("/opt/homebrew/opt/postgresql@14/bin/postgres".to_string()),
+            ..Default::default()
+        };
+
+        enrich_context_with(&mut context, &GathererFlags::default(), &runner);
+        assert_eq!(context.brew_service.as_deref(), Some("postgresql@14"));
+
+        // Executables outside Homebrew never query brew
+        let mut context = AnalysisContext {
+            command: "postgres".to_string(),
+            executable_path: Some("/Applications/Postgres.app/bin/postgres".to_string()),
+            ..Default::default()
+        };
+        enrich_context_with(&mut context, &GathererFlags::default(), &runner);
+        assert_eq!(context.brew_service, None);
+    }
+
+    #[test]
+    fn test_enrich_from_macos_app_with_mock_runner() {
+        let runner = MockRunner::default()
+            .respond(
+                "env",
+                "-p 7",
+                "shaun 1 Fri Oct 16 01:08:26 2026 /Applications/Linear.app/Contents/MacOS/Linear\n",
+            )
+            .respond(
+                "mdls",
+                "/Applications/Linear.app",
+                "kMDItemCFBundleIdentifier = \"com.linear\"\n\
+                 kMDItemDisplayName = \"Linear\"\n\
+                 kMDItemKind = \"Application\"\n",
+            );
+        let mut context = AnalysisContext {
+            command: "Linear".to_string(),
+            pid: Some(7),
+            ..Default::default()
+        };
+        let flags = GathererFlags {
+            lsof_cwd: false,
+            ..Default::default()
+        };
+
+        enrich_context_with(&mut context, &flags, &runner);
+
+        assert_eq!(context.macos_app_name.as_deref(), Some("Linear"));
+        assert_eq!(context.macos_app_kind.as_deref(), Some("Application"));
+    }
+
+    #[test]
+    fn test_enrich_from_docker_with_mock_runner() {
+        let runner = MockRunner::default()
+            .respond(
+                "docker",
+                "{{json .Config.Labels}}",
+                r#"{"com.docker.compose.service":"api","com.docker.compose.project":"shop","org.opencontainers.image.title":"Shop API"}"#,
+            )
+            .respond(
+                "docker",
+                "{{.Config.WorkingDir}}",
+                "/app|[\"npm\",\"start\"]||sha256:4f2a\n",
+            );
+        let mut context = AnalysisContext {
+            command: "com.docker.backend".to_string(),
+            container_name: Some("shop-api-1".to_string()),
+            ..Default::default()
+        };
+
+        enrich_context_with(&mut context, &GathererFlags::default(), &runner);
+
+        assert_eq!(context.docker_service.as_deref(), Some("api"));
+        assert_eq!(context.docker_project.as_deref(), Some("shop"));
+        assert_eq!(context.docker_image.as_deref(), Some("Shop API"));
+        assert_eq!(context.docker_workdir.as_deref(), Some("/app"));
+        assert_eq!(context.docker_cmd.as_deref(), Some("npm start"));
+        assert_eq!(context.docker_image_digest.as_deref(), Some("sha256:4f2a"));
+    }
+
+    #[test]
+    fn test_enrich_from_docker_by_container_id() {
+        let runner = MockRunner::default()
+            .respond("docker", "{{.Name}}", "/shop-api-1\n")
+            .respond(
+                "docker",
+                "{{json .Config.Labels}}",
+                r#"{"com.docker.compose.service":"api","com.docker.compose.project":"shop"}"#,
+            )
+            .respond("docker", "{{.Config.WorkingDir}}", "/app|null\n");
+        let mut context = AnalysisContext {
+            command: "com.docker.backend".to_string(),
+            container_id: Some("3f2a9c".to_string()),
+            ..Default::default()
+        };
+
+        enrich_context_with(&mut context, &GathererFlags::default(), &runner);
+
+        assert_eq!(context.container_name.as_deref(), Some("shop-api-1"));
+        assert_eq!(context.docker_service.as_deref(), Some("api"));
+        assert_eq!(context.docker_project.as_deref(), Some("shop"));
+        assert_eq!(context.docker_workdir.as_deref(), Some("/app"));
+    }
+
+    #[test]
+    fn test_failed_commands_leave_context_untouched() {
+        let mut context = AnalysisContext {
+            command: "node".to_string(),
+            pid: Some(4242),
+            container_name: Some("gone".to_string()),
+            ..Default::default()
+        };
+
+        enrich_context_with(
+            &mut context,
+            &GathererFlags::default(),
+            &MockRunner::default(),
+        );
+
+        assert!(!context.is_enriched());
     }
 }
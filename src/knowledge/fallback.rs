@@ -1,69 +1,438 @@
-use super::types::{AnalysisContext, IcaAnalysisResponse, ProcessCategory};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use super::proxy_config::proxied_name;
+use super::types::{
+    AnalysisContext, Confidence, IcaAnalysisResponse, ProcessCategory, Protocol, now_timestamp,
+};
+
+/// System table of registered port names
+const ETC_SERVICES_PATH: &str = "/etc/services";
+
+/// Registered service names by port and protocol
+type PortServices = HashMap<(u16, Protocol), String>;
+
+/// Parsed `/etc/services`, read on first use
+static ETC_SERVICES: OnceLock<PortServices> = OnceLock::new();
+
+/// Processes younger than this may be short-lived, so weak guesses about
+/// them are held back
+const SHORT_LIVED_SECS: i64 = 60;
 
 /// Generate a display name from heuristics when ICA is not available
 pub fn generate_fallback(context: &AnalysisContext) -> IcaAnalysisResponse {
-    let (display_name, category, description) = analyze_context(context);
+    generate_fallback_with(context, etc_services(), now_timestamp())
+}
+
+/// [`generate_fallback`] with an explicit table of registered port names and
+/// current time
+fn generate_fallback_with(
+    context: &AnalysisContext,
+    services: &PortServices,
+    now: i64,
+) -> IcaAnalysisResponse {
+    let (display_name, category, description, reason, mut signal) =
+        analyze_context(context, services);
+
+    // A command or project is little to go on for a process that may be gone
+    // in a moment
+    if matches!(signal, Signal::Project | Signal::KnownCommand)
+        && context
+            .process_age_secs(now)
+            .is_some_and(|age| age < SHORT_LIVED_SECS)
+    {
+        signal = Signal::BareCommand;
+    }
 
     IcaAnalysisResponse {
         display_name,
         description,
         category,
-        group_hint: context.container_prefix.clone(),
-        confidence: 0.5,
+        group_hint: context
+            .project_group
+            .clone()
+            .or_else(|| context.container_prefix.clone()),
+        confidence: signal.confidence(),
+        category_confidence: None,
+        reason: Some(reason),
     }
 }
 
-fn analyze_context(context: &AnalysisContext) -> (String, ProcessCategory, String) {
-    // Try to build a nice name from available context
+/// Kind of evidence a fallback name rests on, which sets its confidence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Signal {
+    /// Display name from the macOS app bundle
+    MacosApp,
+    /// Port conventionally bound to a known service
+    WellKnownPort,
+    /// Homebrew service running the process
+    BrewService,
+    /// Target of an ssh port forward
+    SshForward,
+    /// Upstreams from a reverse proxy's config
+    ProxyUpstream,
+    /// Container name or compose service
+    Container,
+    /// Port name registered in `/etc/services`
+    RegisteredPort,
+    /// Project the process runs in
+    Project,
+    /// Command whose category is recognized
+    KnownCommand,
+    /// Nothing but an unrecognized (or missing) command
+    BareCommand,
+}
 
-    // Docker container with prefix
-    if let Some(ref prefix) = context.container_prefix {
-        let prefix_upper = capitalize_words(prefix);
-        if let Some(ref container) = context.container_name {
-            // Extract service name from container (e.g., "dss_app" -> "app")
-            let service = container
-                .strip_prefix(&format!("{}_", prefix))
-                .unwrap_or(container);
-            let service_upper = capitalize_words(service);
+impl Signal {
+    fn confidence(self) -> Confidence {
+        Confidence::new(match self {
+            Self::MacosApp => 0.7,
+            Self::WellKnownPort | Self::BrewService => 0.65,
+            Self::SshForward | Self::ProxyUpstream => 0.65,
+            Self::Container | Self::RegisteredPort => 0.6,
+            Self::Project | Self::KnownCommand => 0.5,
+            Self::BareCommand => 0.3,
+        })
+    }
+}
 
-            let category = infer_category_from_name(service);
-            let description = format!("{} {} service", prefix_upper, service);
+/// Name, category, description, reason, and the signal that decided them
+type Naming = (String, ProcessCategory, String, String, Signal);
 
-            return (format!("{} {}", prefix_upper, service_upper), category, description);
-        }
+/// Name a process from the strongest evidence in its context.
+///
+/// Evidence is tried in a fixed order and the first present decides the
+/// name, even over a later signal of higher confidence:
+///
+/// 1. Container name with its compose prefix ("Dss App")
+/// 2. Container name alone
+/// 3. No command name at all: the port or pid
+/// 4. Homebrew service ("Postgresql 14")
+/// 5. ssh forward target ("SSH tunnel -> db:5432")
+/// 6. Reverse proxy upstreams ("NGINX -> api:3000")
+/// 7. macOS app bundle name
+/// 8. Project name and command ("Shop (node)")
+/// 9. The command, or the tool an interpreter runs: by name when its
+///    category is recognized, else by well-known port, `/etc/services`
+///    entry, or bare port
+fn analyze_context(context: &AnalysisContext, services: &PortServices) -> Naming {
+    match context {
+        AnalysisContext {
+            container_prefix: Some(prefix),
+            container_name: Some(container),
+            ..
+        } => name_prefixed_container(prefix, container),
+        AnalysisContext {
+            container_name: Some(container),
+            ..
+        } => name_container(container),
+        AnalysisContext { command, .. } if command.trim().is_empty() => name_unnamed(context),
+        AnalysisContext {
+            brew_service: Some(service),
+            ..
+        } => name_brew_service(context, service),
+        AnalysisContext {
+            ssh_forward: Some(target),
+            ..
+        } => name_ssh_forward(context, target),
+        AnalysisContext {
+            proxy_upstreams, ..
+        } if !proxy_upstreams.is_empty() => name_proxy(context),
+        AnalysisContext {
+            macos_app_name: Some(app),
+            ..
+        } => name_macos_app(context, app),
+        AnalysisContext {
+            project_name: Some(project),
+            ..
+        } => name_project(context, project),
+        _ => name_command(context, services),
     }
+}
+
+/// Compose service named with its project prefix
+fn name_prefixed_container(prefix: &str, container: &str) -> Naming {
+    let prefix_upper = capitalize_words(prefix);
+    // Extract service name from container (e.g., "dss_app" -> "app")
+    let service = container
+        .strip_prefix(&format!("{}_", prefix))
+        .unwrap_or(container);
+    let service_upper = capitalize_words(service);
+
+    let category = infer_category_from_name(service);
+    let description = format!("{} {} service", prefix_upper, service);
 
-    // Container name without prefix
-    if let Some(ref container) = context.container_name {
-        let name = capitalize_words(container);
-        let category = infer_category_from_name(container);
-        let description = format!("Docker container: {}", container);
-        return (name, category, description);
+    (
+        format!("{} {}", prefix_upper, service_upper),
+        category,
+        description,
+        format!("container prefix '{}'", prefix),
+        Signal::Container,
+    )
+}
+
+/// Container name without prefix
+fn name_container(container: &str) -> Naming {
+    let name = capitalize_words(container);
+    let category = infer_category_from_name(container);
+    let description = format!("Docker container: {}", container);
+    let reason = format!("container name '{}'", container);
+    (name, category, description, reason, Signal::Container)
+}
+
+/// No command name to go on; identify it by port or pid instead
+fn name_unnamed(context: &AnalysisContext) -> Naming {
+    let (name, description) = match (context.port, context.pid) {
+        (Some(port), _) => (
+            format!("Unknown process on :{}", port),
+            format!("Unnamed process listening on port {}", port),
+        ),
+        (None, Some(pid)) => (
+            format!("Unknown process (pid {})", pid),
+            "Unnamed process".to_string(),
+        ),
+        (None, None) => ("Unknown process".to_string(), "Unnamed process".to_string()),
+    };
+    (
+        name,
+        ProcessCategory::Unknown,
+        description,
+        "empty command name".to_string(),
+        Signal::BareCommand,
+    )
+}
+
+/// Homebrew services are named after the formula (e.g. "Postgresql 14")
+fn name_brew_service(context: &AnalysisContext, service: &str) -> Naming {
+    let (formula, version) = service.split_once('@').unwrap_or((service, ""));
+    let name = match version {
+        "" => capitalize_words(formula),
+        version => format!("{} {}", capitalize_words(formula), version),
+    };
+    let category = match infer_category_from_command(formula) {
+        ProcessCategory::Unknown => infer_category_from_command(&context.command),
+        category => category,
+    };
+    let description = match context.port {
+        Some(port) => format!("Homebrew service {} listening on port {}", service, port),
+        None => format!("Homebrew service {}", service),
+    };
+    (
+        name,
+        category,
+        description,
+        format!("Homebrew service '{}'", service),
+        Signal::BrewService,
+    )
+}
+
+/// ssh port forwards are named after where they lead
+fn name_ssh_forward(context: &AnalysisContext, target: &str) -> Naming {
+    let description = match context.port {
+        Some(port) => format!("SSH port forward from port {} to {}", port, target),
+        None => format!("SSH port forward to {}", target),
+    };
+    (
+        format!("SSH tunnel -> {}", target),
+        ProcessCategory::Proxy,
+        description,
+        format!("ssh forward to '{}'", target),
+        Signal::SshForward,
+    )
+}
+
+/// Reverse proxies are named after what they proxy to
+fn name_proxy(context: &AnalysisContext) -> Naming {
+    let proxy = match context.command.as_str() {
+        "nginx" => "NGINX".to_string(),
+        command => capitalize_words(command),
+    };
+    let upstreams = context.proxy_upstreams.join(", ");
+    let description = match context.port {
+        Some(port) => format!("{} on port {} proxying to {}", proxy, port, upstreams),
+        None => format!("{} proxying to {}", proxy, upstreams),
+    };
+    (
+        proxied_name(&proxy, &context.proxy_upstreams),
+        ProcessCategory::Proxy,
+        description,
+        format!("proxy upstreams '{}'", upstreams),
+        Signal::ProxyUpstream,
+    )
+}
+
+/// macOS apps go by their bundle's display name
+fn name_macos_app(context: &AnalysisContext, app: &str) -> Naming {
+    let description = match context.port {
+        Some(port) => format!("macOS app {} listening on port {}", app, port),
+        None => format!("macOS app {}", app),
+    };
+    (
+        app.to_string(),
+        infer_category_from_command(&context.command),
+        description,
+        format!("macOS app '{}'", app),
+        Signal::MacosApp,
+    )
+}
+
+/// Project name + command
+fn name_project(context: &AnalysisContext, project: &str) -> Naming {
+    let wrapped = wrapped_tool(context);
+    let command = wrapped.as_deref().unwrap_or(&context.command);
+    let project_name = capitalize_words(project);
+    let category = infer_category_from_command(command);
+    let description = format!("{} running in project {}", command, project);
+    (
+        format!("{} ({})", project_name, command),
+        category,
+        description,
+        format!("project '{}'", project),
+        Signal::Project,
+    )
+}
+
+/// Just the command, or the port it listens on when the command says nothing
+fn name_command(context: &AnalysisContext, services: &PortServices) -> Naming {
+    // Interpreters are named after the script they run (`python -m celery`)
+    let wrapped = wrapped_tool(context);
+    let command = wrapped.as_deref().unwrap_or(&context.command);
+    let category = infer_category_from_command(command);
+
+    // Unrecognized command on a well-known port
+    if category == ProcessCategory::Unknown
+        && let Some(port) = context.port
+    {
+        let protocol = context.protocol.unwrap_or(Protocol::Tcp);
+        if let Some((service, category)) = well_known_port(port, protocol) {
+            let description = format!("{} listening on {}/{}", service, port, protocol.as_str());
+            return (
+                format!("{} ({})", service, command),
+                category,
+                description,
+                format!("well-known port {}/{}", port, protocol.as_str()),
+                Signal::WellKnownPort,
+            );
+        }
+        if let Some(service) = services.get(&(port, protocol)) {
+            let description = format!("{} listening on {}/{}", service, port, protocol.as_str());
+            return (
+                format!("{} ({})", capitalize_words(service), command),
+                infer_category_from_name(service),
+                description,
+                format!("{} entry {}/{}", ETC_SERVICES_PATH, port, protocol.as_str()),
+                Signal::RegisteredPort,
+            );
+        }
     }
 
-    // Project name + command
-    if let Some(ref project) = context.project_name {
-        let project_name = capitalize_words(project);
-        let command = &context.command;
-        let category = infer_category_from_command(command);
-        let description = format!("{} running in project {}", command, project);
+    let description = category_description(&category, command, context.port);
+
+    // Nothing identifies it; lead with the port, which is what users look for
+    if category == ProcessCategory::Unknown
+        && let Some(port) = context.port
+    {
         return (
-            format!("{} ({})", project_name, command),
+            format!("Service on :{} ({})", port, command),
             category,
             description,
+            format!("unrecognized command on port {}", port),
+            Signal::BareCommand,
         );
     }
 
-    // Just command
-    let category = infer_category_from_command(&context.command);
-    let description = format!("{} process", context.command);
+    let signal = match category {
+        ProcessCategory::Unknown => Signal::BareCommand,
+        _ => Signal::KnownCommand,
+    };
+    // A bare interpreter is named after its version when enrichment found it
+    let name = match (interpreter_of(command), &context.runtime_version) {
+        (Some(interpreter), Some(version)) if wrapped.is_none() => {
+            format!("{} {} Server", interpreter_label(interpreter), version)
+        }
+        _ => capitalize_words(command),
+    };
     (
-        capitalize_words(&context.command),
+        name,
         category,
         description,
+        format!("command '{}'", command),
+        signal,
     )
 }
 
+/// Friendly description for a command based on its inferred category
+fn category_description(category: &ProcessCategory, command: &str, port: Option<u16>) -> String {
+    let what = match category {
+        ProcessCategory::Frontend => "Frontend dev server".to_string(),
+        ProcessCategory::Backend => format!("{} backend server", command),
+        ProcessCategory::Database => "Database server".to_string(),
+        ProcessCategory::Cache => "Cache server".to_string(),
+        ProcessCategory::Search => "Search engine".to_string(),
+        ProcessCategory::Proxy => "Web server / reverse proxy".to_string(),
+        ProcessCategory::DevTool => format!("{} development tool", command),
+        ProcessCategory::Infrastructure => format!("{} infrastructure service", command),
+        ProcessCategory::Unknown => format!("{} process", command),
+    };
+    match port {
+        Some(port) => format!("{} listening on port {}", what, port),
+        None => what,
+    }
+}
+
+/// Look up the service conventionally bound to a port
+fn well_known_port(port: u16, protocol: Protocol) -> Option<(&'static str, ProcessCategory)> {
+    let service = match (port, protocol) {
+        (53, Protocol::Udp) => ("DNS Resolver", ProcessCategory::Infrastructure),
+        (123, Protocol::Udp) => ("NTP Server", ProcessCategory::Infrastructure),
+        (5353, Protocol::Udp) => ("mDNS Responder", ProcessCategory::Infrastructure),
+        (3306, Protocol::Tcp) => ("MySQL Database", ProcessCategory::Database),
+        (5432, Protocol::Tcp) => ("PostgreSQL Database", ProcessCategory::Database),
+        (5672, Protocol::Tcp) => ("RabbitMQ", ProcessCategory::Infrastructure),
+        (6333, Protocol::Tcp) => ("Qdrant", ProcessCategory::Database),
+        (6379, Protocol::Tcp) => ("Redis Cache", ProcessCategory::Cache),
+        (7700, Protocol::Tcp) => ("Meilisearch", ProcessCategory::Search),
+        (8108, Protocol::Tcp) => ("Typesense", ProcessCategory::Search),
+        (8123, Protocol::Tcp) => ("ClickHouse", ProcessCategory::Database),
+        (9200, Protocol::Tcp) => ("Elasticsearch", ProcessCategory::Search),
+        (11211, Protocol::Tcp) => ("Memcached", ProcessCategory::Cache),
+        (27017, Protocol::Tcp) => ("MongoDB", ProcessCategory::Database),
+        _ => return None,
+    };
+    Some(service)
+}
+
+/// Registered port names from `/etc/services`; empty if the file is absent
+fn etc_services() -> &'static PortServices {
+    ETC_SERVICES.get_or_init(|| {
+        std::fs::read_to_string(ETC_SERVICES_PATH)
+            .map(|content| parse_etc_services(&content))
+            .unwrap_or_default()
+    })
+}
+
+/// Parse `name port/protocol [aliases] [# comment]` lines into a port map.
+/// The first name listed for a port wins.
+fn parse_etc_services(content: &str) -> PortServices {
+    let mut services = HashMap::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(port_protocol)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some((port, protocol)) = port_protocol.split_once('/') else {
+            continue;
+        };
+        if let (Ok(port), Some(protocol)) = (port.parse(), Protocol::parse(protocol)) {
+            services
+                .entry((port, protocol))
+                .or_insert_with(|| name.to_string());
+        }
+    }
+    services
+}
+
 fn capitalize_words(s: &str) -> String {
     s.split(|c: char| c == '_' || c == '-' || c == ' ')
         .filter(|word| !word.is_empty())
@@ -78,7 +447,10 @@ fn capitalize_words(s: &str) -> String {
         .join(" ")
 }
 
-fn infer_category_from_name(name: &str) -> ProcessCategory {
+/// Guess a category from a project or container name.
+///
+/// Pure keyword heuristic: needs no knowledge base and never consults ICA.
+pub fn infer_category_from_name(name: &str) -> ProcessCategory {
     let lower = name.to_lowercase();
 
     // Database indicators
@@ -96,6 +468,11 @@ fn infer_category_from_name(name: &str) -> ProcessCategory {
         return ProcessCategory::Cache;
     }
 
+    // Search indicators
+    if lower.contains("search") || lower.contains("meili") || lower.contains("typesense") {
+        return ProcessCategory::Search;
+    }
+
     // Proxy indicators
     if lower.contains("nginx")
         || lower.contains("proxy")
@@ -137,58 +514,765 @@ fn infer_category_from_name(name: &str) -> ProcessCategory {
     ProcessCategory::Unknown
 }
 
-fn infer_category_from_command(command: &str) -> ProcessCategory {
-    let lower = command.to_lowercase();
+/// Guess a category from a command name or command line.
+///
+/// Pure keyword heuristic: needs no knowledge base and never consults ICA.
+pub fn infer_category_from_command(command: &str) -> ProcessCategory {
+    let tokens = tokenize_command(command);
 
     // Databases
-    if lower.contains("postgres")
-        || lower.contains("mysql")
-        || lower.contains("mongo")
-        || lower.contains("redis")
-    {
+    if has_token_prefix(
+        &tokens,
+        &[
+            "postgres",
+            "mysql",
+            "mongo",
+            "redis",
+            "clickhouse",
+            "qdrant",
+        ],
+    ) {
         return ProcessCategory::Database;
     }
 
+    // Search engines
+    if has_token_prefix(
+        &tokens,
+        &["meilisearch", "typesense", "elasticsearch", "opensearch"],
+    ) {
+        return ProcessCategory::Search;
+    }
+
     // Frontend tools
-    if lower.contains("vite")
-        || lower.contains("webpack")
-        || lower.contains("parcel")
-        || lower.contains("next")
-        || lower.contains("remix")
+    if has_token_prefix(&tokens, &["vite", "webpack", "parcel"])
+        || has_token(&tokens, &["next", "remix"])
     {
         return ProcessCategory::Frontend;
     }
 
     // Backend runtimes
-    if lower.contains("node")
-        || lower.contains("python")
-        || lower.contains("ruby")
-        || lower.contains("go")
-        || lower.contains("java")
-        || lower.contains("php")
-        || lower.contains("bun")
-        || lower.contains("deno")
+    if has_token_prefix(&tokens, &["node", "python", "ruby", "java", "php", "deno"])
+        || has_token(&tokens, &["go", "bun"])
     {
         return ProcessCategory::Backend;
     }
 
     // Proxies
-    if lower.contains("nginx") || lower.contains("caddy") || lower.contains("httpd") {
+    if has_token_prefix(&tokens, &["nginx", "caddy", "httpd"]) {
         return ProcessCategory::Proxy;
     }
 
     // Docker
-    if lower.contains("docker") || lower.contains("orbstack") {
+    if has_token_prefix(&tokens, &["docker", "orbstack"]) {
         return ProcessCategory::Infrastructure;
     }
 
     ProcessCategory::Unknown
 }
 
+/// Interpreters whose first script argument names the real tool
+const INTERPRETERS: &[&str] = &["python", "ruby", "node", "php"];
+/// Interpreter flags followed by a separate value
+const INTERPRETER_VALUE_FLAGS: &[&str] = &[
+    "-r",
+    "--require",
+    "--import",
+    "--loader",
+    "-W",
+    "-X",
+    "-I",
+    "-d",
+];
+/// Interpreter flags that run inline code instead of a script
+const INTERPRETER_CODE_FLAGS: &[&str] = &["-c", "-e", "--eval", "-p", "--print"];
+/// Script names too generic to identify the tool
+const GENERIC_SCRIPT_NAMES: &[&str] = &[
+    "main", "index", "app", "server", "manage", "run", "start", "__main__",
+];
+
+/// Name of the tool an interpreter is running, from the first non-flag
+/// argument of the full command line (e.g. "celery" for `python3 -m celery
+/// worker`, "next" for `node node_modules/.bin/next dev`). `None` when the
+/// command is not an interpreter or the script name is too generic.
+pub fn wrapped_tool(context: &AnalysisContext) -> Option<String> {
+    interpreter_of(&context.command)?;
+
+    let mut args = context.full_command.as_deref()?.split_whitespace().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-m" {
+            let module = args.next()?.split('.').next()?;
+            return Some(module.to_string()).filter(|m| !m.is_empty());
+        }
+        if INTERPRETER_CODE_FLAGS.contains(&arg) {
+            return None;
+        }
+        if INTERPRETER_VALUE_FLAGS.contains(&arg) {
+            args.next();
+            continue;
+        }
+        if arg.starts_with('-') || version_token(arg).is_some() {
+            continue;
+        }
+
+        let file = arg.rsplit('/').next().unwrap_or(arg);
+        let name = [".py", ".js", ".mjs", ".cjs", ".ts", ".rb", ".php"]
+            .iter()
+            .find_map(|ext| file.strip_suffix(ext))
+            .unwrap_or(file);
+        if name.is_empty() || GENERIC_SCRIPT_NAMES.contains(&name) {
+            return None;
+        }
+        return Some(name.to_string());
+    }
+    None
+}
+
+/// The interpreter a command runs (e.g. "python" for `python3.11`), if it is
+/// one of [`INTERPRETERS`]
+pub(crate) fn interpreter_of(command: &str) -> Option<&'static str> {
+    let base = command
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let name = base.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS
+        .iter()
+        .copied()
+        .find(|interpreter| *interpreter == name)
+}
+
+/// Name an interpreter is shown under
+fn interpreter_label(interpreter: &str) -> &str {
+    match interpreter {
+        "python" => "Python",
+        "ruby" => "Ruby",
+        "node" => "Node.js",
+        "php" => "PHP",
+        other => other,
+    }
+}
+
+/// Version of the interpreter `command` runs, e.g. "3.11" for `python3.11`.
+///
+/// Taken from the command name, else from the executable in `full_command`:
+/// its name, a version manager or Homebrew directory on its path
+/// (`.nvm/versions/node/v20.11.1/bin/node`, `python@3.11`), or a version
+/// argument right after it (`node v20`). `None` for other commands and
+/// interpreters of unknown version.
+pub fn parse_runtime_version(command: &str, full_command: Option<&str>) -> Option<String> {
+    let interpreter = interpreter_of(command)?;
+    if let Some(version) = suffix_version(command, interpreter) {
+        return Some(version);
+    }
+
+    let mut args = full_command?.split_whitespace();
+    let executable = args.next()?;
+    let mut components = executable.rsplit('/');
+    let name = components.next()?;
+    if interpreter_of(name) != Some(interpreter) {
+        return None;
+    }
+    suffix_version(name, interpreter)
+        .or_else(|| {
+            components
+                .find_map(|dir| suffix_version(dir, interpreter).or_else(|| version_token(dir)))
+        })
+        .or_else(|| args.next().and_then(version_token))
+}
+
+/// Version written after the interpreter's name (`python3.11`, `python@3.11`)
+fn suffix_version(name: &str, interpreter: &str) -> Option<String> {
+    let name = name.rsplit('/').next()?.to_lowercase();
+    let rest = name.strip_prefix(interpreter)?;
+    version_token(rest.trim_start_matches(['-', '@']))
+}
+
+/// A version number on its own: `v20` style (as `node --version` prints)
+/// or dotted (`3.11`); a bare number is too ambiguous
+fn version_token(token: &str) -> Option<String> {
+    let (version, prefixed) = match token.strip_prefix('v') {
+        Some(version) => (version, true),
+        None => (token, false),
+    };
+    let numeric = version.starts_with(|c: char| c.is_ascii_digit())
+        && version.ends_with(|c: char| c.is_ascii_digit())
+        && version.chars().all(|c| c.is_ascii_digit() || c == '.');
+    (numeric && (prefixed || version.contains('.'))).then(|| version.to_string())
+}
+
+/// Split a command into lowercase tokens on path separators, whitespace and
+/// common delimiters (e.g. "/usr/bin/redis-server" -> ["usr", "bin", "redis", "server"])
+pub fn tokenize_command(command: &str) -> Vec<String> {
+    command
+        .split(|c: char| {
+            c.is_whitespace() || matches!(c, '/' | '\\' | '-' | '_' | '.' | '@' | ':' | '=')
+        })
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+/// Check whether any token exactly equals one of the keywords
+pub fn has_token(tokens: &[String], keywords: &[&str]) -> bool {
+    tokens.iter().any(|t| keywords.contains(&t.as_str()))
+}
+
+/// Check whether any token starts with one of the keywords (e.g. "mongod" for "mongo")
+pub fn has_token_prefix(tokens: &[String], keywords: &[&str]) -> bool {
+    tokens
+        .iter()
+        .any(|t| keywords.iter().any(|k| t.starts_with(k)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_database_description_template() {
+        let context = AnalysisContext {
+            command: "postgres".to_string(),
+            port: Some(5433),
+            ..Default::default()
+        };
+        let result = generate_fallback(&context);
+        assert_eq!(result.category, ProcessCategory::Database);
+        assert_eq!(result.description, "Database server listening on port 5433");
+
+        let no_port = AnalysisContext::new("mongod");
+        assert_eq!(generate_fallback(&no_port).description, "Database server");
+    }
+
+    #[test]
+    fn test_unknown_description_template() {
+        let context = AnalysisContext {
+            command: "mystery".to_string(),
+            port: Some(4000),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_fallback_with(&context, &PortServices::new(), 0).description,
+            "mystery process listening on port 4000"
+        );
+    }
+
+    #[test]
+    fn test_empty_command_named_by_port() {
+        let context = AnalysisContext {
+            port: Some(7000),
+            pid: Some(412),
+            ..AnalysisContext::new("")
+        };
+        let result = generate_fallback(&context);
+        assert_eq!(result.display_name, "Unknown process on :7000");
+        assert_eq!(result.category, ProcessCategory::Unknown);
+
+        let pid_only = AnalysisContext {
+            pid: Some(412),
+            ..AnalysisContext::new(" ")
+        };
+        assert_eq!(
+            generate_fallback(&pid_only).display_name,
+            "Unknown process (pid 412)"
+        );
+    }
+
+    #[test]
+    fn test_parse_runtime_version() {
+        assert_eq!(
+            parse_runtime_version("python3.11", None).as_deref(),
+            Some("3.11")
+        );
+        assert_eq!(
+            parse_runtime_version("node", Some("node v20.11.1 server.js")).as_deref(),
+            Some("20.11.1")
+        );
+        assert_eq!(
+            parse_runtime_version(
+                "node",
+                Some("/Users/dev/.nvm/versions/node/v20.11.1/bin/node server.js")
+            )
+            .as_deref(),
+            Some("20.11.1")
+        );
+        assert_eq!(
+            parse_runtime_version(
+                "Python",
+                Some("/opt/homebrew/opt/python@3.12/bin/python3 app.py")
+            )
+            .as_deref(),
+            Some("3.12")
+        );
+        // No version, or not an interpreter
+        assert_eq!(
+            parse_runtime_version("python3", Some("python3 -m http.server")),
+            None
+        );
+        assert_eq!(parse_runtime_version("ruby", None), None);
+        assert_eq!(
+            parse_runtime_version("redis-server", Some("redis-server 7.2")),
+            None
+        );
+        assert_eq!(
+            parse_runtime_version("node", Some("/opt/app/v2/bin/nodemon")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_versioned_interpreter_name() {
+        // The version enrichment parsed, if enabled (see `GathererFlags`)
+        let versioned = |context: AnalysisContext| AnalysisContext {
+            runtime_version: parse_runtime_version(
+                &context.command,
+                context.full_command.as_deref(),
+            ),
+            ..context
+        };
+        let context = AnalysisContext {
+            port: Some(8000),
+            ..AnalysisContext::new("python3.11")
+        };
+        assert_eq!(generate_fallback(&context).display_name, "Python3.11");
+        let result = generate_fallback(&versioned(context));
+        assert_eq!(result.display_name, "Python 3.11 Server");
+        assert_eq!(result.category, ProcessCategory::Backend);
+
+        let context = AnalysisContext {
+            full_command: Some("node v20 server.js".to_string()),
+            ..AnalysisContext::new("node")
+        };
+        assert_eq!(
+            generate_fallback(&versioned(context)).display_name,
+            "Node.js 20 Server"
+        );
+
+        // Wrapped tools keep their own name; no version keeps the command
+        let context = AnalysisContext {
+            full_command: Some("python3.11 -m celery worker".to_string()),
+            ..AnalysisContext::new("python3.11")
+        };
+        assert_eq!(generate_fallback(&context).display_name, "Celery");
+        assert_eq!(
+            generate_fallback(&AnalysisContext::new("ruby")).display_name,
+            "Ruby"
+        );
+    }
+
+    #[test]
+    fn test_interpreter_named_after_wrapped_tool() {
+        let celery = AnalysisContext {
+            full_command: Some("/usr/bin/python3 -u -m celery -A shop worker".to_string()),
+            ..AnalysisContext::new("python3")
+        };
+        assert_eq!(wrapped_tool(&celery).as_deref(), Some("celery"));
+        assert_eq!(generate_fallback(&celery).display_name, "Celery");
+
+        let next = AnalysisContext {
+            full_command: Some("node node_modules/.bin/next dev".to_string()),
+            ..AnalysisContext::new("node")
+        };
+        assert_eq!(wrapped_tool(&next).as_deref(), Some("next"));
+        let response = generate_fallback(&next);
+        assert_eq!(response.display_name, "Next");
+        assert_eq!(response.category, ProcessCategory::Frontend);
+
+        // Generic entry points and inline code keep the interpreter's name
+        for full_command in ["node server.js", "python -c print(1)", "ruby"] {
+            let context = AnalysisContext {
+                full_command: Some(full_command.to_string()),
+                ..AnalysisContext::new(full_command.split(' ').next().unwrap())
+            };
+            assert_eq!(wrapped_tool(&context), None, "{}", full_command);
+        }
+        let not_interpreter = AnalysisContext {
+            full_command: Some("nodemon watch.js".to_string()),
+            ..AnalysisContext::new("nodemon")
+        };
+        assert_eq!(wrapped_tool(&not_interpreter), None);
+    }
+
+    #[test]
+    fn test_brew_service_named_after_formula() {
+        let context = AnalysisContext {
+            port: Some(5432),
+            brew_service: Some("postgresql@14".to_string()),
+            ..AnalysisContext::new("postgres")
+        };
+        let result = generate_fallback(&context);
+        assert_eq!(result.display_name, "Postgresql 14");
+        assert_eq!(result.category, ProcessCategory::Database);
+        assert_eq!(
+            result.description,
+            "Homebrew service postgresql@14 listening on port 5432"
+        );
+    }
+
+    #[test]
+    fn test_ssh_forward_named_after_target() {
+        let context = AnalysisContext {
+            port: Some(8080),
+            ssh_forward: Some("db:5432".to_string()),
+            ..AnalysisContext::new("ssh")
+        };
+        let result = generate_fallback(&context);
+        assert_eq!(result.display_name, "SSH tunnel -> db:5432");
+        assert_eq!(result.category, ProcessCategory::Proxy);
+    }
+
+    #[test]
+    fn test_reverse_proxy_named_after_upstreams() {
+        let context = AnalysisContext {
+            port: Some(8080),
+            proxy_upstreams: vec!["api:3000".to_string()],
+            ..AnalysisContext::new("nginx")
+        };
+        let result = generate_fallback(&context);
+        assert_eq!(result.display_name, "NGINX -> api:3000");
+        assert_eq!(result.category, ProcessCategory::Proxy);
+        assert_eq!(
+            result.description,
+            "NGINX on port 8080 proxying to api:3000"
+        );
+    }
+
+    #[test]
+    fn test_confidence_follows_strongest_signal() {
+        let confidence = |context: AnalysisContext| {
+            generate_fallback_with(&context, &PortServices::new(), 0).confidence
+        };
+
+        let app = AnalysisContext {
+            macos_app_name: Some("Control Center".to_string()),
+            ..AnalysisContext::new("ControlCe")
+        };
+        assert_eq!(confidence(app.clone()), 0.7);
+        assert_eq!(generate_fallback(&app).display_name, "Control Center");
+
+        let postgres_port = AnalysisContext {
+            port: Some(5432),
+            ..AnalysisContext::new("mystery")
+        };
+        assert_eq!(confidence(postgres_port), 0.65);
+
+        let container = AnalysisContext {
+            container_name: Some("dss_app".to_string()),
+            container_prefix: Some("dss".to_string()),
+            ..AnalysisContext::new("node")
+        };
+        assert_eq!(confidence(container), 0.6);
+
+        assert_eq!(confidence(AnalysisContext::new("postgres")), 0.5);
+        assert_eq!(confidence(AnalysisContext::new("mystery")), 0.3);
+        let unknown_port = AnalysisContext {
+            port: Some(4000),
+            ..AnalysisContext::new("mystery")
+        };
+        assert_eq!(confidence(unknown_port), 0.3);
+        assert_eq!(confidence(AnalysisContext::new("")), 0.3);
+    }
+
+    fn on_port(command: &str, port: u16) -> AnalysisContext {
+        AnalysisContext {
+            port: Some(port),
+            ..AnalysisContext::new(command)
+        }
+    }
+
+    /// Name, category, confidence, and reason expected for a context
+    type Expected = (&'static str, ProcessCategory, f32, &'static str);
+
+    /// Clears the evidence that decided the previous name
+    type RemoveWinner = fn(&mut AnalysisContext);
+
+    fn assert_naming(context: &AnalysisContext, services: &PortServices, expected: Expected) {
+        let (name, category, confidence, reason) = expected;
+        let result = generate_fallback_with(context, services, 1_700_000_000);
+        assert_eq!(result.display_name, name, "{:?}", context);
+        assert_eq!(result.category, category, "{}", name);
+        assert_eq!(result.confidence, confidence, "{}", name);
+        assert_eq!(result.reason.as_deref(), Some(reason), "{}", name);
+    }
+
+    #[test]
+    fn test_signal_priority_order() {
+        use ProcessCategory::*;
+
+        // Every signal at once; each step removes the one that just won,
+        // walking down the priority list
+        let mut context = AnalysisContext {
+            port: Some(8080),
+            pid: Some(42),
+            container_prefix: Some("dss".to_string()),
+            container_name: Some("dss_api".to_string()),
+            brew_service: Some("nginx".to_string()),
+            ssh_forward: Some("db:5432".to_string()),
+            proxy_upstreams: vec!["api:3000".to_string()],
+            macos_app_name: Some("Shop Desktop".to_string()),
+            project_name: Some("shop".to_string()),
+            ..AnalysisContext::new("nginx")
+        };
+        let steps: [(Expected, RemoveWinner); 8] = [
+            (("Dss Api", Backend, 0.6, "container prefix 'dss'"), |c| {
+                c.container_prefix = None
+            }),
+            (("Dss Api", Backend, 0.6, "container name 'dss_api'"), |c| {
+                c.container_name = None
+            }),
+            (("Nginx", Proxy, 0.65, "Homebrew service 'nginx'"), |c| {
+                c.brew_service = None
+            }),
+            (
+                (
+                    "SSH tunnel -> db:5432",
+                    Proxy,
+                    0.65,
+                    "ssh forward to 'db:5432'",
+                ),
+                |c| c.ssh_forward = None,
+            ),
+            (
+                (
+                    "NGINX -> api:3000",
+                    Proxy,
+                    0.65,
+                    "proxy upstreams 'api:3000'",
+                ),
+                |c| c.proxy_upstreams.clear(),
+            ),
+            (
+                ("Shop Desktop", Proxy, 0.7, "macOS app 'Shop Desktop'"),
+                |c| c.macos_app_name = None,
+            ),
+            (("Shop (nginx)", Proxy, 0.5, "project 'shop'"), |c| {
+                c.project_name = None
+            }),
+            (("Nginx", Proxy, 0.5, "command 'nginx'"), |_| {}),
+        ];
+        for (expected, remove_winner) in steps {
+            assert_naming(&context, &PortServices::new(), expected);
+            remove_winner(&mut context);
+        }
+
+        // A missing command outranks everything but containers
+        let unnamed = AnalysisContext {
+            port: Some(8080),
+            brew_service: Some("nginx".to_string()),
+            macos_app_name: Some("Shop Desktop".to_string()),
+            ..AnalysisContext::new(" ")
+        };
+        let expected = (
+            "Unknown process on :8080",
+            Unknown,
+            0.3,
+            "empty command name",
+        );
+        assert_naming(&unnamed, &PortServices::new(), expected);
+        let unnamed = AnalysisContext {
+            pid: Some(42),
+            ..AnalysisContext::new("")
+        };
+        let expected = (
+            "Unknown process (pid 42)",
+            Unknown,
+            0.3,
+            "empty command name",
+        );
+        assert_naming(&unnamed, &PortServices::new(), expected);
+
+        // A prefix without a container name says nothing
+        let prefix_only = AnalysisContext {
+            container_prefix: Some("dss".to_string()),
+            ..AnalysisContext::new("postgres")
+        };
+        let expected = ("Postgres", Database, 0.5, "command 'postgres'");
+        assert_naming(&prefix_only, &PortServices::new(), expected);
+    }
+
+    #[test]
+    fn test_command_signal_matrix() {
+        use ProcessCategory::*;
+        let mut services = PortServices::new();
+        services.insert((4242, Protocol::Tcp), "shop-sync".to_string());
+        services.insert((5432, Protocol::Tcp), "postgresql".to_string());
+
+        let cases: Vec<(AnalysisContext, Expected)> = vec![
+            // Unrecognized commands: well-known port, then /etc/services,
+            // then the bare port
+            (
+                on_port("mystery", 5432),
+                (
+                    "PostgreSQL Database (mystery)",
+                    Database,
+                    0.65,
+                    "well-known port 5432/TCP",
+                ),
+            ),
+            (
+                on_port("mystery", 4242),
+                (
+                    "Shop Sync (mystery)",
+                    Unknown,
+                    0.6,
+                    "/etc/services entry 4242/TCP",
+                ),
+            ),
+            (
+                on_port("mystery", 4243),
+                (
+                    "Service on :4243 (mystery)",
+                    Unknown,
+                    0.3,
+                    "unrecognized command on port 4243",
+                ),
+            ),
+            (
+                AnalysisContext::new("mystery"),
+                ("Mystery", Unknown, 0.3, "command 'mystery'"),
+            ),
+            // A recognized command ignores its port
+            (
+                on_port("postgres", 6379),
+                ("Postgres", Database, 0.5, "command 'postgres'"),
+            ),
+            // Interpreters: the tool they run, else their version
+            (
+                AnalysisContext {
+                    full_command: Some("node node_modules/.bin/vite --port 5173".to_string()),
+                    runtime_version: Some("20".to_string()),
+                    ..AnalysisContext::new("node")
+                },
+                ("Vite", Frontend, 0.5, "command 'vite'"),
+            ),
+            (
+                AnalysisContext {
+                    runtime_version: Some("3.11".to_string()),
+                    ..on_port("python3.11", 8000)
+                },
+                ("Python 3.11 Server", Backend, 0.5, "command 'python3.11'"),
+            ),
+            (
+                AnalysisContext {
+                    runtime_version: Some("20".to_string()),
+                    ..AnalysisContext::new("node")
+                },
+                ("Node.js 20 Server", Backend, 0.5, "command 'node'"),
+            ),
+            // Weak signals of a process that just started are held back
+            (
+                AnalysisContext {
+                    process_started_at: Some(1_700_000_000 - 5),
+                    ..AnalysisContext::new("postgres")
+                },
+                ("Postgres", Database, 0.3, "command 'postgres'"),
+            ),
+            (
+                AnalysisContext {
+                    process_started_at: Some(1_700_000_000 - 5),
+                    ..on_port("mystery", 5432)
+                },
+                (
+                    "PostgreSQL Database (mystery)",
+                    Database,
+                    0.65,
+                    "well-known port 5432/TCP",
+                ),
+            ),
+        ];
+        for (context, expected) in cases {
+            assert_naming(&context, &services, expected);
+        }
+    }
+
+    #[test]
+    fn test_short_lived_processes_get_low_confidence() {
+        let now = 1_700_000_000;
+        let fallback = |started_at: i64, context: &AnalysisContext| {
+            let context = AnalysisContext {
+                process_started_at: Some(started_at),
+                ..context.clone()
+            };
+            generate_fallback_with(&context, &PortServices::new(), now).confidence
+        };
+
+        let project = AnalysisContext {
+            project_name: Some("dss".to_string()),
+            ..AnalysisContext::new("node")
+        };
+        assert_eq!(fallback(now - 5, &project), 0.3);
+        assert_eq!(fallback(now - 3600, &project), 0.5);
+        assert_eq!(fallback(now - 5, &AnalysisContext::new("postgres")), 0.3);
+
+        // Strong signals don't depend on how long the process has run
+        let postgres_port = AnalysisContext {
+            port: Some(5432),
+            ..AnalysisContext::new("mystery")
+        };
+        assert_eq!(fallback(now - 5, &postgres_port), 0.65);
+    }
+
+    #[test]
+    fn test_parse_etc_services() {
+        let content = "\
+# Network services, Internet style
+#
+http-alt        8080/tcp     webcache   # HTTP Alternate
+http-alt        8080/udp
+postgresql      5432/udp    # PostgreSQL Database
+postgresql      5432/tcp
+memcache        11211/tcp   memcached
+webcache        8080/tcp
+broken          notaport/tcp
+sctp-only       9999/sctp
+";
+        let services = parse_etc_services(content);
+        assert_eq!(services.len(), 5);
+        assert_eq!(
+            services.get(&(8080, Protocol::Tcp)).map(String::as_str),
+            Some("http-alt")
+        );
+        assert_eq!(
+            services.get(&(5432, Protocol::Tcp)).map(String::as_str),
+            Some("postgresql")
+        );
+        assert_eq!(
+            services.get(&(11211, Protocol::Tcp)).map(String::as_str),
+            Some("memcache")
+        );
+        assert!(parse_etc_services("").is_empty());
+    }
+
+    #[test]
+    fn test_unknown_command_named_by_port() {
+        let context = AnalysisContext {
+            command: "mystery".to_string(),
+            port: Some(8080),
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_fallback_with(&context, &PortServices::new(), 0).display_name,
+            "Service on :8080 (mystery)"
+        );
+
+        // A registered port name is used when nothing better exists
+        let services = parse_etc_services("http-alt 8080/tcp webcache\n");
+        let result = generate_fallback_with(&context, &services, 0);
+        assert_eq!(result.display_name, "Http Alt (mystery)");
+        assert_eq!(
+            result.reason.as_deref(),
+            Some("/etc/services entry 8080/TCP")
+        );
+
+        // Recognized commands keep their own name
+        let node = AnalysisContext {
+            command: "node".to_string(),
+            port: Some(8080),
+            ..Default::default()
+        };
+        assert_eq!(generate_fallback(&node).display_name, "Node");
+    }
+
     #[test]
     fn test_capitalize_words() {
         assert_eq!(capitalize_words("hello_world"), "Hello World");
@@ -221,4 +1305,57 @@ mod tests {
         let result = generate_fallback(&context);
         assert!(result.display_name.contains("My Project"));
     }
+
+    #[test]
+    fn test_well_known_port_respects_protocol() {
+        let udp = AnalysisContext {
+            command: "mystery".to_string(),
+            port: Some(53),
+            protocol: Some(Protocol::Udp),
+            ..Default::default()
+        };
+        let result = generate_fallback(&udp);
+        assert!(result.display_name.contains("DNS"));
+        assert_eq!(result.category, ProcessCategory::Infrastructure);
+
+        let tcp = AnalysisContext {
+            protocol: Some(Protocol::Tcp),
+            ..udp
+        };
+        let result = generate_fallback(&tcp);
+        assert!(!result.display_name.contains("DNS"));
+        assert_eq!(result.category, ProcessCategory::Unknown);
+    }
+
+    #[test]
+    fn test_tokenize_command() {
+        assert_eq!(
+            tokenize_command("/usr/local/bin/redis-server *:6379"),
+            vec!["usr", "local", "bin", "redis", "server", "*", "6379"]
+        );
+        assert_eq!(tokenize_command("Python3.11"), vec!["python3", "11"]);
+        assert!(tokenize_command("").is_empty());
+    }
+
+    #[test]
+    fn test_category_matches_whole_tokens() {
+        assert_eq!(
+            infer_category_from_command("mongod"),
+            ProcessCategory::Database
+        );
+        assert_eq!(
+            infer_category_from_command("com.docker.backend"),
+            ProcessCategory::Infrastructure
+        );
+        assert_eq!(infer_category_from_command("go"), ProcessCategory::Backend);
+        // Substrings inside unrelated words no longer match
+        assert_eq!(
+            infer_category_from_command("gopher"),
+            ProcessCategory::Unknown
+        );
+        assert_eq!(
+            infer_category_from_command("cargo"),
+            ProcessCategory::Unknown
+        );
+    }
 }
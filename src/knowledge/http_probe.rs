@@ -0,0 +1,161 @@
+//! Optional HTTP probe for listening processes.
+//!
+//! Many dev servers announce themselves through a `Server` header or a
+//! health endpoint returning `{"service": "..."}`. Probing is opt-in and
+//! bounded by a hard deadline so it can run on the learning worker without
+//! ever stalling it for long.
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use super::types::AnalysisContext;
+
+/// Paths tried in order; the first response that identifies anything wins
+const PROBE_PATHS: &[&str] = &["/health", "/"];
+/// Maximum bytes read from a probe response
+const MAX_RESPONSE_BYTES: usize = 16 * 1024;
+/// JSON body keys that commonly carry a service identifier
+const SERVICE_KEYS: &[&str] = &["service", "service_name", "serviceName", "name", "app"];
+
+/// What an HTTP probe revealed about a listening process
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpProbe {
+    /// Value of the `Server` response header
+    pub server_header: Option<String>,
+    /// Service identifier found in a JSON response body
+    pub service_name: Option<String>,
+}
+
+impl HttpProbe {
+    fn is_empty(&self) -> bool {
+        self.server_header.is_none() && self.service_name.is_none()
+    }
+}
+
+/// Probe `localhost:<port>` over HTTP, spending at most `timeout` per path
+pub fn probe_http(port: u16, timeout: Duration) -> Option<HttpProbe> {
+    PROBE_PATHS
+        .iter()
+        .filter_map(|path| probe_path(port, path, timeout))
+        .find(|probe| !probe.is_empty())
+}
+
+/// Probe the context's port and record anything found in the context
+pub fn enrich_from_http(ctx: &mut AnalysisContext, timeout: Duration) {
+    let Some(port) = ctx.port else {
+        return;
+    };
+    if let Some(probe) = probe_http(port, timeout) {
+        ctx.http_server_header = probe.server_header;
+        ctx.http_service_name = probe.service_name;
+    }
+}
+
+fn probe_path(port: u16, path: &str, timeout: Duration) -> Option<HttpProbe> {
+    let deadline = Instant::now() + timeout;
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: localhost:{}\r\nUser-Agent: portkiller\r\nAccept: application/json, */*\r\nConnection: close\r\n\r\n",
+        path, port
+    );
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 4096];
+    while response.len() < MAX_RESPONSE_BYTES {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        stream.set_read_timeout(Some(remaining)).ok()?;
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => response.extend_from_slice(&buf[..n]),
+        }
+    }
+    response.truncate(MAX_RESPONSE_BYTES);
+
+    parse_http_response(&String::from_utf8_lossy(&response))
+}
+
+/// Extract identifying details from a raw HTTP response
+fn parse_http_response(response: &str) -> Option<HttpProbe> {
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((response, ""));
+    let mut lines = head.lines();
+    if !lines.next()?.starts_with("HTTP/") {
+        return None;
+    }
+
+    let server_header = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("server"))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+
+    Some(HttpProbe {
+        server_header,
+        service_name: parse_service_name(body),
+    })
+}
+
+/// Look for an obvious service identifier in a JSON body
+fn parse_service_name(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body.trim()).ok()?;
+    let object = value.as_object()?;
+    SERVICE_KEYS
+        .iter()
+        .filter_map(|key| object.get(*key)?.as_str())
+        .map(str::trim)
+        .find(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve one canned response per accepted connection
+    fn mock_server(response: &'static str, connections: usize) -> u16 {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn test_probe_reads_server_header_and_service() {
+        let port = mock_server(
+            "HTTP/1.1 200 OK\r\nServer: uvicorn\r\nContent-Type: application/json\r\n\r\n{\"status\":\"ok\",\"service\":\"billing-api\"}",
+            1,
+        );
+        let probe = probe_http(port, Duration::from_secs(2)).unwrap();
+        assert_eq!(probe.server_header.as_deref(), Some("uvicorn"));
+        assert_eq!(probe.service_name.as_deref(), Some("billing-api"));
+    }
+
+    #[test]
+    fn test_probe_gives_up_on_closed_port() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        assert_eq!(probe_http(port, Duration::from_millis(200)), None);
+    }
+
+    #[test]
+    fn test_parse_non_http_response() {
+        assert_eq!(parse_http_response("+OK redis\r\n"), None);
+    }
+}
@@ -1,45 +1,299 @@
+use std::collections::HashMap;
 use std::process::Command;
-use std::sync::OnceLock;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ClientConfig, RootCertStore};
 use serde::{Deserialize, Serialize};
 
-use super::types::{AnalysisContext, IcaAnalysisResponse, LearningConfig};
-
-static SERVICE_KEY: OnceLock<Option<String>> = OnceLock::new();
-
-/// Get the ICA service key from setec
-fn get_service_key(setec_url: &str) -> Option<String> {
-    SERVICE_KEY
-        .get_or_init(|| {
-            let output = Command::new("setec")
-                .args(["-s", setec_url, "get", "ica/service-key"])
-                .output()
-                .ok()?;
-
-            if output.status.success() {
-                let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                if !key.is_empty() {
-                    log::info!("Retrieved ICA service key from setec");
-                    Some(key)
-                } else {
-                    log::warn!("ICA service key from setec is empty");
-                    None
-                }
-            } else {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                log::warn!("Failed to get ICA service key from setec: {}", stderr);
-                None
+use super::json_extract::extract_first_json_object;
+use super::redaction::PromptRedaction;
+use super::types::{
+    AnalysisContext, DEFAULT_SERVICE_NAME, IcaAnalysisResponse, LearningConfig, now_timestamp,
+};
+use super::worker::{PromptHistory, PromptRecord};
+use crate::utils::decode_lossy;
+
+static SERVICE_KEYS: OnceLock<Mutex<ServiceKeyCache>> = OnceLock::new();
+
+/// How long a failed service key lookup is remembered before setec is asked again
+const SERVICE_KEY_RETRY_INTERVAL: Duration = Duration::from_secs(3 * 60);
+
+/// Service keys by setec program, server, and secret, so a config reload
+/// pointing at another setec isn't answered from the old one. Keys are cached
+/// for the life of the process; failures only until the retry interval
+/// passes, so a setec outage recovers without a restart.
+struct ServiceKeyCache {
+    retry_interval: Duration,
+    entries: HashMap<(String, String, String), CachedKey>,
+}
+
+enum CachedKey {
+    Found(String),
+    Missing { checked_at: Instant },
+}
+
+impl ServiceKeyCache {
+    fn new(retry_interval: Duration) -> Self {
+        Self {
+            retry_interval,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Cached key for `command`, calling `fetch` when there is none or the
+    /// last failure is older than the retry interval
+    fn get(
+        &mut self,
+        command: &SetecCommand,
+        now: Instant,
+        fetch: impl FnOnce() -> Option<String>,
+    ) -> Option<String> {
+        let source = (
+            command.program.clone(),
+            command.url.clone(),
+            command.secret.clone(),
+        );
+        match self.entries.get(&source) {
+            Some(CachedKey::Found(key)) => return Some(key.clone()),
+            Some(CachedKey::Missing { checked_at })
+                if now.saturating_duration_since(*checked_at) < self.retry_interval =>
+            {
+                return None;
             }
-        })
-        .clone()
+            _ => {}
+        }
+
+        let key = fetch();
+        let entry = match key {
+            Some(ref key) => CachedKey::Found(key.clone()),
+            None => CachedKey::Missing { checked_at: now },
+        };
+        self.entries.insert(source, entry);
+        key
+    }
+}
+
+/// Setec secret path holding the service key for a given ICA service name
+fn service_key_path(service_name: &str) -> String {
+    if service_name == DEFAULT_SERVICE_NAME {
+        "ica/service-key".to_string()
+    } else {
+        format!("ica/{}/service-key", service_name)
+    }
+}
+
+/// How to invoke setec for the service key:
+/// `<program> -s <url> <extra args...> get <secret>`
+#[derive(Clone, Debug, PartialEq)]
+struct SetecCommand {
+    program: String,
+    url: String,
+    secret: String,
+    extra_args: Vec<String>,
+}
+
+impl SetecCommand {
+    fn from_config(config: &LearningConfig) -> Self {
+        Self {
+            program: config.setec_path.clone(),
+            url: config.setec_url.clone(),
+            secret: config
+                .setec_secret
+                .clone()
+                .unwrap_or_else(|| service_key_path(&config.service_name)),
+            extra_args: config.setec_args.clone(),
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = vec!["-s".to_string(), self.url.clone()];
+        args.extend(self.extra_args.iter().cloned());
+        args.extend(["get".to_string(), self.secret.clone()]);
+        args
+    }
+}
+
+/// Fetches secrets from setec
+trait SetecRunner {
+    fn get(&self, command: &SetecCommand) -> Option<String>;
+}
+
+/// Runs the `setec` CLI
+struct SetecCli;
+
+impl SetecRunner for SetecCli {
+    fn get(&self, command: &SetecCommand) -> Option<String> {
+        fetch_service_key(command)
+    }
+}
+
+/// Get the ICA service key from the `key_env` environment variable, falling
+/// back to setec
+fn get_service_key(setec: &SetecCommand, key_env: &str) -> Option<String> {
+    resolve_service_key(env_service_key(key_env), setec, &SetecCli)
+}
+
+/// Non-empty value of the environment variable `name` (empty name disables)
+fn env_service_key(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return None;
+    }
+    let key = std::env::var(name).ok()?.trim().to_string();
+    (!key.is_empty()).then_some(key)
+}
+
+/// Use `from_env` if set; otherwise ask setec, through the shared cache
+fn resolve_service_key(
+    from_env: Option<String>,
+    command: &SetecCommand,
+    setec: &impl SetecRunner,
+) -> Option<String> {
+    if from_env.is_some() {
+        return from_env;
+    }
+    let cache =
+        SERVICE_KEYS.get_or_init(|| Mutex::new(ServiceKeyCache::new(SERVICE_KEY_RETRY_INTERVAL)));
+    // Held across the fetch so concurrent callers don't all shell out to setec
+    match cache.lock() {
+        Ok(mut cache) => cache.get(command, Instant::now(), || setec.get(command)),
+        Err(_) => setec.get(command),
+    }
+}
+
+fn fetch_service_key(command: &SetecCommand) -> Option<String> {
+    let key_path = &command.secret;
+    let output = Command::new(&command.program)
+        .args(command.args())
+        .output()
+        .map_err(|e| log::warn!("Failed to run {}: {}", command.program, e))
+        .ok()?;
+
+    if output.status.success() {
+        // A mangled key would only fail later with a confusing auth error
+        let Ok(key) = String::from_utf8(output.stdout) else {
+            log::warn!("ICA service key {} from setec is not valid UTF-8", key_path);
+            return None;
+        };
+        let key = key.trim().to_string();
+        if !key.is_empty() {
+            log::info!("Retrieved ICA service key {} from setec", key_path);
+            Some(key)
+        } else {
+            log::warn!("ICA service key {} from setec is empty", key_path);
+            None
+        }
+    } else {
+        let stderr = decode_lossy(&output.stderr, "setec stderr");
+        log::warn!("Failed to get ICA service key {} from setec: {}", key_path, stderr);
+        None
+    }
+}
+
+/// Anything ICA request headers can be applied to
+trait HeaderSink: Sized {
+    fn header(self, name: &str, value: &str) -> Self;
+}
+
+impl HeaderSink for ureq::Request {
+    fn header(self, name: &str, value: &str) -> Self {
+        self.set(name, value)
+    }
+}
+
+/// Apply the headers every ICA request needs
+fn apply_headers<R: HeaderSink>(request: R, service_key: &str, service_name: &str) -> R {
+    request
+        .header("Content-Type", "application/json")
+        .header("X-ICA-Service-Key", service_key)
+        .header("X-ICA-Service-Name", service_name)
+}
+
+/// Placeholder substituted for the service key in logs and prompt history
+const REDACTED: &str = "[REDACTED]";
+
+/// Replace every occurrence of the service key
+fn redact(text: &str, service_key: &str) -> String {
+    if service_key.is_empty() {
+        text.to_string()
+    } else {
+        text.replace(service_key, REDACTED)
+    }
+}
+
+/// Build a rustls config for a private CA and/or client certificate.
+/// Returns `None` when no TLS paths are configured (ureq defaults apply).
+fn build_tls_config(config: &LearningConfig) -> Result<Option<Arc<ClientConfig>>> {
+    if config.client_cert_path.is_none()
+        && config.client_key_path.is_none()
+        && config.ca_bundle_path.is_none()
+    {
+        return Ok(None);
+    }
+
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    if let Some(ref ca_path) = config.ca_bundle_path {
+        let certs = CertificateDer::pem_file_iter(ca_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .with_context(|| format!("failed to read CA bundle {}", ca_path.display()))?;
+        let (added, _) = roots.add_parsable_certificates(certs);
+        if added == 0 {
+            anyhow::bail!("no usable certificates in CA bundle {}", ca_path.display());
+        }
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("failed to set up TLS protocol versions")?
+        .with_root_certificates(roots);
+
+    let tls_config = match (&config.client_cert_path, &config.client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let certs = CertificateDer::pem_file_iter(cert_path)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .with_context(|| {
+                    format!("failed to read client certificate {}", cert_path.display())
+                })?;
+            let key = PrivateKeyDer::from_pem_file(key_path)
+                .with_context(|| format!("failed to read client key {}", key_path.display()))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .context("client certificate and key are not usable")?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => anyhow::bail!("client_cert_path and client_key_path must be set together"),
+    };
+
+    Ok(Some(Arc::new(tls_config)))
+}
+
+/// ureq agent honoring the configured TLS settings
+fn build_agent(config: &LearningConfig) -> Result<ureq::Agent> {
+    let builder = ureq::AgentBuilder::new().timeout(Duration::from_secs(30));
+    Ok(match build_tls_config(config)? {
+        Some(tls_config) => builder.tls_config(tls_config),
+        None => builder,
+    }
+    .build())
 }
 
 /// ICA API client for process analysis
 pub struct IcaClient {
+    /// HTTP agent, or why the TLS configuration could not be loaded
+    agent: std::result::Result<ureq::Agent, String>,
     ica_url: String,
-    setec_url: String,
+    setec: SetecCommand,
+    service_name: String,
+    service_key_env: String,
+    prompt_history: Arc<Mutex<PromptHistory>>,
+    redaction: Option<PromptRedaction>,
+    max_prompt_chars: usize,
 }
 
 #[derive(Serialize)]
@@ -51,31 +305,147 @@ struct ChatStatelessRequest {
 struct ChatStatelessResponse {
     response: String,
     #[allow(dead_code)]
-    #[serde(rename = "sessionId")]
+    #[serde(rename = "sessionId", default)]
     session_id: String,
 }
 
+/// Anthropic-style reply: `{"content": [{"type": "text", "text": "..."}]}`
+#[derive(Deserialize)]
+struct ContentBlocksResponse {
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+/// OpenAI-style reply: `{"choices": [{"message": {"content": "..."}}]}`
+#[derive(Deserialize)]
+struct ChoicesResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ChoiceMessage,
+}
+
+#[derive(Deserialize)]
+struct ChoiceMessage {
+    #[serde(default)]
+    content: String,
+}
+
+/// Text of a chat reply in any of the envelopes backends are known to use,
+/// trying ICA's own shape first. Errors describe the ICA shape.
+fn reply_text(body: &str) -> Result<String> {
+    if let Ok(flat) = serde_json::from_str::<ChatStatelessResponse>(body) {
+        return Ok(flat.response);
+    }
+    if let Ok(blocks) = serde_json::from_str::<ContentBlocksResponse>(body) {
+        return Ok(blocks
+            .content
+            .into_iter()
+            .filter(|block| block.kind == "text")
+            .map(|block| block.text)
+            .collect());
+    }
+    if let Ok(choices) = serde_json::from_str::<ChoicesResponse>(body) {
+        return Ok(choices
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .unwrap_or_default());
+    }
+    serde_json::from_str::<ChatStatelessResponse>(body)
+        .map(|flat| flat.response)
+        .context("Failed to parse ICA response")
+}
+
 impl IcaClient {
     pub fn new(config: &LearningConfig) -> Self {
+        let agent = build_agent(config).map_err(|e| {
+            log::error!("ICA TLS configuration failed, ICA calls will fail: {:#}", e);
+            format!("{:#}", e)
+        });
         Self {
+            agent,
             ica_url: config.ica_url.clone(),
-            setec_url: config.setec_url.clone(),
+            setec: SetecCommand::from_config(config),
+            service_name: config.service_name.clone(),
+            service_key_env: config.service_key_env.clone(),
+            prompt_history: Arc::new(Mutex::new(PromptHistory::new(config.prompt_history_size))),
+            redaction: PromptRedaction::from_config(config),
+            max_prompt_chars: config.max_prompt_chars,
         }
     }
 
+    /// Record prompts into `history`, shared with whoever reads it, instead
+    /// of a buffer of its own
+    pub fn with_prompt_history(mut self, history: Arc<Mutex<PromptHistory>>) -> Self {
+        self.prompt_history = history;
+        self
+    }
+
+    /// Recent prompt/response pairs (empty unless `prompt_history_size` is set)
+    pub fn prompt_history(&self) -> Arc<Mutex<PromptHistory>> {
+        self.prompt_history.clone()
+    }
+
     /// Check if ICA is available (has service key)
     pub fn is_available(&self) -> bool {
-        get_service_key(&self.setec_url).is_some()
+        self.service_key().is_some()
+    }
+
+    fn service_key(&self) -> Option<String> {
+        get_service_key(&self.setec, &self.service_key_env)
     }
 
     /// Analyze a process context using ICA
     pub fn analyze(&self, context: &AnalysisContext) -> Result<IcaAnalysisResponse> {
-        let service_key = get_service_key(&self.setec_url)
-            .context("ICA service key not available from setec")?;
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "ica_analyze",
+            command = %context.command,
+            port = ?context.port,
+            outcome = tracing::field::Empty,
+        )
+        .entered();
 
-        let prompt = build_analysis_prompt(context);
+        let result = retry_once_if_retryable(|| self.request_analysis(context));
 
-        let request = ChatStatelessRequest { message: prompt };
+        #[cfg(feature = "tracing")]
+        span.record("outcome", if result.is_ok() { "ok" } else { "error" });
+        result
+    }
+
+    fn request_analysis(&self, context: &AnalysisContext) -> Result<IcaAnalysisResponse> {
+        let agent = self
+            .agent
+            .as_ref()
+            .map_err(|e| anyhow!("ICA TLS configuration failed: {}", e))?;
+        let service_key = self.service_key().with_context(|| {
+            format!(
+                "ICA service key not set in {} or available from setec",
+                self.service_key_env
+            )
+        })?;
+
+        let prompt = build_analysis_prompt(context, self.redaction.as_ref(), self.max_prompt_chars);
+        log::debug!(
+            "ICA prompt for {}:\n{}",
+            context.command,
+            redact(&prompt, &service_key)
+        );
+
+        let request = ChatStatelessRequest {
+            message: prompt.clone(),
+        };
         let request_body =
             serde_json::to_string(&request).context("Failed to serialize request")?;
 
@@ -83,24 +453,180 @@ impl IcaClient {
 
         log::debug!("Calling ICA at {} for: {}", url, context.command);
 
-        let response = ureq::post(&url)
-            .set("Content-Type", "application/json")
-            .set("X-ICA-Service-Key", &service_key)
-            .set("X-ICA-Service-Name", "portkiller")
-            .timeout(Duration::from_secs(30))
+        let result = apply_headers(agent.post(&url), &service_key, &self.service_name)
             .send_string(&request_body)
-            .context("Failed to call ICA API")?;
+            .context("Failed to call ICA API")
+            .and_then(read_ica_body);
+
+        if let Ok(mut history) = self.prompt_history.lock()
+            && history.is_enabled()
+        {
+            let response = match &result {
+                Ok(text) => text.clone(),
+                Err(e) => format!("error: {:#}", e),
+            };
+            history.push(PromptRecord {
+                command: context.command.clone(),
+                prompt: redact(&prompt, &service_key),
+                response: redact(&response, &service_key),
+            });
+        }
+
+        parse_ica_body(&result?)
+    }
+}
+
+/// ICA analysis failures that callers may handle differently from a generic error
+#[derive(Debug, PartialEq, Eq)]
+pub enum AnalysisError {
+    /// ICA answered successfully but the model's reply was blank
+    EmptyResponse,
+}
+
+impl AnalysisError {
+    /// Whether an immediate retry is likely to succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::EmptyResponse)
+    }
+}
+
+impl std::fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyResponse => write!(f, "ICA returned an empty response"),
+        }
+    }
+}
 
-        let response_text = response.into_string().context("Failed to read ICA response")?;
-        let response_body: ChatStatelessResponse =
-            serde_json::from_str(&response_text).context("Failed to parse ICA response")?;
+impl std::error::Error for AnalysisError {}
 
-        // Parse the JSON response from Claude
-        parse_claude_response(&response_body.response)
+/// Run `attempt`, repeating it once right away if it fails with a retryable
+/// [`AnalysisError`]
+fn retry_once_if_retryable<T>(mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+    match attempt() {
+        Err(e)
+            if e.downcast_ref::<AnalysisError>()
+                .is_some_and(AnalysisError::is_retryable) =>
+        {
+            log::info!("Retrying ICA analysis: {}", e);
+            attempt()
+        }
+        result => result,
     }
 }
 
-fn build_analysis_prompt(context: &AnalysisContext) -> String {
+/// Largest ICA reply read before giving up; real replies are a few hundred bytes
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// Read an ICA reply body, bounded by [`MAX_RESPONSE_BYTES`]. Server-sent
+/// event streams are reassembled from their `data:` lines.
+fn read_ica_body(response: ureq::Response) -> Result<String> {
+    let streamed = response.content_type() == "text/event-stream";
+    let body = read_capped(response.into_reader(), MAX_RESPONSE_BYTES)
+        .context("Failed to read ICA response")?;
+    Ok(if streamed {
+        accumulate_sse_data(&body)
+    } else {
+        body
+    })
+}
+
+/// Read at most `cap` bytes as UTF-8, failing if the source holds more
+fn read_capped(reader: impl std::io::Read, cap: usize) -> Result<String> {
+    use std::io::Read;
+
+    let mut bytes = Vec::new();
+    reader
+        .take(cap as u64 + 1)
+        .read_to_end(&mut bytes)
+        .context("failed to read body")?;
+    if bytes.len() > cap {
+        anyhow::bail!("body exceeds {} bytes", cap);
+    }
+    String::from_utf8(bytes).context("body is not valid UTF-8")
+}
+
+/// Concatenate the payloads of an SSE stream's `data:` lines, skipping the
+/// `[DONE]` terminator some backends send
+fn accumulate_sse_data(stream: &str) -> String {
+    stream
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .filter(|data| *data != "[DONE]")
+        .collect()
+}
+
+/// Parse the body of an ICA stateless chat reply into an analysis
+fn parse_ica_body(body: &str) -> Result<IcaAnalysisResponse> {
+    let text = reply_text(body)?;
+
+    // Blank replies happen occasionally and are not worth a parse error
+    if text.trim().is_empty() {
+        return Err(AnalysisError::EmptyResponse.into());
+    }
+
+    // Parse the JSON response from Claude
+    parse_claude_response(&text)
+}
+
+/// Context lines dropped, least important first, when a prompt is over
+/// budget. Command, port, and project lines are always kept.
+const PROMPT_TRIM_ORDER: &[&str] = &[
+    "Process uptime: ",
+    "Process user: ",
+    "Container health: ",
+    "Related processes: ",
+    "Container command: ",
+    "Container workdir: ",
+    "Full command: ",
+    "Executable: ",
+    "Docker image: ",
+    "HTTP Server header: ",
+    "Working directory: ",
+    "macOS App Kind: ",
+    "Container prefix: ",
+    "Protocol: ",
+    "Runtime version: ",
+    "Detected framework: ",
+    "Docker container ID: ",
+    "Project group: ",
+    "Docker compose project: ",
+    "HTTP service name: ",
+    "Docker container: ",
+    "Docker compose service: ",
+    "Homebrew service: ",
+    "Proxies to: ",
+    "SSH forward to: ",
+    "macOS App Name: ",
+];
+
+/// Build the analysis prompt, dropping low-priority context lines until it
+/// fits in `max_chars` (0 for no limit). The instructions are never cut.
+fn build_analysis_prompt(
+    context: &AnalysisContext,
+    redaction: Option<&PromptRedaction>,
+    max_chars: usize,
+) -> String {
+    let details = context.to_prompt(now_timestamp());
+    let details = match redaction {
+        Some(redaction) => redaction.apply(&details),
+        None => details,
+    };
+    let mut lines: Vec<&str> = details.lines().collect();
+    let overhead = analysis_prompt("").chars().count();
+    let fits =
+        |lines: &[&str]| max_chars == 0 || overhead + lines.join("\n").chars().count() <= max_chars;
+    for label in PROMPT_TRIM_ORDER {
+        if fits(&lines) {
+            break;
+        }
+        lines.retain(|line| !line.starts_with(label));
+    }
+    analysis_prompt(&lines.join("\n"))
+}
+
+fn analysis_prompt(details: &str) -> String {
     format!(
         r#"Analyze this development process and return ONLY valid JSON (no markdown, no explanation):
 
@@ -110,9 +636,10 @@ Return a JSON object with these exact fields:
 {{
   "display_name": "Human-friendly name (e.g., 'DSS Backend API', 'macOS Control Center', 'Tailscale VPN Proxy')",
   "description": "Brief description of what this process does (1-2 sentences)",
-  "category": "One of: frontend, backend, database, cache, proxy, dev_tool, infrastructure, unknown",
+  "category": "One of: frontend, backend, database, cache, search, proxy, dev_tool, infrastructure, unknown",
   "group_hint": "Optional group name if this seems related to a stack (e.g., 'DSS Stack'), or null",
-  "confidence": 0.0-1.0 representing how confident you are in this analysis
+  "confidence": 0.0-1.0 representing how confident you are in this analysis,
+  "category_confidence": 0.0-1.0 representing how confident you are in the category alone
 }}
 
 Use the provided context to determine:
@@ -122,96 +649,449 @@ Use the provided context to determine:
 - For system services: Identify the official service name
 
 Return ONLY the JSON object, nothing else."#,
-        context.to_prompt()
+        details
     )
 }
 
+/// Run a raw model response through the same extraction, parsing, and
+/// validation applied to live ICA responses. Lets recorded responses be
+/// checked against prompt changes without calling ICA.
+pub fn validate_response_contract(raw: &str) -> Result<IcaAnalysisResponse> {
+    parse_claude_response(raw)
+}
+
 fn parse_claude_response(response: &str) -> Result<IcaAnalysisResponse> {
     // Try to find JSON in the response (Claude sometimes adds extra text)
-    let json_str = extract_json(response)?;
+    let json_str = extract_first_json_object(response)
+        .with_context(|| format!("No valid JSON found in response: {}", response))?;
+    let json_str = strip_json_extensions(json_str);
 
-    serde_json::from_str(&json_str).context("Failed to parse Claude's JSON response")
+    let value: serde_json::Value =
+        serde_json::from_str(&json_str).context("Failed to parse Claude's JSON response")?;
+    validate_confidence_ranges(&value)?;
+    let mut parsed: IcaAnalysisResponse =
+        serde_json::from_value(value).context("Failed to parse Claude's JSON response")?;
+    validate_response(&parsed)?;
+    parsed.category_confidence.get_or_insert(parsed.confidence);
+    Ok(parsed)
 }
 
-fn extract_json(text: &str) -> Result<String> {
-    // Try to find JSON object in response
-    let trimmed = text.trim();
-
-    // If it starts with {, try to find matching }
-    if trimmed.starts_with('{') {
-        if let Some(end) = find_matching_brace(trimmed) {
-            return Ok(trimmed[..=end].to_string());
-        }
+/// Reject responses that parse but can't be used as-is
+fn validate_response(response: &IcaAnalysisResponse) -> Result<()> {
+    if response.display_name.trim().is_empty() {
+        anyhow::bail!("display_name is empty");
     }
+    Ok(())
+}
 
-    // Look for JSON block in markdown code block
-    if let Some(start) = trimmed.find("```json") {
-        if let Some(end) = trimmed[start..].find("```\n").or(trimmed[start..].rfind("```")) {
-            let json_start = start + 7;
-            let json_end = start + end;
-            if json_end > json_start {
-                return Ok(trimmed[json_start..json_end].trim().to_string());
-            }
+/// Reject out-of-range confidences, which mean the model ignored the
+/// contract. Checked on the raw JSON, before `Confidence` clamps them.
+fn validate_confidence_ranges(value: &serde_json::Value) -> Result<()> {
+    for field in ["confidence", "category_confidence"] {
+        if let Some(confidence) = value.get(field).and_then(|v| v.as_f64())
+            && !(0.0..=1.0).contains(&confidence)
+        {
+            anyhow::bail!("{} {} is outside 0.0-1.0", field, confidence);
         }
     }
+    Ok(())
+}
+
+/// Remove `//` and `/* */` comments and trailing commas, which models
+/// sometimes emit, so the JSON5-ish text parses as strict JSON.
+/// String literals are left untouched.
+fn strip_json_extensions(json: &str) -> String {
+    let without_comments = strip_json_comments(json);
 
-    // Try to find any { } block
-    if let Some(start) = trimmed.find('{') {
-        if let Some(end) = find_matching_brace(&trimmed[start..]) {
-            return Ok(trimmed[start..=start + end].to_string());
+    let mut out = String::with_capacity(without_comments.len());
+    let mut chars = without_comments.chars();
+    let mut in_string = false;
+    let mut escape_next = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                _ if escape_next => escape_next = false,
+                '\\' => escape_next = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let rest = chars.clone().find(|next| !next.is_whitespace());
+            if matches!(rest, Some('}') | Some(']')) {
+                continue;
+            }
         }
+        out.push(c);
     }
 
-    anyhow::bail!("No valid JSON found in response: {}", text)
+    out
 }
 
-fn find_matching_brace(s: &str) -> Option<usize> {
-    let mut depth = 0;
+fn strip_json_comments(json: &str) -> String {
+    let mut out = String::with_capacity(json.len());
+    let mut chars = json.chars().peekable();
     let mut in_string = false;
     let mut escape_next = false;
 
-    for (i, c) in s.chars().enumerate() {
-        if escape_next {
-            escape_next = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                _ if escape_next => escape_next = false,
+                '\\' => escape_next = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            out.push(c);
             continue;
         }
 
-        match c {
-            '\\' if in_string => escape_next = true,
-            '"' => in_string = !in_string,
-            '{' if !in_string => depth += 1,
-            '}' if !in_string => {
-                depth -= 1;
-                if depth == 0 {
-                    return Some(i);
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                // Line comment: drop up to (not including) the newline
+                while chars.next_if(|next| *next != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = '\0';
+                for next in chars.by_ref() {
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
                 }
+                // Keep tokens on either side of the comment apart
+                out.push(' ');
             }
-            _ => {}
+            _ => out.push(c),
         }
     }
 
-    None
+    out
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::knowledge::context_gatherer::attach_siblings;
+    use crate::knowledge::types::{Confidence, ProcessCategory};
+
+    #[test]
+    fn test_failed_service_key_retried_after_interval() {
+        let mut cache = ServiceKeyCache::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let mut fetches = 0;
+        let command = setec_command("portkiller-cache-test");
+        let mut get = |cache: &mut ServiceKeyCache, elapsed: u64, result: Option<&str>| {
+            let now = start + Duration::from_secs(elapsed);
+            cache.get(&command, now, || {
+                fetches += 1;
+                result.map(str::to_string)
+            })
+        };
+
+        assert_eq!(get(&mut cache, 0, None), None);
+        // Still inside the retry interval: setec is not asked again
+        assert_eq!(get(&mut cache, 30, Some("key")), None);
+        assert_eq!(get(&mut cache, 61, Some("key")), Some("key".to_string()));
+        // Successes are cached for good
+        assert_eq!(get(&mut cache, 10_000, None), Some("key".to_string()));
+        assert_eq!(fetches, 2);
+
+        // The same secret on another setec server is looked up afresh
+        let staging = SetecCommand {
+            url: "https://setec-staging.example".to_string(),
+            ..setec_command("portkiller-cache-test")
+        };
+        assert_eq!(cache.get(&staging, start, || None), None);
+    }
+
+    /// Records the setec invocations instead of running the CLI
+    #[derive(Default)]
+    struct RecordingSetec {
+        calls: std::cell::RefCell<Vec<Vec<String>>>,
+    }
+
+    impl SetecRunner for RecordingSetec {
+        fn get(&self, command: &SetecCommand) -> Option<String> {
+            let mut call = vec![command.program.clone()];
+            call.extend(command.args());
+            self.calls.borrow_mut().push(call);
+            None
+        }
+    }
+
+    fn setec_command(service_name: &str) -> SetecCommand {
+        SetecCommand::from_config(&LearningConfig {
+            setec_url: "https://setec.example".to_string(),
+            service_name: service_name.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_env_service_key_skips_setec() {
+        let setec = RecordingSetec::default();
+        let command = setec_command("portkiller-env-test");
+        let key = resolve_service_key(Some("env-key".to_string()), &command, &setec);
+        assert_eq!(key.as_deref(), Some("env-key"));
+        assert!(setec.calls.borrow().is_empty());
+
+        resolve_service_key(None, &command, &setec);
+        assert_eq!(
+            *setec.calls.borrow(),
+            vec![vec![
+                "setec",
+                "-s",
+                "https://setec.example",
+                "get",
+                "ica/portkiller-env-test/service-key"
+            ]]
+        );
+        assert_eq!(env_service_key(""), None);
+    }
+
+    #[test]
+    fn test_configured_setec_command() {
+        let setec = RecordingSetec::default();
+        let command = SetecCommand::from_config(&LearningConfig {
+            setec_path: "/opt/tools/setec".to_string(),
+            setec_url: "https://setec.example".to_string(),
+            setec_secret: Some("staging/ica-key".to_string()),
+            setec_args: vec!["--timeout=5s".to_string()],
+            ..Default::default()
+        });
+        resolve_service_key(None, &command, &setec);
+
+        let calls = setec.calls.borrow();
+        assert_eq!(
+            calls[0],
+            vec![
+                "/opt/tools/setec",
+                "-s",
+                "https://setec.example",
+                "--timeout=5s",
+                "get",
+                "staging/ica-key"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response_with_trailing_commas() {
+        let response = r#"{
+            "display_name": "Test",
+            "description": "A test, really",
+            "category": "backend",
+            "group_hint": null,
+            "confidence": 0.9,
+        }"#;
+        let result = parse_claude_response(response).unwrap();
+        assert_eq!(result.display_name, "Test");
+        assert_eq!(result.description, "A test, really");
+    }
+
+    #[test]
+    fn test_parse_response_with_comments() {
+        let response = r#"```json
+{
+  // Looks like a Vite dev server
+  "display_name": "Vite Dev Server",
+  "description": "Serves http://localhost:5173 /* not a comment */",
+  "category": "frontend", /* inferred from the port */
+  "group_hint": null,
+  "confidence": 0.8
+}
+```"#;
+        let result = parse_claude_response(response).unwrap();
+        assert_eq!(result.display_name, "Vite Dev Server");
+        assert_eq!(
+            result.description,
+            "Serves http://localhost:5173 /* not a comment */"
+        );
+        assert_eq!(result.category, ProcessCategory::Frontend);
+    }
 
     #[test]
-    fn test_extract_json_direct() {
-        let response = r#"{"display_name": "Test", "description": "A test", "category": "backend", "group_hint": null, "confidence": 0.9}"#;
-        let result = extract_json(response).unwrap();
-        assert!(result.starts_with('{'));
-        assert!(result.ends_with('}'));
+    fn test_parse_response_with_category_confidence() {
+        let response = r#"{"display_name": "Postgres (maybe shop-db)", "description": "A database", "category": "database", "group_hint": null, "confidence": 0.4, "category_confidence": 0.95}"#;
+        let result = parse_claude_response(response).unwrap();
+        assert_eq!(result.confidence, 0.4);
+        assert_eq!(result.category_confidence.map(Confidence::get), Some(0.95));
+
+        // Defaults to the overall confidence when the model omits it
+        let response = r#"{"display_name": "Test", "description": "A test", "category": "backend", "group_hint": null, "confidence": 0.7}"#;
+        let result = parse_claude_response(response).unwrap();
+        assert_eq!(result.category_confidence.map(Confidence::get), Some(0.7));
     }
 
     #[test]
-    fn test_extract_json_with_text() {
-        let response = r#"Here's the analysis:
-{"display_name": "Test", "description": "A test", "category": "backend", "group_hint": null, "confidence": 0.9}
-Hope this helps!"#;
-        let result = extract_json(response).unwrap();
-        assert!(result.contains("display_name"));
+    fn test_empty_response_is_retryable() {
+        let body = r#"{"response": " \n ", "sessionId": "abc"}"#;
+        let err = parse_ica_body(body).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AnalysisError>(),
+            Some(&AnalysisError::EmptyResponse)
+        );
+
+        // Garbage is a hard failure, not retried
+        let body = r#"{"response": "no idea", "sessionId": "abc"}"#;
+        let err = parse_ica_body(body).unwrap_err();
+        assert!(err.downcast_ref::<AnalysisError>().is_none());
+    }
+
+    #[test]
+    fn test_reply_envelopes() {
+        let analysis = r#"{\"display_name\": \"Shop API\", \"description\": \"REST API\", \"category\": \"backend\", \"group_hint\": null, \"confidence\": 0.9}"#;
+        let flat = format!(r#"{{"response": "{}", "sessionId": "abc"}}"#, analysis);
+        let content = format!(
+            r#"{{"id": "msg_1", "content": [{{"type": "thinking", "thinking": "hm"}}, {{"type": "text", "text": "{}"}}]}}"#,
+            analysis
+        );
+        let choices = format!(
+            r#"{{"choices": [{{"index": 0, "message": {{"role": "assistant", "content": "{}"}}}}]}}"#,
+            analysis
+        );
+        for body in [&flat, &content, &choices] {
+            let result = parse_ica_body(body).unwrap_or_else(|e| panic!("{}: {:#}", body, e));
+            assert_eq!(result.display_name, "Shop API");
+        }
+
+        // Blank text in any envelope is still retryable
+        let err = parse_ica_body(r#"{"content": []}"#).unwrap_err();
+        assert!(err.downcast_ref::<AnalysisError>().is_some());
+
+        // Unknown envelopes are reported against ICA's own shape
+        let err = parse_ica_body(r#"{"output": "hi"}"#).unwrap_err();
+        assert!(format!("{:#}", err).contains("missing field `response`"));
+    }
+
+    #[test]
+    fn test_read_capped() {
+        let body = "x".repeat(MAX_RESPONSE_BYTES);
+        let read = read_capped(body.as_bytes(), MAX_RESPONSE_BYTES).unwrap();
+        assert_eq!(read.len(), MAX_RESPONSE_BYTES);
+
+        let body = "x".repeat(MAX_RESPONSE_BYTES + 1);
+        let err = read_capped(body.as_bytes(), MAX_RESPONSE_BYTES).unwrap_err();
+        assert!(format!("{:#}", err).contains("exceeds 65536 bytes"));
+    }
+
+    #[test]
+    fn test_accumulate_sse_data() {
+        let stream = "event: message\n\
+                      data: {\"response\": \"{}\",\n\
+                      \n\
+                      data: \"sessionId\": \"abc\"}\n\
+                      : keep-alive\n\
+                      data: [DONE]\n";
+        assert_eq!(
+            accumulate_sse_data(stream),
+            r#"{"response": "{}","sessionId": "abc"}"#
+        );
+    }
+
+    #[test]
+    fn test_retryable_error_is_retried_once() {
+        let mut attempts = 0;
+        let result: Result<()> = retry_once_if_retryable(|| {
+            attempts += 1;
+            Err(AnalysisError::EmptyResponse.into())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 2);
+
+        let mut attempts = 0;
+        let result = retry_once_if_retryable(|| {
+            attempts += 1;
+            if attempts == 1 {
+                Err(AnalysisError::EmptyResponse.into())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+
+        let mut attempts = 0;
+        let result: Result<()> = retry_once_if_retryable(|| {
+            attempts += 1;
+            Err(anyhow!("connection refused"))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_recorded_good_responses_parse() {
+        let good = [
+            (
+                "plain.json",
+                include_str!("testdata/responses/good/plain.json"),
+                "DSS Backend API",
+            ),
+            (
+                "markdown_fence.txt",
+                include_str!("testdata/responses/good/markdown_fence.txt"),
+                "Vite Dev Server",
+            ),
+            (
+                "chatty.txt",
+                include_str!("testdata/responses/good/chatty.txt"),
+                "macOS Control Center",
+            ),
+            (
+                "trailing_comma.txt",
+                include_str!("testdata/responses/good/trailing_comma.txt"),
+                "Storybook",
+            ),
+        ];
+        for (name, raw, display_name) in good {
+            let response = validate_response_contract(raw)
+                .unwrap_or_else(|e| panic!("{} should parse: {:#}", name, e));
+            assert_eq!(response.display_name, display_name, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_recorded_bad_responses_fail() {
+        let bad = [
+            (
+                "no_json.txt",
+                include_str!("testdata/responses/bad/no_json.txt"),
+                "No valid JSON found",
+            ),
+            (
+                "unknown_category.txt",
+                include_str!("testdata/responses/bad/unknown_category.txt"),
+                "unknown variant `spaceship`",
+            ),
+            (
+                "missing_confidence.txt",
+                include_str!("testdata/responses/bad/missing_confidence.txt"),
+                "missing field `confidence`",
+            ),
+            (
+                "empty_display_name.txt",
+                include_str!("testdata/responses/bad/empty_display_name.txt"),
+                "display_name is empty",
+            ),
+            (
+                "confidence_out_of_range.txt",
+                include_str!("testdata/responses/bad/confidence_out_of_range.txt"),
+                "confidence 85 is outside 0.0-1.0",
+            ),
+        ];
+        for (name, raw, expected) in bad {
+            let err = validate_response_contract(raw).expect_err(name);
+            let message = format!("{:#}", err);
+            assert!(message.contains(expected), "{}: {}", name, message);
+        }
     }
 
     #[test]
@@ -222,9 +1102,170 @@ Hope this helps!"#;
             project_name: Some("dss".to_string()),
             ..Default::default()
         };
-        let prompt = build_analysis_prompt(&context);
+        let prompt = build_analysis_prompt(&context, None, 0);
         assert!(prompt.contains("node"));
         assert!(prompt.contains("3001"));
         assert!(prompt.contains("dss"));
     }
+
+    #[test]
+    fn test_over_budget_prompt_drops_low_priority_fields() {
+        let context = AnalysisContext {
+            command: "node".to_string(),
+            port: Some(3001),
+            project_name: Some("dss".to_string()),
+            full_command: Some(format!("node server.js {}", "--flag ".repeat(25))),
+            docker_cmd: Some("x".repeat(300)),
+            docker_workdir: Some("/app".to_string()),
+            docker_service: Some("api".to_string()),
+            proxy_upstreams: vec!["api:3000".to_string()],
+            runtime_version: Some("20".to_string()),
+            ..Default::default()
+        };
+        let full = build_analysis_prompt(&context, None, 0);
+        let budget = full.chars().count() - 200;
+
+        let prompt = build_analysis_prompt(&context, None, budget);
+        assert!(prompt.chars().count() <= budget);
+        assert!(!prompt.contains("Container command:"));
+        assert!(prompt.contains("Command: node"));
+        assert!(prompt.contains("Port: 3001"));
+        assert!(prompt.contains("Project: dss"));
+        assert!(prompt.contains("Docker compose service: api"));
+        assert!(prompt.ends_with("Return ONLY the JSON object, nothing else."));
+        assert!(prompt.contains("\"category_confidence\""));
+
+        // An impossible budget still keeps the essentials and the contract
+        let tiny = build_analysis_prompt(&context, None, 10);
+        assert!(tiny.contains("Command: node"));
+        assert!(!tiny.contains("Docker compose service"));
+        assert!(!tiny.contains("Proxies to"));
+        assert!(!tiny.contains("Runtime version"));
+        assert!(tiny.contains("\"display_name\""));
+    }
+
+    #[test]
+    fn test_prompt_lists_sibling_services() {
+        let mut contexts = vec![
+            AnalysisContext {
+                port: Some(3000),
+                docker_project: Some("shop".to_string()),
+                docker_service: Some("api".to_string()),
+                ..AnalysisContext::new("node")
+            },
+            AnalysisContext {
+                port: Some(5432),
+                docker_project: Some("shop".to_string()),
+                docker_service: Some("db".to_string()),
+                ..AnalysisContext::new("postgres")
+            },
+        ];
+        let alone = build_analysis_prompt(&contexts[0], None, 0);
+        assert!(!alone.contains("Related processes"));
+
+        attach_siblings(&mut contexts);
+        let prompt = build_analysis_prompt(&contexts[0], None, 0);
+        assert!(prompt.contains("Related processes: db :5432"));
+    }
+
+    impl HeaderSink for Vec<(String, String)> {
+        fn header(mut self, name: &str, value: &str) -> Self {
+            self.push((name.to_string(), value.to_string()));
+            self
+        }
+    }
+
+    #[test]
+    fn test_headers_use_configured_service_name() {
+        let config = LearningConfig {
+            service_name: "portkiller-ci".to_string(),
+            ..Default::default()
+        };
+        let client = IcaClient::new(&config);
+        let headers = apply_headers(Vec::new(), "secret", &client.service_name);
+        assert!(headers.contains(&(
+            "X-ICA-Service-Name".to_string(),
+            "portkiller-ci".to_string()
+        )));
+        assert!(headers.contains(&("X-ICA-Service-Key".to_string(), "secret".to_string())));
+    }
+
+    #[test]
+    fn test_service_key_path_is_scoped() {
+        assert_eq!(service_key_path(DEFAULT_SERVICE_NAME), "ica/service-key");
+        assert_eq!(
+            service_key_path("portkiller-ci"),
+            "ica/portkiller-ci/service-key"
+        );
+    }
+
+    #[test]
+    fn test_prompt_history_disabled_by_default() {
+        let client = IcaClient::new(&LearningConfig::default());
+        let history = client.prompt_history();
+        let mut history = history.lock().unwrap();
+        history.push(PromptRecord {
+            command: "node".to_string(),
+            prompt: "prompt".to_string(),
+            response: "response".to_string(),
+        });
+        assert!(history.records().is_empty());
+    }
+
+    #[test]
+    fn test_prompt_history_shared_with_caller() {
+        let history = Arc::new(Mutex::new(PromptHistory::new(4)));
+        let client =
+            IcaClient::new(&LearningConfig::default()).with_prompt_history(history.clone());
+        assert!(Arc::ptr_eq(&client.prompt_history(), &history));
+    }
+
+    #[test]
+    fn test_redact_service_key() {
+        assert_eq!(
+            redact("key=sk-123 again sk-123", "sk-123"),
+            "key=[REDACTED] again [REDACTED]"
+        );
+    }
+
+    fn testdata(name: &str) -> Option<std::path::PathBuf> {
+        Some(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("src/knowledge/testdata")
+                .join(name),
+        )
+    }
+
+    #[test]
+    fn test_no_tls_paths_uses_defaults() {
+        assert!(
+            build_tls_config(&LearningConfig::default())
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_tls_config_built_from_paths() {
+        let config = LearningConfig {
+            client_cert_path: testdata("client.pem"),
+            client_key_path: testdata("client.key"),
+            ca_bundle_path: testdata("ca.pem"),
+            ..Default::default()
+        };
+        let tls_config = build_tls_config(&config).unwrap().unwrap();
+        assert!(tls_config.client_auth_cert_resolver.has_certs());
+        assert!(IcaClient::new(&config).agent.is_ok());
+    }
+
+    #[test]
+    fn test_missing_cert_file_is_an_error() {
+        let config = LearningConfig {
+            ca_bundle_path: testdata("missing.pem"),
+            ..Default::default()
+        };
+        let err = build_tls_config(&config).unwrap_err();
+        assert!(format!("{:#}", err).contains("missing.pem"));
+        assert!(IcaClient::new(&config).agent.is_err());
+    }
 }
@@ -0,0 +1,287 @@
+//! Locating a JSON object in free-form model output.
+//!
+//! Models wrap their JSON in prose or markdown fences, and nothing stops the
+//! text around it (or the strings inside it) from containing stray braces and
+//! quotes. The scanner here is a single linear pass that understands JSON
+//! strings and escapes, so adversarial input can't make it slow or make it
+//! return a slice that cuts through a string.
+
+/// Slice of the first balanced JSON object in `text`, if there is one.
+///
+/// A fenced ```` ```json ```` block is preferred when the text doesn't start
+/// with an object. If an opening brace is never closed, the earliest object
+/// that is balanced is returned instead. The slice is only guaranteed to be
+/// balanced, not to be valid JSON.
+pub fn extract_first_json_object(text: &str) -> Option<&str> {
+    let trimmed = text.trim();
+
+    if trimmed.starts_with('{')
+        && let Some(end) = find_matching_brace(trimmed)
+    {
+        return Some(&trimmed[..=end]);
+    }
+
+    if let Some(block) = fenced_json_block(trimmed)
+        && let Some(object) = first_balanced_object(block)
+    {
+        return Some(object);
+    }
+
+    first_balanced_object(trimmed)
+}
+
+/// Contents of the first ```` ```json ```` fence, up to its closing fence or
+/// the end of the text
+fn fenced_json_block(text: &str) -> Option<&str> {
+    const FENCE: &str = "```json";
+    let start = text.find(FENCE)? + FENCE.len();
+    let rest = &text[start..];
+    Some(rest.find("```").map_or(rest, |end| &rest[..end]))
+}
+
+/// Byte index of the `}` closing the `{` that starts `s`
+fn find_matching_brace(s: &str) -> Option<usize> {
+    let mut scanner = Scanner::default();
+    for (i, c) in s.char_indices() {
+        match scanner.step(c) {
+            Some(Brace::Close { depth: 0 }) => return Some(i),
+            Some(Brace::Unmatched) => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The earliest-starting balanced object in `text`, in one pass: every
+/// closing brace pops the position of its opening brace, and the outermost
+/// closed object seen so far wins
+fn first_balanced_object(text: &str) -> Option<&str> {
+    let start = text.find('{')?;
+    let text = &text[start..];
+    let mut scanner = Scanner::default();
+    let mut open: Vec<usize> = Vec::new();
+    let mut best: Option<(usize, usize)> = None;
+
+    for (i, c) in text.char_indices() {
+        match scanner.step(c) {
+            Some(Brace::Open) => open.push(i),
+            Some(Brace::Close { .. }) => {
+                let Some(from) = open.pop() else {
+                    continue;
+                };
+                if open.is_empty() {
+                    // Nothing encloses it, so nothing can start earlier
+                    return Some(&text[from..=i]);
+                }
+                if best.is_none_or(|(best_from, _)| from < best_from) {
+                    best = Some((from, i));
+                }
+            }
+            Some(Brace::Unmatched) | None => {}
+        }
+    }
+
+    best.map(|(from, to)| &text[from..=to])
+}
+
+enum Brace {
+    Open,
+    /// Closes an object, leaving `depth` objects still open
+    Close {
+        depth: usize,
+    },
+    /// A `}` with no object open
+    Unmatched,
+}
+
+/// Tracks nesting depth and string state one character at a time
+#[derive(Default)]
+struct Scanner {
+    depth: usize,
+    in_string: bool,
+    escape_next: bool,
+}
+
+impl Scanner {
+    fn step(&mut self, c: char) -> Option<Brace> {
+        if self.escape_next {
+            self.escape_next = false;
+            return None;
+        }
+        match c {
+            '\\' if self.in_string => self.escape_next = true,
+            '"' => self.in_string = !self.in_string,
+            '{' if !self.in_string => {
+                self.depth += 1;
+                return Some(Brace::Open);
+            }
+            '}' if !self.in_string => {
+                let Some(depth) = self.depth.checked_sub(1) else {
+                    return Some(Brace::Unmatched);
+                };
+                self.depth = depth;
+                return Some(Brace::Close { depth });
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Map, Value};
+
+    fn extract(text: &str) -> Option<&str> {
+        extract_first_json_object(text)
+    }
+
+    #[test]
+    fn test_extract_json_direct() {
+        let response = r#"{"display_name": "Test", "description": "A test", "category": "backend", "group_hint": null, "confidence": 0.9}"#;
+        let result = extract(response).unwrap();
+        assert!(result.starts_with('{'));
+        assert!(result.ends_with('}'));
+    }
+
+    #[test]
+    fn test_extract_json_with_text() {
+        let response = r#"Here's the analysis:
+{"display_name": "Test", "description": "A test", "category": "backend", "group_hint": null, "confidence": 0.9}
+Hope this helps!"#;
+        let result = extract(response).unwrap();
+        assert!(result.contains("display_name"));
+    }
+
+    #[test]
+    fn test_braces_and_quotes_inside_strings() {
+        let object = r#"{"name": "a } b { c", "quote": "say \"}\" twice", "path": "C:\\"}"#;
+        assert_eq!(extract(object), Some(object));
+        let wrapped = format!("Here you go: {} -- done }}", object);
+        assert_eq!(extract(&wrapped), Some(object));
+    }
+
+    #[test]
+    fn test_nested_objects() {
+        let object = r#"{"a": {"b": {"c": {}}}, "d": [{"e": 1}, {}]}"#;
+        assert_eq!(extract(&format!("result:\n{}\n", object)), Some(object));
+    }
+
+    #[test]
+    fn test_fenced_block_preferred_over_earlier_braces() {
+        let text = "Use the {name} field.\n```json\n{\"name\": \"API\"}\n```\n";
+        assert_eq!(extract(text), Some(r#"{"name": "API"}"#));
+        // An unterminated fence still yields its object
+        assert_eq!(extract("```json\n{\"a\": 1}"), Some(r#"{"a": 1}"#));
+        // A fence without an object falls back to the whole text
+        assert_eq!(
+            extract("Result: {\"a\": 1} ```json\n[]\n```"),
+            Some(r#"{"a": 1}"#)
+        );
+    }
+
+    #[test]
+    fn test_unterminated_input() {
+        assert_eq!(extract(""), None);
+        assert_eq!(extract("no json here"), None);
+        assert_eq!(extract(r#"{"name": "API""#), None);
+        assert_eq!(extract(r#"{"name": "API}"#), None);
+        assert_eq!(extract("}}}"), None);
+        // The outer object never closes; the inner one is still found
+        assert_eq!(extract(r#"{"broken": {"a": 1}"#), Some(r#"{"a": 1}"#));
+        assert_eq!(extract(r#"{ x {"a": 1} {"b": 2}"#), Some(r#"{"a": 1}"#));
+    }
+
+    #[test]
+    fn test_multibyte_text_is_sliced_on_char_boundaries() {
+        let object = r#"{"name": "Café ☕ {server}"}"#;
+        assert_eq!(extract(&format!("Voilà ✓ {}", object)), Some(object));
+    }
+
+    #[test]
+    fn test_deeply_nested_input() {
+        let depth = 50_000;
+        let nested = format!("{}{}", "{\"a\":".repeat(depth), "}".repeat(depth));
+        // Balanced, though the innermost value is missing
+        assert_eq!(extract(&nested).map(str::len), Some(nested.len()));
+
+        let unterminated = "{".repeat(depth);
+        assert_eq!(extract(&unterminated), None);
+        let closed_once = format!("{}}}", unterminated);
+        assert_eq!(extract(&closed_once), Some("{}"));
+    }
+
+    #[test]
+    fn test_adversarial_escapes() {
+        // A trailing backslash escapes the closing quote, so the brace after
+        // it is still inside the string
+        assert_eq!(extract(r#"{"a": "\"}"#), None);
+        assert_eq!(extract(r#"{"a": "\\"}"#), Some(r#"{"a": "\\"}"#));
+        // Backslashes outside strings are not escapes
+        assert_eq!(extract(r#"\{"a": 1}"#), Some(r#"{"a": 1}"#));
+    }
+
+    /// Random JSON string full of characters the scanner cares about
+    fn random_string(rng: &mut fastrand::Rng) -> String {
+        const ALPHABET: &[char] = &['{', '}', '"', '\\', '[', ']', ':', ',', 'a', 'é', ' '];
+        (0..rng.usize(0..8))
+            .map(|_| ALPHABET[rng.usize(..ALPHABET.len())])
+            .collect()
+    }
+
+    fn random_value(rng: &mut fastrand::Rng, depth: u32) -> Value {
+        match rng.u8(0..4) {
+            0 if depth < 6 => Value::Object(random_object(rng, depth + 1)),
+            1 if depth < 6 => Value::Array(
+                (0..rng.usize(0..4))
+                    .map(|_| random_value(rng, depth + 1))
+                    .collect(),
+            ),
+            2 => Value::from(rng.i64(..)),
+            _ => Value::String(random_string(rng)),
+        }
+    }
+
+    fn random_object(rng: &mut fastrand::Rng, depth: u32) -> Map<String, Value> {
+        (0..rng.usize(0..4))
+            .map(|_| (random_string(rng), random_value(rng, depth)))
+            .collect()
+    }
+
+    #[test]
+    fn test_random_objects_round_trip() {
+        let mut rng = fastrand::Rng::with_seed(0x5eed);
+        for _ in 0..500 {
+            let value = Value::Object(random_object(&mut rng, 0));
+            let json = if rng.bool() {
+                serde_json::to_string(&value).unwrap()
+            } else {
+                serde_json::to_string_pretty(&value).unwrap()
+            };
+            let prose = random_string(&mut rng).replace('{', "");
+            let text = format!("{} {}\n{}", prose, json, random_string(&mut rng));
+
+            let found = extract(&text).unwrap();
+            assert_eq!(serde_json::from_str::<Value>(found).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_random_truncations_never_split_strings() {
+        let mut rng = fastrand::Rng::with_seed(0xbad);
+        for _ in 0..500 {
+            let value = Value::Object(random_object(&mut rng, 0));
+            let json = serde_json::to_string(&value).unwrap();
+            let mut cut = rng.usize(..json.len());
+            while !json.is_char_boundary(cut) {
+                cut -= 1;
+            }
+
+            // Whatever is found in a truncated object is a complete object
+            if let Some(found) = extract(&json[..cut]) {
+                assert!(serde_json::from_str::<Value>(found).unwrap().is_object());
+            }
+        }
+    }
+}
@@ -1,9 +1,27 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
 
+use super::audit::AuditRecord;
+use super::fallback::{infer_category_from_command, interpreter_of};
+use super::pattern::matches_process;
+use super::project_config::user_defined_response;
 use super::types::{
-    AnalysisContext, KnowledgeBase, KnowledgeEntry, KnowledgeSource, LearningConfig, PendingEntry,
-    ProcessFingerprint,
+    AnalysisContext, Confidence, FailureRecord, KnowledgeBase, KnowledgeEntry, KnowledgeSource,
+    LearningConfig, MatchTier, NameExplanation, PendingEntry, PendingStatus, ProcessCategory,
+    ProcessFingerprint, now_timestamp,
 };
+use super::worker::{AnalysisResult, KnowledgeChange, KnowledgeEvent};
+
+/// Upper bound for confidence gained through repeated sightings
+const SIGHTING_CONFIDENCE_CAP: f32 = 0.95;
+/// Fraction of the remaining gap to the cap closed by each sighting
+const SIGHTING_CONFIDENCE_STEP: f32 = 0.02;
+
+/// Cooldown after the first failed analysis; doubles with each further failure
+const FAILURE_BASE_COOLDOWN_SECS: i64 = 5 * 60;
+/// Upper bound for the failure cooldown
+const FAILURE_MAX_COOLDOWN_SECS: i64 = 24 * 60 * 60;
 
 /// Record a process sighting and queue for analysis if needed
 pub fn record_sighting(
@@ -15,19 +33,92 @@ pub fn record_sighting(
     let hash = fingerprint.hash_key();
     let now = now_timestamp();
 
+    // Project overrides are applied directly and never sent for analysis.
+    // Those declared by a command glob share one entry for the project.
+    if let Some(response) = user_defined_response(&context) {
+        let fingerprint = match context.command_glob_override {
+            Some(ref glob) => ProcessFingerprint {
+                project_hash: fingerprint.project_hash.clone(),
+                ..ProcessFingerprint::new(glob).with_command_glob(glob)
+            },
+            None => fingerprint.clone(),
+        };
+        let shared_hash = fingerprint
+            .command_glob
+            .is_some()
+            .then(|| fingerprint.hash_key());
+        // A shared entry's fallback description varies with the command
+        // that matched, so only a declared one is compared
+        let compare_description = shared_hash.is_none() || context.description_override.is_some();
+        if kb.entries.get(&fingerprint.hash_key()).is_none_or(|entry| {
+            entry.source != KnowledgeSource::UserDefined
+                || entry.display_name != response.display_name
+                || (compare_description && entry.description != response.description)
+                || entry.group_id != response.group_hint
+        }) {
+            store_result(kb, fingerprint, response, KnowledgeSource::UserDefined);
+            return None;
+        }
+        if let Some(entry) = shared_hash.and_then(|hash| kb.entries.get_mut(&hash)) {
+            entry.sightings += 1;
+            entry.last_seen = now;
+            return None;
+        }
+    }
+
+    let executable_path = context.executable_path.as_deref();
+    if is_ignored_process(&context.command, executable_path, &config.ignore_commands) {
+        return None;
+    }
+
     // If already known, just update sightings
     if let Some(entry) = kb.entries.get_mut(&hash) {
         entry.sightings += 1;
+        if entry.first_seen == 0 {
+            entry.first_seen = now;
+        }
+        entry.last_seen = now;
+        if matches!(
+            entry.source,
+            KnowledgeSource::ApiLearned | KnowledgeSource::Heuristic
+        ) {
+            entry.confidence = boost_confidence(entry.confidence);
+        }
+
+        // Heuristic stand-ins for failed analyses are retried once their cooldown expires
+        if entry.source == KnowledgeSource::Heuristic
+            && let Some(failure) = kb.failed_analysis.get_mut(&hash)
+            && now >= failure_retry_at(failure)
+        {
+            failure.last_attempt = now;
+            return Some(context);
+        }
+        return None;
+    }
+
+    // A builtin or user-defined entry names every process it matches, e.g.
+    // `postgres` whatever its executable path. Interpreters are the
+    // exception: each venv or project runs a different program.
+    if interpreter_of(&fingerprint.command).is_none()
+        && lookup_best(kb, &fingerprint).is_some_and(|entry| {
+            matches!(
+                entry.source,
+                KnowledgeSource::Builtin | KnowledgeSource::UserDefined
+            )
+        })
+    {
         return None;
     }
 
+    let required = config.min_sightings_for(&infer_category_from_command(&context.command));
+
     // Check pending list
     if let Some(pending) = kb.pending_analysis.get_mut(&hash) {
         pending.sightings += 1;
         pending.last_seen = now;
 
-        // If reached threshold, return context for analysis
-        if pending.sightings >= config.min_sightings {
+        // If reached threshold, return context for analysis unless backing off
+        if pending.sightings >= required && retry_due(&mut kb.failed_analysis, &hash, now) {
             return Some(pending.context.clone());
         }
 
@@ -36,6 +127,7 @@ pub fn record_sighting(
 
     // New process - add to pending if room
     if kb.pending_analysis.len() < config.max_pending {
+        let due = required <= 1 && retry_due(&mut kb.failed_analysis, &hash, now);
         kb.pending_analysis.insert(
             hash,
             PendingEntry {
@@ -43,14 +135,69 @@ pub fn record_sighting(
                 sightings: 1,
                 first_seen: now,
                 last_seen: now,
-                context,
+                context: context.clone(),
             },
         );
+        if due {
+            return Some(context);
+        }
     }
 
     None
 }
 
+/// Whether a fingerprint may be analyzed now given its failure cooldown,
+/// counting this as a new attempt if so
+fn retry_due(failures: &mut HashMap<String, FailureRecord>, hash: &str, now: i64) -> bool {
+    let Some(failure) = failures.get_mut(hash) else {
+        return true;
+    };
+    if now < failure_retry_at(failure) {
+        return false;
+    }
+    failure.last_attempt = now;
+    true
+}
+
+/// Record every process seen in one refresh, returning the fingerprints and
+/// contexts that are due for analysis so the caller can queue them together.
+///
+/// A fingerprint listed more than once (e.g. a process listening on several
+/// ports) counts as a single sighting; its first context is used.
+pub fn record_snapshot(
+    kb: &mut KnowledgeBase,
+    sightings: &[(ProcessFingerprint, AnalysisContext)],
+    config: &LearningConfig,
+) -> Vec<(ProcessFingerprint, AnalysisContext)> {
+    let mut seen = HashSet::new();
+    sightings
+        .iter()
+        .filter(|(fingerprint, _)| seen.insert(fingerprint.hash_key()))
+        .filter_map(|(fingerprint, context)| {
+            record_sighting(kb, fingerprint.clone(), context.clone(), config)
+                .map(|due| (fingerprint.clone(), due))
+        })
+        .collect()
+}
+
+/// Whether `command` matches any ignore pattern. Patterns containing `*` or `?`
+/// are globs over the whole command; others match as substrings.
+pub fn is_ignored_command(command: &str, patterns: &[String]) -> bool {
+    is_ignored_process(command, None, patterns)
+}
+
+/// [`is_ignored_command`] that also honours path patterns (e.g.
+/// `~/projects/*/node`), matched against the executable path
+pub fn is_ignored_process(
+    command: &str,
+    executable_path: Option<&str>,
+    patterns: &[String],
+) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| matches_process(pattern, command, executable_path))
+}
+
 /// Store analysis result in the knowledge base
 pub fn store_result(
     kb: &mut KnowledgeBase,
@@ -61,12 +208,46 @@ pub fn store_result(
     let hash = fingerprint.hash_key();
     let now = now_timestamp();
 
+    // Entries declared by the user are never replaced by learned ones
+    if source != KnowledgeSource::UserDefined
+        && kb
+            .entries
+            .get(&hash)
+            .is_some_and(|entry| entry.source == KnowledgeSource::UserDefined)
+    {
+        kb.pending_analysis.remove(&hash);
+        return;
+    }
+
     // Remove from pending
-    let sightings = kb
-        .pending_analysis
-        .remove(&hash)
-        .map(|p| p.sightings)
-        .unwrap_or(1);
+    let pending = kb.pending_analysis.remove(&hash);
+    let (sightings, pending_first_seen, last_seen) = pending
+        .as_ref()
+        .map(|p| (p.sightings, p.first_seen, p.last_seen))
+        .unwrap_or((1, now, now));
+
+    // Only project-specific entries are tied to a directory
+    let project_dir = if fingerprint.project_hash.is_some() {
+        pending
+            .and_then(|p| p.context.working_directory)
+            .or_else(|| kb.entries.get(&hash).and_then(|e| e.project_dir.clone()))
+    } else {
+        None
+    };
+
+    // Re-analysis keeps the original first sighting
+    let first_seen = kb
+        .entries
+        .get(&hash)
+        .map(|existing| existing.first_seen)
+        .filter(|first_seen| *first_seen > 0)
+        .unwrap_or(pending_first_seen);
+
+    // Hiding is a user choice that survives re-analysis
+    let hidden = kb
+        .entries
+        .get(&hash)
+        .is_some_and(|existing| existing.hidden);
 
     // Create entry
     let entry = KnowledgeEntry {
@@ -79,9 +260,96 @@ pub fn store_result(
         source,
         sightings,
         updated_at: now,
+        first_seen,
+        last_seen,
+        reason: response.reason,
+        project_dir,
+        category_confidence: response.category_confidence,
+        hidden,
     };
 
-    kb.entries.insert(hash, entry);
+    // A successful ICA analysis ends any failure backoff
+    if entry.source == KnowledgeSource::ApiLearned {
+        kb.failed_analysis.remove(&hash);
+    }
+
+    if let Some(ref log) = kb.audit_log
+        && let Err(e) = log.append(&AuditRecord::from_entry(&hash, &entry))
+    {
+        log::warn!("Failed to write audit log: {:#}", e);
+    }
+
+    let change = if kb.entries.insert(hash.clone(), entry).is_some() {
+        KnowledgeChange::Updated(hash)
+    } else {
+        KnowledgeChange::Inserted(hash)
+    };
+    notify(kb, change);
+}
+
+/// Receive a [`KnowledgeEvent::EntryChanged`] whenever an entry of `kb` is
+/// stored, updated, or removed. Sightings of known entries are not reported,
+/// since every refresh would produce them. Dropping the receiver unsubscribes.
+pub fn subscribe(kb: &mut KnowledgeBase) -> crossbeam_channel::Receiver<KnowledgeEvent> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    kb.observers.push(tx);
+    rx
+}
+
+/// Send `change` to every subscriber, forgetting those that went away
+pub(super) fn notify(kb: &mut KnowledgeBase, change: KnowledgeChange) {
+    kb.observers.retain(|tx| {
+        tx.send(KnowledgeEvent::EntryChanged(change.clone()))
+            .is_ok()
+    });
+}
+
+/// Remove the entries `remove` selects, notifying subscribers of each.
+/// Returns the number removed.
+fn remove_entries(kb: &mut KnowledgeBase, remove: impl Fn(&KnowledgeEntry) -> bool) -> usize {
+    let mut removed = Vec::new();
+    kb.entries.retain(|key, entry| {
+        if remove(entry) {
+            removed.push(key.clone());
+            return false;
+        }
+        true
+    });
+    let count = removed.len();
+    for key in removed {
+        notify(kb, KnowledgeChange::Removed(key));
+    }
+    count
+}
+
+/// Record a failed ICA analysis, extending the fingerprint's retry cooldown
+pub fn record_failure(kb: &mut KnowledgeBase, fingerprint: &ProcessFingerprint) {
+    let now = now_timestamp();
+    let failure = kb
+        .failed_analysis
+        .entry(fingerprint.hash_key())
+        .or_insert(FailureRecord {
+            failures: 0,
+            last_attempt: now,
+        });
+    failure.failures += 1;
+    failure.last_attempt = now;
+}
+
+/// Store a result from the learning worker, backing off fingerprints ICA
+/// keeps failing on
+pub fn apply_result(kb: &mut KnowledgeBase, result: AnalysisResult) {
+    if result.failed {
+        record_failure(kb, &result.fingerprint);
+    }
+    store_result(kb, result.fingerprint, result.response, result.source);
+}
+
+/// Earliest timestamp at which a failed fingerprint may be analyzed again
+fn failure_retry_at(failure: &FailureRecord) -> i64 {
+    let exponent = failure.failures.saturating_sub(1).min(16);
+    let cooldown = (FAILURE_BASE_COOLDOWN_SECS << exponent).min(FAILURE_MAX_COOLDOWN_SECS);
+    failure.last_attempt + cooldown
 }
 
 /// Look up a display name for a process
@@ -96,6 +364,328 @@ pub fn lookup_entry<'a>(kb: &'a KnowledgeBase, fingerprint: &ProcessFingerprint)
     kb.entries.get(&hash)
 }
 
+/// Best entry among all whose fingerprint matches `fingerprint` (see
+/// [`ProcessFingerprint::matches`]).
+///
+/// Entries matching the command exactly beat `command_glob` entries. After
+/// that, precedence is `UserDefined` > `Builtin` > `ApiLearned` > `Heuristic`;
+/// within a source, higher confidence wins, then the more specific
+/// fingerprint. Remaining ties are broken by hash key so the result is stable.
+pub fn lookup_best<'a>(
+    kb: &'a KnowledgeBase,
+    fingerprint: &ProcessFingerprint,
+) -> Option<&'a KnowledgeEntry> {
+    lookup_best_with(kb, fingerprint, 0)
+}
+
+/// Like [`lookup_best`], but within a source an entry that is not provisional
+/// (see [`KnowledgeEntry::is_provisional`]) beats a provisional one, however
+/// confident or specific
+pub fn lookup_best_with<'a>(
+    kb: &'a KnowledgeBase,
+    fingerprint: &ProcessFingerprint,
+    provisional_threshold: u32,
+) -> Option<&'a KnowledgeEntry> {
+    kb.entries
+        .values()
+        .filter(|entry| entry.fingerprint.matches(fingerprint))
+        .max_by(|a, b| {
+            let exact = |entry: &KnowledgeEntry| entry.fingerprint.command_glob.is_none();
+            let settled = |entry: &KnowledgeEntry| !entry.is_provisional(provisional_threshold);
+            exact(a)
+                .cmp(&exact(b))
+                .then_with(|| source_precedence(&a.source).cmp(&source_precedence(&b.source)))
+                .then_with(|| settled(a).cmp(&settled(b)))
+                .then_with(|| a.confidence.get().total_cmp(&b.confidence.get()))
+                .then_with(|| specificity(&a.fingerprint).cmp(&specificity(&b.fingerprint)))
+                .then_with(|| b.hash_key().cmp(&a.hash_key()))
+        })
+}
+
+fn source_precedence(source: &KnowledgeSource) -> u8 {
+    match source {
+        KnowledgeSource::UserDefined => 3,
+        KnowledgeSource::Builtin => 2,
+        KnowledgeSource::ApiLearned => 1,
+        KnowledgeSource::Heuristic => 0,
+    }
+}
+
+/// Number of optional fields a fingerprint pins
+fn specificity(fingerprint: &ProcessFingerprint) -> usize {
+    [
+        fingerprint.default_port.is_some(),
+        fingerprint.project_hash.is_some(),
+        fingerprint.container_prefix.is_some(),
+        fingerprint.exe_hash.is_some(),
+        fingerprint.image_digest.is_some(),
+    ]
+    .iter()
+    .filter(|&&set| set)
+    .count()
+}
+
+/// Progress of a fingerprint waiting in the pending queue, if it is there
+pub fn pending_status(
+    kb: &KnowledgeBase,
+    fingerprint: &ProcessFingerprint,
+    config: &LearningConfig,
+) -> Option<PendingStatus> {
+    let pending = kb.pending_analysis.get(&fingerprint.hash_key())?;
+    let required = config.min_sightings_for(&infer_category_from_command(&pending.context.command));
+    Some(PendingStatus {
+        sightings: pending.sightings,
+        required,
+        remaining: required.saturating_sub(pending.sightings),
+    })
+}
+
+/// Explain how the display name for a process was derived
+pub fn explain(kb: &KnowledgeBase, fingerprint: &ProcessFingerprint) -> Option<NameExplanation> {
+    explain_with(kb, fingerprint, 0)
+}
+
+/// Like [`explain`], resolving the entry as the menu does: an exact match,
+/// else the best match (see [`lookup_best_with`])
+pub fn explain_with(
+    kb: &KnowledgeBase,
+    fingerprint: &ProcessFingerprint,
+    provisional_threshold: u32,
+) -> Option<NameExplanation> {
+    let (entry, tier) = match lookup_entry(kb, fingerprint) {
+        Some(entry) => (entry, MatchTier::Exact),
+        None => {
+            let entry = lookup_best_with(kb, fingerprint, provisional_threshold)?;
+            let tier = if entry.fingerprint.command_glob.is_some() {
+                MatchTier::Glob
+            } else {
+                MatchTier::Partial
+            };
+            (entry, tier)
+        }
+    };
+    let reason = match entry.source {
+        KnowledgeSource::Builtin => format!("matched builtin '{}'", entry.fingerprint.command),
+        KnowledgeSource::ApiLearned => "ICA analysis".to_string(),
+        KnowledgeSource::Heuristic => match entry.reason {
+            Some(ref reason) => format!("heuristic: {}", reason),
+            None => "heuristic".to_string(),
+        },
+        KnowledgeSource::UserDefined => entry
+            .reason
+            .clone()
+            .unwrap_or_else(|| "user-defined".to_string()),
+    };
+    Some(NameExplanation {
+        source: entry.source.clone(),
+        confidence: entry.confidence.get(),
+        reason,
+        tier,
+    })
+}
+
+/// Distinct commands with knowledge entries (builtin and learned), sorted
+pub fn known_commands(kb: &KnowledgeBase) -> BTreeSet<String> {
+    kb.entries
+        .values()
+        .map(|entry| entry.fingerprint.command.clone())
+        .collect()
+}
+
+/// All entries in menu order: category, then display name, then command.
+/// `KnowledgeBase::entries` iterates in arbitrary order, so this keeps the
+/// menu from reshuffling between refreshes.
+pub fn entries_sorted(kb: &KnowledgeBase) -> Vec<&KnowledgeEntry> {
+    let mut entries: Vec<_> = kb.entries.values().collect();
+    entries.sort_by(|a, b| menu_order(a, b));
+    entries
+}
+
+/// Entries keyed by group id (ungrouped entries under `None`), each group in
+/// the same order as [`entries_sorted`]
+pub fn entries_by_group(kb: &KnowledgeBase) -> BTreeMap<Option<&str>, Vec<&KnowledgeEntry>> {
+    let mut groups: BTreeMap<Option<&str>, Vec<&KnowledgeEntry>> = BTreeMap::new();
+    for entry in entries_sorted(kb) {
+        groups
+            .entry(entry.group_id.as_deref())
+            .or_default()
+            .push(entry);
+    }
+    groups
+}
+
+fn menu_order(a: &KnowledgeEntry, b: &KnowledgeEntry) -> Ordering {
+    a.category
+        .cmp(&b.category)
+        .then_with(|| a.display_name.cmp(&b.display_name))
+        .then_with(|| a.fingerprint.command.cmp(&b.fingerprint.command))
+        // Fully tied entries (e.g. same command in two containers) still
+        // need a fixed order
+        .then_with(|| a.hash_key().cmp(&b.hash_key()))
+}
+
+/// Number of currently running processes that map to each knowledge entry,
+/// keyed by entry hash (e.g. three compose replicas of one service count 3).
+/// Processes without an entry are not counted.
+pub fn instance_counts<'a>(
+    kb: &KnowledgeBase,
+    active: impl IntoIterator<Item = &'a ProcessFingerprint>,
+) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for fingerprint in active {
+        let hash = fingerprint.hash_key();
+        if kb.entries.contains_key(&hash) {
+            *counts.entry(hash).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Number of running processes in `active` that share `fingerprint`'s entry
+pub fn instance_count<'a>(
+    kb: &KnowledgeBase,
+    active: impl IntoIterator<Item = &'a ProcessFingerprint>,
+    fingerprint: &ProcessFingerprint,
+) -> usize {
+    instance_counts(kb, active)
+        .get(&fingerprint.hash_key())
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Nudge confidence toward the sighting cap; never lowers it
+fn boost_confidence(confidence: Confidence) -> Confidence {
+    let confidence = confidence.get();
+    if confidence >= SIGHTING_CONFIDENCE_CAP {
+        return Confidence::new(confidence);
+    }
+    let boosted = confidence + (SIGHTING_CONFIDENCE_CAP - confidence) * SIGHTING_CONFIDENCE_STEP;
+    Confidence::new(boosted.min(SIGHTING_CONFIDENCE_CAP))
+}
+
+/// Remove all learned (non-builtin) entries and pending analyses, restoring any
+/// missing builtins. Returns the number of entries removed.
+pub fn reset_learned(kb: &mut KnowledgeBase) -> usize {
+    let removed = remove_entries(kb, |entry| entry.source != KnowledgeSource::Builtin);
+    kb.pending_analysis.clear();
+    kb.failed_analysis.clear();
+
+    // Restore builtins that may have been deleted or overwritten
+    super::builtin::populate_builtins(kb);
+
+    removed
+}
+
+/// Learned and user-defined entries (everything but builtins), for sharing,
+/// in [`entries_sorted`] order
+pub fn export_user_entries(kb: &KnowledgeBase) -> Vec<KnowledgeEntry> {
+    entries_sorted(kb)
+        .into_iter()
+        .filter(|entry| entry.source != KnowledgeSource::Builtin)
+        .cloned()
+        .collect()
+}
+
+/// [`export_user_entries`] reduced to what is safe to share: plain
+/// command-to-name mappings. Entries tied to a project, container, or
+/// executable are left out, since their names usually are project names;
+/// the rest lose their group, project directory, reason, and usage history.
+pub fn export_user_entries_anonymized(kb: &KnowledgeBase) -> Vec<KnowledgeEntry> {
+    export_user_entries(kb)
+        .into_iter()
+        .filter(|entry| {
+            let fingerprint = &entry.fingerprint;
+            fingerprint.project_hash.is_none()
+                && fingerprint.container_prefix.is_none()
+                && fingerprint.exe_hash.is_none()
+        })
+        .map(|entry| KnowledgeEntry {
+            group_id: None,
+            sightings: 0,
+            updated_at: 0,
+            first_seen: 0,
+            last_seen: 0,
+            reason: None,
+            project_dir: None,
+            ..entry
+        })
+        .collect()
+}
+
+/// Hide or show the entry for `fingerprint` in the menu.
+/// Returns false if there is no such entry.
+pub fn set_hidden(kb: &mut KnowledgeBase, fingerprint: &ProcessFingerprint, hidden: bool) -> bool {
+    let hash = fingerprint.hash_key();
+    let Some(entry) = kb.entries.get_mut(&hash) else {
+        return false;
+    };
+    if entry.hidden != hidden {
+        entry.hidden = hidden;
+        notify(kb, KnowledgeChange::Updated(hash));
+    }
+    true
+}
+
+/// Entries that may be shown in the menu, in [`entries_sorted`] order
+pub fn visible_entries(kb: &KnowledgeBase) -> Vec<&KnowledgeEntry> {
+    entries_sorted(kb)
+        .into_iter()
+        .filter(|entry| !entry.hidden)
+        .collect()
+}
+
+/// Learned entries not seen within `older_than_secs`; builtins are never stale
+pub fn stale_entries(kb: &KnowledgeBase, older_than_secs: i64) -> Vec<&KnowledgeEntry> {
+    let cutoff = now_timestamp() - older_than_secs;
+    kb.entries
+        .values()
+        .filter(|entry| entry.source != KnowledgeSource::Builtin)
+        .filter(|entry| entry.last_active() < cutoff)
+        .collect()
+}
+
+/// Remove learned entries whose project directory no longer exists.
+/// Builtins and user-defined entries are kept. Returns the number removed.
+pub fn prune_dead_projects(kb: &mut KnowledgeBase) -> usize {
+    prune_dead_projects_with(kb, |dir| dir.exists())
+}
+
+/// [`prune_dead_projects`] with an injectable existence check
+pub fn prune_dead_projects_with(kb: &mut KnowledgeBase, exists: impl Fn(&Path) -> bool) -> usize {
+    remove_entries(kb, |entry| {
+        !matches!(
+            entry.source,
+            KnowledgeSource::Builtin | KnowledgeSource::UserDefined
+        ) && entry
+            .project_dir
+            .as_ref()
+            .is_some_and(|dir| !exists(Path::new(dir)))
+    })
+}
+
+/// Re-run command category inference over heuristic entries, e.g. after the
+/// heuristics improve. Names are left alone, as are entries from other
+/// sources, and an `Unknown` inference never replaces a known category.
+/// Returns the number of entries updated.
+pub fn reclassify_heuristics(kb: &mut KnowledgeBase) -> usize {
+    let mut updated = Vec::new();
+    for (key, entry) in kb.entries.iter_mut() {
+        if entry.source != KnowledgeSource::Heuristic {
+            continue;
+        }
+        let category = infer_category_from_command(&entry.fingerprint.command);
+        if category != ProcessCategory::Unknown && category != entry.category {
+            entry.category = category;
+            updated.push(key.clone());
+        }
+    }
+    let count = updated.len();
+    for key in updated {
+        notify(kb, KnowledgeChange::Updated(key));
+    }
+    count
+}
+
 /// Clean up old pending entries (entries that haven't been seen recently)
 pub fn cleanup_stale_pending(kb: &mut KnowledgeBase, max_age_secs: i64) {
     let now = now_timestamp();
@@ -105,13 +695,6 @@ pub fn cleanup_stale_pending(kb: &mut KnowledgeBase, max_age_secs: i64) {
         .retain(|_, entry| entry.last_seen > cutoff);
 }
 
-fn now_timestamp() -> i64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs() as i64
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,6 +707,7 @@ mod tests {
             max_pending: 10,
             ica_url: "http://localhost:4000".to_string(),
             setec_url: "https://setec.tailb726.ts.net".to_string(),
+            ..Default::default()
         }
     }
 
@@ -163,6 +747,35 @@ mod tests {
         assert!(result.is_some());
     }
 
+    #[test]
+    fn test_snapshot_returns_all_threshold_crossers() {
+        let mut kb = KnowledgeBase::default();
+        let config = test_config();
+        let sighting = |command: &str, port: u16| {
+            let context = AnalysisContext {
+                command: command.to_string(),
+                port: Some(port),
+                ..Default::default()
+            };
+            (ProcessFingerprint::new(command), context)
+        };
+        let snapshot = vec![
+            sighting("node", 3000),
+            // Same process on a second port: still one sighting this refresh
+            sighting("node", 3001),
+            sighting("vite", 5173),
+        ];
+
+        assert!(record_snapshot(&mut kb, &snapshot, &config).is_empty());
+        assert_eq!(kb.pending_analysis[&snapshot[0].0.hash_key()].sightings, 1);
+
+        let due = record_snapshot(&mut kb, &snapshot, &config);
+        let commands: Vec<&str> = due.iter().map(|(_, ctx)| ctx.command.as_str()).collect();
+        assert_eq!(commands, vec!["node", "vite"]);
+        assert_eq!(due[0].0, snapshot[0].0);
+        assert_eq!(due[0].1.port, Some(3000));
+    }
+
     #[test]
     fn test_known_process_not_queued() {
         let mut kb = KnowledgeBase::default();
@@ -178,10 +791,16 @@ mod tests {
                 description: "Test".to_string(),
                 category: super::super::types::ProcessCategory::Backend,
                 group_id: None,
-                confidence: 1.0,
+                confidence: Confidence::new(1.0),
                 source: KnowledgeSource::Builtin,
                 sightings: 5,
                 updated_at: 0,
+                first_seen: 0,
+                last_seen: 0,
+                reason: None,
+                project_dir: None,
+                category_confidence: None,
+                hidden: false,
             },
         );
 
@@ -195,4 +814,970 @@ mod tests {
         assert!(result.is_none());
         assert!(!kb.pending_analysis.contains_key(&fp.hash_key()));
     }
+
+    #[test]
+    fn test_sightings_boost_confidence_up_to_cap() {
+        let mut kb = KnowledgeBase::default();
+        let config = test_config();
+        let fp = ProcessFingerprint::new("myapp");
+
+        kb.entries.insert(
+            fp.hash_key(),
+            KnowledgeEntry {
+                fingerprint: fp.clone(),
+                display_name: "My App".to_string(),
+                description: "Test".to_string(),
+                category: super::super::types::ProcessCategory::Backend,
+                group_id: None,
+                confidence: Confidence::new(0.5),
+                source: KnowledgeSource::Heuristic,
+                sightings: 1,
+                updated_at: 0,
+                first_seen: 0,
+                last_seen: 0,
+                reason: None,
+                project_dir: None,
+                category_confidence: None,
+                hidden: false,
+            },
+        );
+
+        let ctx = AnalysisContext::new("myapp");
+        let mut previous = 0.5;
+        for _ in 0..1000 {
+            record_sighting(&mut kb, fp.clone(), ctx.clone(), &config);
+            let confidence = kb.entries[&fp.hash_key()].confidence.get();
+            assert!(confidence >= previous);
+            assert!(confidence <= SIGHTING_CONFIDENCE_CAP);
+            previous = confidence;
+        }
+        assert!(previous > 0.9);
+    }
+
+    #[test]
+    fn test_builtin_confidence_unchanged_by_sightings() {
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+        let config = test_config();
+        let fp = ProcessFingerprint::new("postgres");
+
+        let ctx = AnalysisContext::new("postgres");
+        record_sighting(&mut kb, fp.clone(), ctx, &config);
+        assert_eq!(kb.entries[&fp.hash_key()].confidence, 1.0);
+    }
+
+    #[test]
+    fn test_reset_learned_keeps_builtins() {
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+        let builtin_count = kb.entries.len();
+        let config = test_config();
+
+        let learned = ProcessFingerprint::new("myapp");
+        store_result(
+            &mut kb,
+            learned.clone(),
+            super::super::types::IcaAnalysisResponse {
+                display_name: "My App".to_string(),
+                description: "Test".to_string(),
+                category: super::super::types::ProcessCategory::Backend,
+                group_hint: None,
+                confidence: Confidence::new(0.8),
+                category_confidence: None,
+                reason: None,
+            },
+            KnowledgeSource::ApiLearned,
+        );
+        // A learned entry that replaced a builtin
+        let node = ProcessFingerprint::new("node");
+        kb.entries.get_mut(&node.hash_key()).unwrap().source = KnowledgeSource::ApiLearned;
+        let pending = ProcessFingerprint::new("other");
+        record_sighting(&mut kb, pending, AnalysisContext::new("other"), &config);
+
+        let removed = reset_learned(&mut kb);
+
+        assert_eq!(removed, 2);
+        assert!(!kb.entries.contains_key(&learned.hash_key()));
+        assert!(kb.pending_analysis.is_empty());
+        assert_eq!(kb.entries.len(), builtin_count);
+        assert_eq!(
+            kb.entries[&node.hash_key()].source,
+            KnowledgeSource::Builtin
+        );
+    }
+
+    fn heuristic_response() -> super::super::types::IcaAnalysisResponse {
+        super::super::types::IcaAnalysisResponse {
+            display_name: "Weird".to_string(),
+            description: "Test".to_string(),
+            category: super::super::types::ProcessCategory::Unknown,
+            group_hint: None,
+            confidence: Confidence::new(0.3),
+            category_confidence: None,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn test_subscribers_see_entry_changes() {
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+        let events = subscribe(&mut kb);
+        let fp = ProcessFingerprint::new("weird");
+        let key = fp.hash_key();
+        let changes = || -> Vec<KnowledgeChange> {
+            events
+                .try_iter()
+                .map(|event| match event {
+                    KnowledgeEvent::EntryChanged(change) => change,
+                    other => panic!("unexpected event {:?}", other),
+                })
+                .collect()
+        };
+
+        let store = |kb: &mut KnowledgeBase| {
+            store_result(
+                kb,
+                fp.clone(),
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            )
+        };
+
+        store(&mut kb);
+        assert_eq!(changes(), vec![KnowledgeChange::Inserted(key.clone())]);
+        store(&mut kb);
+        assert_eq!(changes(), vec![KnowledgeChange::Updated(key.clone())]);
+
+        // Sightings and no-op changes stay quiet
+        let absent = ProcessFingerprint::new("absent");
+        let ctx = AnalysisContext::new("weird");
+        record_sighting(&mut kb, fp.clone(), ctx, &test_config());
+        assert!(!set_hidden(&mut kb, &absent, true));
+        assert!(set_hidden(&mut kb, &fp, false));
+        assert!(changes().is_empty());
+
+        reset_learned(&mut kb);
+        assert_eq!(changes(), vec![KnowledgeChange::Removed(key)]);
+
+        // Builtins restored by a reset are reported too
+        let node = ProcessFingerprint::new("node").hash_key();
+        kb.entries.remove(&node);
+        reset_learned(&mut kb);
+        assert_eq!(changes(), vec![KnowledgeChange::Inserted(node)]);
+
+        // A clone doesn't report to the original's subscribers
+        let mut snapshot = kb.clone();
+        assert!(snapshot.observers.is_empty());
+        store(&mut snapshot);
+        assert!(changes().is_empty());
+
+        // A dropped receiver is forgotten on the next change
+        drop(events);
+        store(&mut kb);
+        assert!(kb.observers.is_empty());
+    }
+
+    #[test]
+    fn test_failed_fingerprint_skipped_during_cooldown() {
+        let mut kb = KnowledgeBase::default();
+        let config = test_config();
+        let fp = ProcessFingerprint::new("weird");
+        let ctx = AnalysisContext::new("weird");
+
+        // Fails twice; each time the heuristic fallback is stored
+        for _ in 0..2 {
+            record_failure(&mut kb, &fp);
+            store_result(
+                &mut kb,
+                fp.clone(),
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            );
+        }
+        assert_eq!(kb.failed_analysis[&fp.hash_key()].failures, 2);
+
+        // The next immediate attempt is skipped
+        assert!(record_sighting(&mut kb, fp.clone(), ctx.clone(), &config).is_none());
+
+        // Once the cooldown has passed it is queued again
+        kb.failed_analysis
+            .get_mut(&fp.hash_key())
+            .unwrap()
+            .last_attempt -= FAILURE_MAX_COOLDOWN_SECS;
+        assert!(record_sighting(&mut kb, fp.clone(), ctx.clone(), &config).is_some());
+        // ...but only once per cooldown window
+        assert!(record_sighting(&mut kb, fp.clone(), ctx, &config).is_none());
+    }
+
+    #[test]
+    fn test_failure_cooldown_grows_exponentially() {
+        let first = FailureRecord {
+            failures: 1,
+            last_attempt: 0,
+        };
+        let third = FailureRecord {
+            failures: 3,
+            last_attempt: 0,
+        };
+        let many = FailureRecord {
+            failures: 40,
+            last_attempt: 0,
+        };
+        assert_eq!(failure_retry_at(&first), FAILURE_BASE_COOLDOWN_SECS);
+        assert_eq!(failure_retry_at(&third), FAILURE_BASE_COOLDOWN_SECS * 4);
+        assert_eq!(failure_retry_at(&many), FAILURE_MAX_COOLDOWN_SECS);
+    }
+
+    #[test]
+    fn test_successful_analysis_clears_failures() {
+        let mut kb = KnowledgeBase::default();
+        let fp = ProcessFingerprint::new("weird");
+        record_failure(&mut kb, &fp);
+        store_result(
+            &mut kb,
+            fp.clone(),
+            heuristic_response(),
+            KnowledgeSource::ApiLearned,
+        );
+        assert!(kb.failed_analysis.is_empty());
+    }
+
+    #[test]
+    fn test_sightings_advance_last_seen_only() {
+        let mut kb = KnowledgeBase::default();
+        let config = test_config();
+        let fp = ProcessFingerprint::new("myapp");
+        store_result(
+            &mut kb,
+            fp.clone(),
+            heuristic_response(),
+            KnowledgeSource::Heuristic,
+        );
+        let key = fp.hash_key();
+
+        // Pretend the entry was first seen a while ago
+        let entry = kb.entries.get_mut(&key).unwrap();
+        entry.first_seen = 1_000;
+        entry.last_seen = 2_000;
+
+        record_sighting(&mut kb, fp.clone(), AnalysisContext::new("myapp"), &config);
+
+        let entry = &kb.entries[&key];
+        assert_eq!(entry.first_seen, 1_000);
+        assert!(entry.last_seen > 2_000);
+    }
+
+    #[test]
+    fn test_stale_entries_use_last_seen() {
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+        let active = ProcessFingerprint::new("active");
+        let dead = ProcessFingerprint::new("dead");
+        for fp in [&active, &dead] {
+            store_result(
+                &mut kb,
+                fp.clone(),
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            );
+        }
+        kb.entries.get_mut(&dead.hash_key()).unwrap().last_seen -= 30 * 86_400;
+
+        let stale = stale_entries(&kb, 7 * 86_400);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].fingerprint, dead);
+    }
+
+    #[test]
+    fn test_known_commands_sorted_and_distinct() {
+        let mut kb = KnowledgeBase::default();
+        for fp in [
+            ProcessFingerprint::new("vite"),
+            ProcessFingerprint::new("node"),
+            ProcessFingerprint::new("node").with_container_prefix("dss"),
+        ] {
+            store_result(
+                &mut kb,
+                fp,
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            );
+        }
+
+        let commands: Vec<_> = known_commands(&kb).into_iter().collect();
+        assert_eq!(commands, vec!["node".to_string(), "vite".to_string()]);
+    }
+
+    #[test]
+    fn test_lookup_best_precedence() {
+        use super::super::types::IcaAnalysisResponse;
+        use KnowledgeSource::{ApiLearned, Builtin, Heuristic, UserDefined};
+
+        fn store(
+            kb: &mut KnowledgeBase,
+            fingerprint: ProcessFingerprint,
+            name: &str,
+            confidence: f32,
+            source: KnowledgeSource,
+        ) {
+            let response = IcaAnalysisResponse {
+                display_name: name.to_string(),
+                confidence: Confidence::new(confidence),
+                ..heuristic_response()
+            };
+            store_result(kb, fingerprint, response, source);
+        }
+
+        let mut kb = KnowledgeBase::default();
+        let running = ProcessFingerprint::new("node")
+            .with_port(3000)
+            .with_container_prefix("dss");
+        let best = |kb: &KnowledgeBase| lookup_best(kb, &running).unwrap().display_name.clone();
+
+        let node = ProcessFingerprint::new("node");
+        store(&mut kb, running.clone(), "Guess", 0.9, Heuristic);
+        store(&mut kb, node.clone(), "Low", 0.6, ApiLearned);
+        assert_eq!(best(&kb), "Low");
+
+        let dss = node.clone().with_container_prefix("dss");
+        store(&mut kb, dss, "High", 0.8, ApiLearned);
+        // Pins a different container, so never a candidate
+        let other = node.clone().with_container_prefix("other");
+        store(&mut kb, other, "Other", 1.0, UserDefined);
+        assert_eq!(best(&kb), "High");
+
+        let alias = ProcessFingerprint::new("nodejs");
+        store(&mut kb, alias, "Builtin", 0.5, Builtin);
+        assert_eq!(best(&kb), "Builtin");
+
+        let pinned_port = node.with_port(3000);
+        store(&mut kb, pinned_port, "Mine", 1.0, UserDefined);
+        assert_eq!(best(&kb), "Mine");
+
+        assert!(lookup_best(&kb, &ProcessFingerprint::new("vite")).is_none());
+    }
+
+    #[test]
+    fn test_provisional_entries_lose_to_established_ones() {
+        use super::super::types::IcaAnalysisResponse;
+
+        let mut kb = KnowledgeBase::default();
+        let running = ProcessFingerprint::new("node").with_port(3000);
+        let node = ProcessFingerprint::new("node");
+        for (fingerprint, name, confidence, sightings) in
+            [(&running, "One-off", 0.9, 1), (&node, "Regular", 0.6, 10)]
+        {
+            let response = IcaAnalysisResponse {
+                display_name: name.to_string(),
+                confidence: Confidence::new(confidence),
+                ..heuristic_response()
+            };
+            let source = KnowledgeSource::ApiLearned;
+            store_result(&mut kb, fingerprint.clone(), response, source);
+            let entry = kb.entries.get_mut(&fingerprint.hash_key()).unwrap();
+            entry.sightings = sightings;
+        }
+
+        assert!(kb.entries[&running.hash_key()].is_provisional(3));
+        assert!(!kb.entries[&node.hash_key()].is_provisional(3));
+        assert_eq!(lookup_best(&kb, &running).unwrap().display_name, "One-off");
+        assert_eq!(
+            lookup_best_with(&kb, &running, 3).unwrap().display_name,
+            "Regular"
+        );
+    }
+
+    #[test]
+    fn test_exact_command_beats_glob() {
+        use super::super::types::IcaAnalysisResponse;
+
+        let mut kb = KnowledgeBase::default();
+        let worker = |name: &str| IcaAnalysisResponse {
+            display_name: name.to_string(),
+            ..heuristic_response()
+        };
+        let glob = ProcessFingerprint::new("celery").with_command_glob("*celery*");
+        store_result(
+            &mut kb,
+            glob,
+            worker("Worker"),
+            KnowledgeSource::UserDefined,
+        );
+
+        let beat = ProcessFingerprint::new("celery-beat");
+        let best = |kb: &KnowledgeBase| lookup_best(kb, &beat).unwrap().display_name.clone();
+        assert_eq!(best(&kb), "Worker");
+
+        store_result(
+            &mut kb,
+            beat.clone(),
+            worker("Scheduler"),
+            KnowledgeSource::Heuristic,
+        );
+        assert_eq!(best(&kb), "Scheduler");
+        // Exact lookups never consult globs
+        assert!(lookup_entry(&kb, &ProcessFingerprint::new("celery")).is_none());
+    }
+
+    #[test]
+    fn test_builtins_cover_processes_with_executable_paths() {
+        let config = test_config();
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+
+        for (command, path) in [
+            ("postgres", "/opt/homebrew/opt/postgresql@16/bin/postgres"),
+            ("python", "/Users/al/shop/.venv/bin/python"),
+        ] {
+            let ctx = AnalysisContext {
+                executable_path: Some(path.to_string()),
+                ..AnalysisContext::new(command)
+            };
+            let fingerprint = ProcessFingerprint::from_config(&ctx, &config);
+            record_sighting(&mut kb, fingerprint, ctx, &config);
+        }
+
+        // The builtin names postgres; the venv's python is learned
+        let pending: Vec<_> = kb
+            .pending_analysis
+            .values()
+            .map(|pending| pending.fingerprint.command.as_str())
+            .collect();
+        assert_eq!(pending, ["python"]);
+    }
+
+    #[test]
+    fn test_prune_dead_projects() {
+        let config = test_config();
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+        let builtins = kb.entries.len();
+
+        let live = ProcessFingerprint::new("node").with_project_hash("live");
+        let dead = ProcessFingerprint::new("node").with_project_hash("dead");
+        for (fingerprint, dir) in [(&live, "/src/live"), (&dead, "/src/dead")] {
+            let ctx = AnalysisContext {
+                command: "node".to_string(),
+                working_directory: Some(dir.to_string()),
+                ..Default::default()
+            };
+            record_sighting(&mut kb, fingerprint.clone(), ctx, &config);
+            store_result(
+                &mut kb,
+                fingerprint.clone(),
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            );
+        }
+        // Not project-specific, so never pruned
+        store_result(
+            &mut kb,
+            ProcessFingerprint::new("my-tool"),
+            heuristic_response(),
+            KnowledgeSource::Heuristic,
+        );
+        assert_eq!(
+            lookup_entry(&kb, &dead).unwrap().project_dir.as_deref(),
+            Some("/src/dead")
+        );
+
+        let removed = prune_dead_projects_with(&mut kb, |dir| dir != Path::new("/src/dead"));
+
+        assert_eq!(removed, 1);
+        assert!(lookup_entry(&kb, &dead).is_none());
+        assert!(lookup_entry(&kb, &live).is_some());
+        assert_eq!(kb.entries.len(), builtins + 2);
+    }
+
+    #[test]
+    fn test_hidden_entries_not_visible() {
+        let mut kb = KnowledgeBase::default();
+        let shown = ProcessFingerprint::new("my-tool");
+        let hidden = ProcessFingerprint::new("rapportd");
+        for fingerprint in [&shown, &hidden] {
+            store_result(
+                &mut kb,
+                fingerprint.clone(),
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            );
+        }
+
+        assert!(set_hidden(&mut kb, &hidden, true));
+        let absent = ProcessFingerprint::new("absent");
+        assert!(!set_hidden(&mut kb, &absent, true));
+        let visible = |kb: &KnowledgeBase| -> Vec<String> {
+            visible_entries(kb)
+                .iter()
+                .map(|entry| entry.fingerprint.command.clone())
+                .collect()
+        };
+        assert_eq!(visible(&kb), vec!["my-tool"]);
+
+        // Still learned: re-analysis updates the entry but keeps it hidden
+        store_result(
+            &mut kb,
+            hidden.clone(),
+            heuristic_response(),
+            KnowledgeSource::ApiLearned,
+        );
+        let entry = lookup_entry(&kb, &hidden).unwrap();
+        assert_eq!(entry.source, KnowledgeSource::ApiLearned);
+        assert!(entry.hidden);
+
+        set_hidden(&mut kb, &hidden, false);
+        assert_eq!(visible(&kb).len(), 2);
+    }
+
+    #[test]
+    fn test_reclassify_heuristics_upgrades_stale_category() {
+        let mut kb = KnowledgeBase::default();
+        let stale = ProcessFingerprint::new("postgres");
+        let learned = ProcessFingerprint::new("redis-server");
+        store_result(
+            &mut kb,
+            stale.clone(),
+            heuristic_response(),
+            KnowledgeSource::Heuristic,
+        );
+        store_result(
+            &mut kb,
+            learned.clone(),
+            heuristic_response(),
+            KnowledgeSource::ApiLearned,
+        );
+        for entry in kb.entries.values_mut() {
+            entry.category = ProcessCategory::Unknown;
+        }
+        let name = lookup_entry(&kb, &stale).unwrap().display_name.clone();
+
+        assert_eq!(reclassify_heuristics(&mut kb), 1);
+        let entry = lookup_entry(&kb, &stale).unwrap();
+        assert_eq!(entry.category, ProcessCategory::Database);
+        assert_eq!(entry.display_name, name);
+        assert_eq!(
+            lookup_entry(&kb, &learned).unwrap().category,
+            ProcessCategory::Unknown
+        );
+        assert_eq!(reclassify_heuristics(&mut kb), 0);
+    }
+
+    #[test]
+    fn test_store_result_appends_audit_lines() {
+        use super::super::audit::{AuditLog, AuditRecord};
+
+        let dir = std::env::temp_dir().join(format!("portkiller-audit-kb-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log = AuditLog::new(dir.join("audit.jsonl"));
+        let mut kb = KnowledgeBase {
+            audit_log: Some(log.clone()),
+            ..Default::default()
+        };
+
+        for command in ["my-tool", "other-tool"] {
+            store_result(
+                &mut kb,
+                ProcessFingerprint::new(command),
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            );
+        }
+
+        let contents = std::fs::read_to_string(log.path()).unwrap();
+        let records: Vec<AuditRecord> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].command, "other-tool");
+        assert_eq!(records[1].source, KnowledgeSource::Heuristic);
+        assert_eq!(
+            records[0].fingerprint,
+            ProcessFingerprint::new("my-tool").hash_key()
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_anonymized_export_has_no_project_fields() {
+        use super::super::types::IcaAnalysisResponse;
+
+        let config = test_config();
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+
+        let project = ProcessFingerprint::new("node").with_project_hash("shop");
+        let ctx = AnalysisContext {
+            command: "node".to_string(),
+            working_directory: Some("/Users/al/shop".to_string()),
+            ..Default::default()
+        };
+        record_sighting(&mut kb, project.clone(), ctx, &config);
+        let shop_api = IcaAnalysisResponse {
+            display_name: "Shop API".to_string(),
+            group_hint: Some("shop".to_string()),
+            ..heuristic_response()
+        };
+        store_result(&mut kb, project, shop_api, KnowledgeSource::ApiLearned);
+        for fingerprint in [
+            ProcessFingerprint::new("node").with_container_prefix("shop"),
+            ProcessFingerprint::new("python")
+                .with_executable_path("/Users/al/shop/.venv/bin/python"),
+        ] {
+            store_result(
+                &mut kb,
+                fingerprint,
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            );
+        }
+        let generic = IcaAnalysisResponse {
+            group_hint: Some("shop".to_string()),
+            reason: Some("project 'shop'".to_string()),
+            ..heuristic_response()
+        };
+        store_result(
+            &mut kb,
+            ProcessFingerprint::new("my-tool").with_port(9000),
+            generic,
+            KnowledgeSource::Heuristic,
+        );
+
+        assert_eq!(export_user_entries(&kb).len(), 4);
+        let exported = export_user_entries_anonymized(&kb);
+        assert_eq!(exported.len(), 1);
+        let entry = &exported[0];
+        assert_eq!(
+            entry.fingerprint,
+            ProcessFingerprint::new("my-tool").with_port(9000)
+        );
+        assert_eq!(entry.group_id, None);
+        assert_eq!(entry.reason, None);
+        assert_eq!(entry.project_dir, None);
+
+        let json = serde_json::to_string(&exported).unwrap();
+        for leak in ["shop", "/Users/al"] {
+            assert!(!json.contains(leak), "{} leaked: {}", leak, json);
+        }
+    }
+
+    #[test]
+    fn test_entries_sorted_is_deterministic() {
+        use super::super::types::{IcaAnalysisResponse, ProcessCategory};
+
+        let mut kb = KnowledgeBase::default();
+        let stored = [
+            ("vite", "Web", ProcessCategory::Frontend, Some("shop")),
+            ("redis-server", "Cache", ProcessCategory::Cache, None),
+            ("node", "API", ProcessCategory::Backend, Some("shop")),
+            ("python", "API", ProcessCategory::Backend, None),
+        ];
+        for (command, name, category, group) in stored {
+            let response = IcaAnalysisResponse {
+                display_name: name.to_string(),
+                category,
+                group_hint: group.map(str::to_string),
+                ..heuristic_response()
+            };
+            store_result(
+                &mut kb,
+                ProcessFingerprint::new(command),
+                response,
+                KnowledgeSource::Heuristic,
+            );
+        }
+
+        let order = |entries: Vec<&KnowledgeEntry>| -> Vec<String> {
+            entries
+                .iter()
+                .map(|e| e.fingerprint.command.clone())
+                .collect()
+        };
+        let first = order(entries_sorted(&kb));
+        assert_eq!(first, vec!["vite", "node", "python", "redis-server"]);
+        assert_eq!(order(entries_sorted(&kb)), first);
+
+        let groups = entries_by_group(&kb);
+        assert_eq!(order(groups[&None].clone()), vec!["python", "redis-server"]);
+        assert_eq!(order(groups[&Some("shop")].clone()), vec!["vite", "node"]);
+    }
+
+    #[test]
+    fn test_instance_count_for_active_replicas() {
+        let mut kb = KnowledgeBase::default();
+        let api = ProcessFingerprint::new("node").with_container_prefix("api");
+        let web = ProcessFingerprint::new("vite");
+        for fp in [api.clone(), web.clone()] {
+            store_result(
+                &mut kb,
+                fp,
+                heuristic_response(),
+                KnowledgeSource::Heuristic,
+            );
+        }
+
+        let unknown = ProcessFingerprint::new("mystery");
+        let mut active = vec![api.clone(); 3];
+        active.extend([web.clone(), unknown.clone()]);
+
+        assert_eq!(instance_count(&kb, &active, &api), 3);
+        assert_eq!(instance_count(&kb, &active, &web), 1);
+        assert_eq!(instance_count(&kb, &active, &unknown), 0);
+        assert_eq!(instance_counts(&kb, &active).len(), 2);
+    }
+
+    #[test]
+    fn test_user_defined_entry_is_not_relearned() {
+        let mut kb = KnowledgeBase::default();
+        let config = test_config();
+        let fp = ProcessFingerprint::new("node");
+        let context = AnalysisContext {
+            command: "node".to_string(),
+            display_name_override: Some("Shop API".to_string()),
+            ..Default::default()
+        };
+
+        // Applied on the first sighting without queueing an analysis
+        assert!(record_sighting(&mut kb, fp.clone(), context.clone(), &config).is_none());
+        let entry = lookup_entry(&kb, &fp).unwrap();
+        assert_eq!(entry.display_name, "Shop API");
+        assert_eq!(entry.source, KnowledgeSource::UserDefined);
+        assert!(kb.pending_analysis.is_empty());
+
+        store_result(
+            &mut kb,
+            fp.clone(),
+            heuristic_response(),
+            KnowledgeSource::ApiLearned,
+        );
+        assert_eq!(lookup_display_name(&kb, &fp).unwrap(), "Shop API");
+
+        // Later sightings just count
+        assert!(record_sighting(&mut kb, fp.clone(), context, &config).is_none());
+        assert_eq!(lookup_entry(&kb, &fp).unwrap().sightings, 2);
+    }
+
+    #[test]
+    fn test_glob_override_is_stored_once_for_matching_commands() {
+        let mut kb = KnowledgeBase::default();
+        let config = test_config();
+        let sighting = |command: &str| {
+            let fp = ProcessFingerprint::new(command).with_project_hash("shop");
+            let context = AnalysisContext {
+                command: command.to_string(),
+                display_name_override: Some("Shop Workers".to_string()),
+                command_glob_override: Some("*celery*".to_string()),
+                ..Default::default()
+            };
+            (fp, context)
+        };
+
+        for command in ["celery", "celery-beat", "celery"] {
+            let (fp, context) = sighting(command);
+            assert!(record_sighting(&mut kb, fp, context, &config).is_none());
+        }
+        assert_eq!(kb.entries.len(), 1);
+        assert!(kb.pending_analysis.is_empty());
+        let entry = kb.entries.values().next().unwrap();
+        assert_eq!(entry.fingerprint.command_glob.as_deref(), Some("*celery*"));
+        assert_eq!(entry.sightings, 3);
+
+        // Any matching command in the project resolves to it, others don't
+        let (flower, _) = sighting("celery-flower");
+        assert_eq!(
+            lookup_best(&kb, &flower).unwrap().display_name,
+            "Shop Workers"
+        );
+        let elsewhere = ProcessFingerprint::new("celery").with_project_hash("blog");
+        assert!(lookup_best(&kb, &elsewhere).is_none());
+    }
+
+    #[test]
+    fn test_pending_status_counts_toward_min_sightings() {
+        let mut kb = KnowledgeBase::default();
+        let config = LearningConfig {
+            min_sightings: 3,
+            ..test_config()
+        };
+        let fp = ProcessFingerprint::new("vite");
+        assert_eq!(pending_status(&kb, &fp, &config), None);
+
+        record_sighting(&mut kb, fp.clone(), AnalysisContext::new("vite"), &config);
+        assert_eq!(
+            pending_status(&kb, &fp, &config),
+            Some(PendingStatus {
+                sightings: 1,
+                required: 3,
+                remaining: 2,
+            })
+        );
+
+        record_sighting(&mut kb, fp.clone(), AnalysisContext::new("vite"), &config);
+        record_sighting(&mut kb, fp.clone(), AnalysisContext::new("vite"), &config);
+        assert_eq!(pending_status(&kb, &fp, &config).unwrap().remaining, 0);
+
+        store_result(
+            &mut kb,
+            fp.clone(),
+            heuristic_response(),
+            KnowledgeSource::Heuristic,
+        );
+        assert_eq!(pending_status(&kb, &fp, &config), None);
+    }
+
+    #[test]
+    fn test_category_min_sightings_override() {
+        let mut kb = KnowledgeBase::default();
+        let config = LearningConfig {
+            category_min_sightings: BTreeMap::from([(ProcessCategory::Database, 1)]),
+            ..test_config()
+        };
+        let mut sight = |command: &str| {
+            let fp = ProcessFingerprint::new(command);
+            record_sighting(&mut kb, fp, AnalysisContext::new(command), &config)
+        };
+
+        // Databases are trusted on the first sighting
+        assert_eq!(sight("postgres").unwrap().command, "postgres");
+        // Other commands still wait for the default threshold
+        assert!(sight("node").is_none());
+        assert!(sight("node").is_some());
+
+        let fp = ProcessFingerprint::new("postgres");
+        assert_eq!(pending_status(&kb, &fp, &config).unwrap().required, 1);
+    }
+
+    #[test]
+    fn test_ignored_command_is_never_queued() {
+        let mut kb = KnowledgeBase::default();
+        let config = LearningConfig {
+            min_sightings: 1,
+            ..test_config()
+        };
+
+        for command in ["ControlCe", "rapportd", "com.apple.geod"] {
+            let fp = ProcessFingerprint::new(command);
+            for _ in 0..3 {
+                let queued =
+                    record_sighting(&mut kb, fp.clone(), AnalysisContext::new(command), &config);
+                assert!(queued.is_none());
+            }
+        }
+        assert!(kb.pending_analysis.is_empty());
+
+        // Not ignored: queued as usual
+        record_sighting(
+            &mut kb,
+            ProcessFingerprint::new("node"),
+            AnalysisContext::new("node"),
+            &config,
+        );
+        assert_eq!(kb.pending_analysis.len(), 1);
+    }
+
+    #[test]
+    fn test_ignored_executable_path() {
+        let mut kb = KnowledgeBase::default();
+        let config = LearningConfig {
+            min_sightings: 1,
+            ignore_commands: vec!["$HOME/sandbox/*".to_string()],
+            ..test_config()
+        };
+        let home = std::env::var("HOME").unwrap_or_default();
+        let context = |path: &str| AnalysisContext {
+            executable_path: Some(format!("{}/{}", home, path)),
+            ..AnalysisContext::new("node")
+        };
+        let fp = ProcessFingerprint::new("node");
+
+        assert!(record_sighting(&mut kb, fp.clone(), context("sandbox/node"), &config).is_none());
+        assert!(kb.pending_analysis.is_empty());
+        assert!(record_sighting(&mut kb, fp, context("projects/node"), &config).is_some());
+    }
+
+    #[test]
+    fn test_ignore_patterns() {
+        use super::super::pattern::glob_match;
+
+        let patterns = vec![
+            "com.apple.*".to_string(),
+            "Helper".to_string(),
+            "redis-?".to_string(),
+        ];
+        assert!(is_ignored_command("com.apple.WebKit", &patterns));
+        assert!(!is_ignored_command("xcom.apple", &patterns));
+        assert!(is_ignored_command("Code Helper (Plugin)", &patterns));
+        assert!(is_ignored_command("redis-x", &patterns));
+        assert!(!is_ignored_command("redis-server", &patterns));
+        assert!(!is_ignored_command("node", &patterns));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(!glob_match("a*b", "ab-c"));
+    }
+
+    #[test]
+    fn test_explain_builtin_entry() {
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+
+        let explanation = explain(&kb, &ProcessFingerprint::new("postgres")).unwrap();
+        assert_eq!(explanation.source, KnowledgeSource::Builtin);
+        assert_eq!(explanation.confidence, 1.0);
+        assert_eq!(explanation.reason, "matched builtin 'postgres'");
+        assert_eq!(explanation.tier, MatchTier::Exact);
+        assert!(explain(&kb, &ProcessFingerprint::new("mystery")).is_none());
+    }
+
+    #[test]
+    fn test_explain_builtin_matched_by_live_fingerprint() {
+        let mut kb = KnowledgeBase::default();
+        super::super::builtin::populate_builtins(&mut kb);
+
+        // Live fingerprints pin the executable, which builtins leave open
+        let live = ProcessFingerprint::new("postgres")
+            .with_port(5432)
+            .with_executable_path("/opt/homebrew/opt/postgresql@16/bin/postgres");
+        assert!(lookup_entry(&kb, &live).is_none());
+
+        let explanation = explain(&kb, &live).unwrap();
+        assert_eq!(explanation.source, KnowledgeSource::Builtin);
+        assert_eq!(explanation.reason, "matched builtin 'postgres'");
+        assert_eq!(explanation.tier, MatchTier::Partial);
+    }
+
+    #[test]
+    fn test_explain_heuristic_entry() {
+        let mut kb = KnowledgeBase::default();
+        let context = AnalysisContext {
+            command: "node".to_string(),
+            container_name: Some("dss_app".to_string()),
+            container_prefix: Some("dss".to_string()),
+            ..Default::default()
+        };
+        let fp = ProcessFingerprint::new("node").with_container_prefix("dss");
+        store_result(
+            &mut kb,
+            fp.clone(),
+            super::super::fallback::generate_fallback(&context),
+            KnowledgeSource::Heuristic,
+        );
+
+        let explanation = explain(&kb, &fp).unwrap();
+        assert_eq!(explanation.source, KnowledgeSource::Heuristic);
+        assert_eq!(explanation.confidence, 0.6);
+        assert_eq!(explanation.reason, "heuristic: container prefix 'dss'");
+        assert_eq!(explanation.tier, MatchTier::Exact);
+
+        let glob = ProcessFingerprint::new("celery").with_command_glob("*celery*");
+        store_result(
+            &mut kb,
+            glob,
+            heuristic_response(),
+            KnowledgeSource::UserDefined,
+        );
+        let explanation = explain(&kb, &ProcessFingerprint::new("celery-beat")).unwrap();
+        assert_eq!(explanation.source, KnowledgeSource::UserDefined);
+        assert_eq!(explanation.tier, MatchTier::Glob);
+    }
 }
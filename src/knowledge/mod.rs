@@ -1,18 +1,63 @@
 pub mod types;
 pub mod storage;
 pub mod builtin;
+#[cfg(feature = "ica")]
 pub mod ica;
 pub mod fallback;
+pub mod json_extract;
+pub mod pattern;
 pub mod learning;
 pub mod worker;
 pub mod context_gatherer;
+pub mod http_probe;
+pub mod project_config;
+pub mod proxy_config;
+pub mod cli;
+pub mod redaction;
+pub mod audit;
 
 // Re-export commonly used items
 pub use types::{
-    AnalysisContext, KnowledgeBase, KnowledgeEntry, LearningConfig, ProcessCategory,
-    ProcessFingerprint,
+    project_hash, AnalysisContext, Confidence, FingerprintStrategy, GathererFlags,
+    KnowledgeBase, KnowledgeEntry, KnowledgeSource, LearningConfig, MatchTier, NameExplanation,
+    PendingStatus, ProcessCategory, ProcessFingerprint, Protocol,
 };
-pub use storage::{load_knowledge_base, save_knowledge_base};
-pub use learning::{lookup_display_name, lookup_entry, record_sighting, store_result};
-pub use worker::{spawn_learning_worker, AnalysisRequest, AnalysisResult};
-pub use context_gatherer::enrich_context;
+pub use storage::{
+    find_project_knowledge, load_knowledge_base, rebuild_index, save_knowledge_base, shutdown,
+    DebouncedSaver, FileStore, InMemoryStore, KnowledgeStore, LayeredStore,
+    PROJECT_KNOWLEDGE_FILE,
+};
+pub use builtin::canonical_command;
+pub use learning::{
+    apply_result, cleanup_stale_pending, entries_by_group, entries_sorted, explain, explain_with,
+    export_user_entries, export_user_entries_anonymized, instance_count, instance_counts,
+    is_ignored_command, is_ignored_process, known_commands, lookup_best, lookup_best_with,
+    lookup_display_name, lookup_entry, pending_status, prune_dead_projects,
+    prune_dead_projects_with, reclassify_heuristics, record_failure, record_sighting,
+    record_snapshot, reset_learned, set_hidden, stale_entries, store_result, subscribe,
+    visible_entries,
+};
+pub use worker::{
+    analyze_now, spawn_learning_worker, AnalysisBackend, AnalysisRequest, AnalysisResult,
+    AnalysisTiming, KnowledgeChange, KnowledgeEvent, LearningWorker, PromptHistory, PromptRecord,
+    RateLimitState,
+};
+#[cfg(feature = "tokio")]
+pub use worker::analyze_async;
+#[cfg(feature = "ica")]
+pub use ica::{validate_response_contract, AnalysisError};
+pub use context_gatherer::{
+    attach_siblings, detect_framework, enrich_context, enrich_context_with, group_process_tree,
+    parse_ssh_forward, CommandRunner, DetectedFramework, SystemRunner,
+};
+pub use project_config::{find_project_config, ProjectConfig, PROJECT_CONFIG_FILE};
+pub use proxy_config::{parse_caddy_upstreams, parse_nginx_upstreams, proxied_name};
+pub use cli::analyze_cli;
+pub use redaction::{PromptRedaction, DEFAULT_REDACT_PATTERNS};
+pub use audit::{AuditLog, AuditRecord, AUDIT_LOG_MAX_BYTES};
+pub use fallback::{
+    has_token, has_token_prefix, infer_category_from_command, infer_category_from_name,
+    parse_runtime_version, tokenize_command, wrapped_tool,
+};
+pub use json_extract::extract_first_json_object;
+pub use pattern::{expand_pattern, glob_match, matches_pattern, matches_process};
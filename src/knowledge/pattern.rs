@@ -0,0 +1,186 @@
+//! Matching of user-supplied command patterns (ignore lists, project
+//! overrides, fingerprint globs).
+//!
+//! Patterns may start with `~` and refer to environment variables as `$NAME`
+//! or `${NAME}`; both are expanded before matching, so an entry like
+//! `~/projects/*/node` works across machines. Patterns containing `/` after
+//! expansion are paths and match a process's executable path; all others
+//! match its command name.
+
+/// Match `text` against a glob (`*`, `?`) or, without wildcards, a
+/// substring; case-insensitive. The pattern is expanded first. Empty
+/// patterns match nothing.
+pub fn matches_pattern(pattern: &str, text: &str) -> bool {
+    matches_expanded(&expand_pattern(pattern), text)
+}
+
+/// Match a pattern against a process: path patterns against its executable
+/// path (never matching when that is unknown), others against `command`
+pub fn matches_process(pattern: &str, command: &str, executable_path: Option<&str>) -> bool {
+    let pattern = expand_pattern(pattern);
+    if is_path_pattern(&pattern) {
+        executable_path.is_some_and(|path| matches_expanded(&pattern, path))
+    } else {
+        matches_expanded(&pattern, command)
+    }
+}
+
+/// Whether an (expanded) pattern names a path rather than a command
+pub fn is_path_pattern(pattern: &str) -> bool {
+    pattern.contains('/')
+}
+
+fn matches_expanded(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    if pattern.contains(['*', '?']) {
+        glob_match(&pattern, &text)
+    } else {
+        !pattern.is_empty() && text.contains(&pattern)
+    }
+}
+
+/// Match `text` against a glob supporting `*` (any run) and `?` (one char)
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text index it was tried against
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Expand a leading `~` and `$NAME`/`${NAME}` references from the process
+/// environment
+pub fn expand_pattern(pattern: &str) -> String {
+    expand_pattern_with(pattern, |name| std::env::var(name).ok())
+}
+
+/// [`expand_pattern`] with an explicit variable lookup. `~` expands to
+/// `HOME`; references to unset variables are left as written.
+pub fn expand_pattern_with(pattern: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    if !pattern.contains(['~', '$']) {
+        return pattern.to_string();
+    }
+
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    if let Some(after) = rest.strip_prefix('~')
+        && (after.is_empty() || after.starts_with('/'))
+        && let Some(home) = lookup("HOME")
+    {
+        expanded.push_str(home.trim_end_matches('/'));
+        rest = after;
+    }
+
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        let (name, remainder) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        match lookup(name).filter(|_| is_variable_name(name)) {
+            Some(value) => {
+                expanded.push_str(&value);
+                rest = remainder;
+            }
+            None => {
+                expanded.push('$');
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(pattern: &str) -> String {
+        expand_pattern_with(pattern, |name| match name {
+            "HOME" => Some("/Users/dev".to_string()),
+            "PROJECTS" => Some("/Users/dev/projects".to_string()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_star_globs() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("node*", "node"));
+        assert!(glob_match("*/node", "/a/b/node"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(matches_pattern("Python*", "python3.12"));
+        assert!(!matches_pattern("", "python"));
+    }
+
+    #[test]
+    fn test_home_and_env_expansion() {
+        assert_eq!(expand("~"), "/Users/dev");
+        assert_eq!(expand("~/projects/*/node"), "/Users/dev/projects/*/node");
+        assert_eq!(expand("$HOME/bin"), "/Users/dev/bin");
+        assert_eq!(expand("${PROJECTS}/*/node"), "/Users/dev/projects/*/node");
+        assert_eq!(expand("$PROJECTS-old"), "/Users/dev/projects-old");
+        // Only a leading `~` on its own is the home directory
+        assert_eq!(expand("~alice/bin"), "~alice/bin");
+        assert_eq!(expand("a~b"), "a~b");
+        // Unset, malformed, and unterminated references stay literal
+        assert_eq!(expand("$UNSET/x"), "$UNSET/x");
+        assert_eq!(expand("cost$"), "cost$");
+        assert_eq!(expand("$1"), "$1");
+        assert_eq!(expand("${HOME"), "${HOME");
+        assert_eq!(expand("$$HOME"), "$/Users/dev");
+    }
+
+    #[test]
+    fn test_path_patterns_match_executable() {
+        let home = std::env::var("HOME").unwrap_or_default();
+        let exe = format!("{}/projects/shop/node", home);
+        assert!(matches_process("~/projects/*/node", "node", Some(&exe)));
+        assert!(matches_process("$HOME/projects/*/node", "node", Some(&exe)));
+        assert!(!matches_process("~/projects/*/node", "node", None));
+        assert!(!matches_process("~/work/*/node", "node", Some(&exe)));
+        // Command patterns ignore the path
+        assert!(matches_process("node", "node", Some("/usr/bin/node")));
+    }
+}
@@ -0,0 +1,301 @@
+//! Per-project overrides declared in a `.portkiller.toml` file.
+//!
+//! In monorepos the detected working directory is often too deep
+//! (e.g. `apps/api/dist`) to yield a useful project name. A `.portkiller.toml`
+//! in any ancestor directory can pin the project name, a group, and display
+//! names per command:
+//!
+//! ```toml
+//! project_name = "shop"
+//! group = "shop"
+//!
+//! [commands.node]
+//! display_name = "Shop API"
+//! description = "Storefront REST API"
+//!
+//! # Globs and paths (with `~`/`$VAR` expansion) match the executable
+//! [commands."~/projects/*/bin/worker"]
+//! display_name = "Shop Worker"
+//!
+//! # Command globs are learned as one entry covering every matching command
+//! [commands."*celery*"]
+//! display_name = "Shop Workers"
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::builtin::canonical_command;
+use super::fallback::generate_fallback;
+use super::pattern::{expand_pattern, is_path_pattern, matches_process};
+use super::types::{AnalysisContext, Confidence, IcaAnalysisResponse};
+
+/// File name looked up in the working directory and its ancestors
+pub const PROJECT_CONFIG_FILE: &str = ".portkiller.toml";
+
+/// Contents of a `.portkiller.toml` file
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ProjectConfig {
+    /// Project name to use instead of the working directory's name
+    pub project_name: Option<String>,
+    /// Group identifier shared by the project's services
+    pub group: Option<String>,
+    /// Overrides keyed by command name, command glob, or executable path
+    pub commands: HashMap<String, CommandOverride>,
+}
+
+/// Naming override for one command within a project
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CommandOverride {
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+}
+
+impl ProjectConfig {
+    /// Parse a project config file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    /// Override for `command`, matching either its exact or canonical name
+    pub fn command_override(&self, command: &str) -> Option<&CommandOverride> {
+        self.process_override(command, None)
+    }
+
+    /// Override for a process. Exact and canonical command names win; after
+    /// that the longest glob or path key matching the process is used.
+    pub fn process_override(
+        &self,
+        command: &str,
+        executable_path: Option<&str>,
+    ) -> Option<&CommandOverride> {
+        self.process_override_entry(command, executable_path)
+            .map(|(_, command_override)| command_override)
+    }
+
+    /// Like [`Self::process_override`], also returning the matching key
+    pub fn process_override_entry(
+        &self,
+        command: &str,
+        executable_path: Option<&str>,
+    ) -> Option<(&str, &CommandOverride)> {
+        self.commands
+            .get_key_value(command)
+            .or_else(|| self.commands.get_key_value(canonical_command(command)))
+            .or_else(|| {
+                self.commands
+                    .iter()
+                    .filter(|(key, _)| key.contains(['*', '?', '/', '~', '$']))
+                    .filter(|(key, _)| matches_process(key, command, executable_path))
+                    // Longest key first, ties broken by name for a stable choice
+                    .max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| b.cmp(a)))
+            })
+            .map(|(key, command_override)| (key.as_str(), command_override))
+    }
+}
+
+/// Find the nearest `.portkiller.toml` at or above `dir`
+pub fn find_project_config(dir: &Path) -> Option<(PathBuf, ProjectConfig)> {
+    let path = dir
+        .ancestors()
+        .map(|ancestor| ancestor.join(PROJECT_CONFIG_FILE))
+        .find(|candidate| candidate.is_file())?;
+    match ProjectConfig::load(&path) {
+        Ok(config) => Some((path, config)),
+        Err(e) => {
+            log::warn!("Ignoring project config: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Apply the nearest project config above `cwd` to the context
+pub fn enrich_from_project_config(ctx: &mut AnalysisContext, cwd: &Path) {
+    let Some((_, config)) = find_project_config(cwd) else {
+        return;
+    };
+
+    if let Some(name) = config.project_name.clone() {
+        ctx.project_name = Some(name);
+    }
+    if let Some(group) = config.group.clone() {
+        ctx.project_group = Some(group);
+    }
+    if let Some((key, command)) =
+        config.process_override_entry(&ctx.command, ctx.executable_path.as_deref())
+    {
+        ctx.display_name_override = command.display_name.clone();
+        ctx.description_override = command.description.clone();
+        ctx.command_glob_override = is_command_glob(key).then(|| key.to_string());
+    }
+}
+
+/// Whether a `[commands]` key is a glob over command names (not a path)
+fn is_command_glob(key: &str) -> bool {
+    key.contains(['*', '?']) && !is_path_pattern(&expand_pattern(key))
+}
+
+/// Response built from a project's display name override, if it declares one.
+/// User-defined names are authoritative, so confidence is always 1.0.
+pub fn user_defined_response(ctx: &AnalysisContext) -> Option<IcaAnalysisResponse> {
+    let display_name = ctx.display_name_override.clone()?;
+    let fallback = generate_fallback(ctx);
+    Some(IcaAnalysisResponse {
+        display_name,
+        description: ctx
+            .description_override
+            .clone()
+            .unwrap_or(fallback.description),
+        category: fallback.category,
+        group_hint: fallback.group_hint,
+        confidence: Confidence::FULL,
+        category_confidence: None,
+        reason: Some(format!("declared in {}", PROJECT_CONFIG_FILE)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Temporary directory removed on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_discovered_config_sets_project_and_group() {
+        let root = TempDir::new("portkiller-project-config");
+        let cwd = root.0.join("apps/api/dist");
+        fs::create_dir_all(&cwd).unwrap();
+        fs::write(
+            root.0.join(PROJECT_CONFIG_FILE),
+            "project_name = \"shop\"\ngroup = \"shop-stack\"\n\n\
+             [commands.node]\ndisplay_name = \"Shop API\"\n",
+        )
+        .unwrap();
+
+        let mut context = AnalysisContext {
+            command: "node".to_string(),
+            project_name: Some("dist".to_string()),
+            ..Default::default()
+        };
+        enrich_from_project_config(&mut context, &cwd);
+
+        assert_eq!(context.project_name.as_deref(), Some("shop"));
+        assert_eq!(context.project_group.as_deref(), Some("shop-stack"));
+        assert_eq!(context.display_name_override.as_deref(), Some("Shop API"));
+
+        let response = user_defined_response(&context).unwrap();
+        assert_eq!(response.display_name, "Shop API");
+        assert_eq!(response.group_hint.as_deref(), Some("shop-stack"));
+        assert_eq!(response.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_missing_or_invalid_config_is_ignored() {
+        let root = TempDir::new("portkiller-project-config-invalid");
+        let mut context = AnalysisContext::new("node");
+
+        enrich_from_project_config(&mut context, &root.0);
+        assert_eq!(context.project_name, None);
+
+        fs::write(root.0.join(PROJECT_CONFIG_FILE), "project_name = [").unwrap();
+        enrich_from_project_config(&mut context, &root.0);
+        assert_eq!(context.project_name, None);
+        assert!(user_defined_response(&context).is_none());
+    }
+
+    #[test]
+    fn test_glob_and_path_overrides() {
+        let config: ProjectConfig = toml::from_str(
+            "[commands.\"celery*\"]\ndisplay_name = \"Workers\"\n\n\
+             [commands.\"celery-beat*\"]\ndisplay_name = \"Scheduler\"\n\n\
+             [commands.\"~/projects/*/node\"]\ndisplay_name = \"Project Node\"\n\n\
+             [commands.node]\ndisplay_name = \"Any Node\"\n",
+        )
+        .unwrap();
+        let name = |command: &str, path: Option<&str>| {
+            config
+                .process_override(command, path)
+                .and_then(|o| o.display_name.clone())
+        };
+        let home = std::env::var("HOME").unwrap_or_default();
+        let project_node = format!("{}/projects/shop/node", home);
+
+        assert_eq!(name("celery", None).as_deref(), Some("Workers"));
+        // The more specific glob wins
+        assert_eq!(name("celery-beat", None).as_deref(), Some("Scheduler"));
+        // An exact command name outranks any path key
+        assert_eq!(
+            name("node", Some(&project_node)).as_deref(),
+            Some("Any Node")
+        );
+        assert_eq!(
+            name("node20", Some(&project_node)).as_deref(),
+            Some("Project Node")
+        );
+        assert_eq!(name("node20", Some("/usr/local/bin/node")), None);
+        // Plain keys still need an exact or canonical match
+        assert_eq!(name("nodemon", None), None);
+    }
+
+    #[test]
+    fn test_only_command_globs_are_recorded_on_the_context() {
+        let root = TempDir::new("portkiller-project-config-glob");
+        fs::write(
+            root.0.join(PROJECT_CONFIG_FILE),
+            "[commands.\"*celery*\"]\ndisplay_name = \"Workers\"\n\n\
+             [commands.\"/opt/*/node\"]\ndisplay_name = \"Opt Node\"\n\n\
+             [commands.vite]\ndisplay_name = \"Web\"\n",
+        )
+        .unwrap();
+        let glob_of = |command: &str, path: Option<&str>| {
+            let mut context = AnalysisContext {
+                command: command.to_string(),
+                executable_path: path.map(str::to_string),
+                ..Default::default()
+            };
+            enrich_from_project_config(&mut context, &root.0);
+            assert!(context.display_name_override.is_some());
+            context.command_glob_override
+        };
+
+        assert_eq!(glob_of("celery-beat", None).as_deref(), Some("*celery*"));
+        assert_eq!(glob_of("node", Some("/opt/shop/node")), None);
+        assert_eq!(glob_of("vite", None), None);
+    }
+
+    #[test]
+    fn test_command_override_matches_canonical_name() {
+        let config: ProjectConfig =
+            toml::from_str("[commands.postgres]\ndisplay_name = \"Shop DB\"\n").unwrap();
+        assert_eq!(
+            config
+                .command_override("postmaster")
+                .and_then(|o| o.display_name.as_deref()),
+            Some("Shop DB")
+        );
+    }
+}
@@ -0,0 +1,581 @@
+//! Upstreams of local reverse proxies, read from their config files.
+//!
+//! A listening nginx or Caddy is rarely interesting in itself; what matters
+//! is what it proxies to. The config is taken from the command line (`-c` for
+//! nginx, `--config` for Caddy) or the usual install locations, and its
+//! `proxy_pass`/`reverse_proxy` targets are collected so the process can be
+//! named e.g. "NGINX -> api:3000". nginx `upstream` blocks are resolved to
+//! their servers and `include`s are followed. All reads are bounded, and
+//! results are cached until a file they came from changes.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use super::pattern::glob_match;
+use super::types::AnalysisContext;
+
+/// Largest prefix of a config file that is read
+const MAX_CONFIG_BYTES: u64 = 256 * 1024;
+
+/// Most files read for one process, including the main config
+const MAX_CONFIG_FILES: usize = 16;
+
+/// Most upstreams kept per process
+const MAX_UPSTREAMS: usize = 8;
+
+/// Files and directories read for some upstreams, with their modification
+/// times when read
+type Sources = Vec<(PathBuf, Option<SystemTime>)>;
+
+/// Upstreams found for a config file, by the config's path
+static UPSTREAM_CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedUpstreams>>> = OnceLock::new();
+
+/// Upstreams of one config and everything read to find them
+#[derive(Debug)]
+struct CachedUpstreams {
+    sources: Sources,
+    upstreams: Vec<String>,
+}
+
+impl CachedUpstreams {
+    /// Whether no file or directory read has changed since
+    fn is_fresh(&self) -> bool {
+        self.sources
+            .iter()
+            .all(|(path, modified)| modified_time(path) == *modified)
+    }
+}
+
+/// Where Homebrew and system packages install nginx's main config
+const NGINX_DEFAULT_CONFIGS: &[&str] = &[
+    "/opt/homebrew/etc/nginx/nginx.conf",
+    "/usr/local/etc/nginx/nginx.conf",
+    "/etc/nginx/nginx.conf",
+];
+
+/// Where Homebrew and system packages install the Caddyfile
+const CADDY_DEFAULT_CONFIGS: &[&str] = &[
+    "/opt/homebrew/etc/Caddyfile",
+    "/usr/local/etc/Caddyfile",
+    "/etc/caddy/Caddyfile",
+];
+
+/// Reverse proxy whose config can be read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProxyKind {
+    Nginx,
+    Caddy,
+}
+
+impl ProxyKind {
+    fn from_command(command: &str) -> Option<Self> {
+        match command {
+            "nginx" => Some(Self::Nginx),
+            "caddy" => Some(Self::Caddy),
+            _ => None,
+        }
+    }
+
+    /// Command line option naming the config file
+    fn config_flags(self) -> &'static [&'static str] {
+        match self {
+            Self::Nginx => &["-c"],
+            Self::Caddy => &["--config", "-config"],
+        }
+    }
+}
+
+/// Fill `proxy_upstreams` for an nginx or Caddy process. Missing or
+/// unreadable configs leave the context untouched.
+pub fn enrich_from_proxy_config(ctx: &mut AnalysisContext) {
+    let Some(kind) = ProxyKind::from_command(&ctx.command) else {
+        return;
+    };
+    let cwd = ctx.working_directory.as_deref().map(Path::new);
+    let Some(path) = find_config(kind, ctx.full_command.as_deref(), cwd) else {
+        log::debug!("No {:?} config found for {}", kind, ctx.command);
+        return;
+    };
+    let upstreams = cached_upstreams(kind, &path);
+    if !upstreams.is_empty() {
+        ctx.proxy_upstreams = upstreams;
+    }
+}
+
+/// Upstreams of the config at `path`, read again only once a file (or
+/// globbed include directory) it was found in has changed
+fn cached_upstreams(kind: ProxyKind, path: &Path) -> Vec<String> {
+    let cache = UPSTREAM_CACHE.get_or_init(Default::default);
+    if let Some(cached) = cache.lock().unwrap().get(path)
+        && cached.is_fresh()
+    {
+        return cached.upstreams.clone();
+    }
+
+    let mut sources = Sources::new();
+    let upstreams = match kind {
+        ProxyKind::Nginx => read_nginx_upstreams(path, &mut sources),
+        ProxyKind::Caddy => {
+            sources.push((path.to_path_buf(), modified_time(path)));
+            read_bounded(path)
+                .map(|config| parse_caddy_upstreams(&config))
+                .unwrap_or_default()
+        }
+    };
+    let cached = CachedUpstreams {
+        sources,
+        upstreams: upstreams.clone(),
+    };
+    cache.lock().unwrap().insert(path.to_path_buf(), cached);
+    upstreams
+}
+
+/// Modification time of a file or directory, if it exists
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// `base` followed by where it proxies to, e.g. "NGINX -> api:3000"
+pub fn proxied_name(base: &str, upstreams: &[String]) -> String {
+    match upstreams {
+        [] => base.to_string(),
+        [only] => format!("{} -> {}", base, only),
+        [first, rest @ ..] => format!("{} -> {} +{}", base, first, rest.len()),
+    }
+}
+
+/// Config file named on the command line, else the first default that exists
+fn find_config(kind: ProxyKind, full_cmd: Option<&str>, cwd: Option<&Path>) -> Option<PathBuf> {
+    if let Some(path) = full_cmd.and_then(|cmd| config_flag(cmd, kind.config_flags())) {
+        let path = PathBuf::from(path);
+        return Some(match cwd {
+            Some(cwd) if path.is_relative() => cwd.join(path),
+            _ => path,
+        });
+    }
+
+    // `caddy run` without `--config` reads the Caddyfile in its directory
+    let local = match kind {
+        ProxyKind::Caddy => cwd.map(|cwd| cwd.join("Caddyfile")),
+        ProxyKind::Nginx => None,
+    };
+    let defaults = match kind {
+        ProxyKind::Nginx => NGINX_DEFAULT_CONFIGS,
+        ProxyKind::Caddy => CADDY_DEFAULT_CONFIGS,
+    };
+    local
+        .into_iter()
+        .chain(defaults.iter().map(PathBuf::from))
+        .find(|path| path.is_file())
+}
+
+/// Value of the first of `flags` on a command line, as `-c path` or
+/// `--config=path`
+fn config_flag<'a>(full_cmd: &'a str, flags: &[&str]) -> Option<&'a str> {
+    let mut args = full_cmd.split_whitespace().skip(1);
+    while let Some(arg) = args.next() {
+        for flag in flags {
+            if arg == *flag {
+                return args.next();
+            }
+            if let Some(value) = arg
+                .strip_prefix(flag)
+                .and_then(|rest| rest.strip_prefix('='))
+            {
+                return Some(value);
+            }
+        }
+    }
+    None
+}
+
+/// Read at most [`MAX_CONFIG_BYTES`] of a file
+fn read_bounded(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let mut bytes = Vec::new();
+    file.take(MAX_CONFIG_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|e| log::debug!("Failed to read {}: {}", path.display(), e))
+        .ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Upstreams of an nginx config and the files it includes, adding what was
+/// read to `sources`
+fn read_nginx_upstreams(main: &Path, sources: &mut Sources) -> Vec<String> {
+    // Relative includes are resolved against the main config's directory
+    let base = main.parent().unwrap_or(Path::new("/"));
+    let mut directives = NginxDirectives::default();
+    let mut queue = vec![main.to_path_buf()];
+    let mut read = 0;
+    while let Some(path) = queue.pop() {
+        if read == MAX_CONFIG_FILES {
+            log::debug!("Stopped following nginx includes at {}", path.display());
+            break;
+        }
+        read += 1;
+        sources.push((path.clone(), modified_time(&path)));
+        let Some(config) = read_bounded(&path) else {
+            continue;
+        };
+        let includes = directives.collect(&config);
+        for include in includes.iter().rev() {
+            queue.extend(resolve_include(base, include, sources).into_iter().rev());
+        }
+    }
+    directives.resolve()
+}
+
+/// Files matched by an nginx `include`, which may glob its file name. A
+/// globbed directory is added to `sources`, as files may appear in it.
+fn resolve_include(base: &Path, include: &str, sources: &mut Sources) -> Vec<PathBuf> {
+    let path = base.join(include);
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return Vec::new();
+    };
+    if !name.contains(['*', '?']) {
+        return vec![path];
+    }
+    let dir = path.parent().unwrap_or(base);
+    sources.push((dir.to_path_buf(), modified_time(dir)));
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut matched: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| glob_match(name, &entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .take(MAX_CONFIG_FILES)
+        .collect();
+    matched.sort();
+    matched
+}
+
+/// Proxy-related directives gathered from one or more nginx config files
+#[derive(Debug, Default)]
+struct NginxDirectives {
+    /// `upstream` block name -> its `server` addresses
+    upstreams: HashMap<String, Vec<String>>,
+    /// `proxy_pass` targets in order of appearance
+    proxy_passes: Vec<String>,
+}
+
+impl NginxDirectives {
+    /// Add the directives of one file, returning its `include` arguments
+    fn collect(&mut self, config: &str) -> Vec<String> {
+        let mut includes = Vec::new();
+        let mut upstream: Option<String> = None;
+        let mut words: Vec<String> = Vec::new();
+        for token in nginx_tokens(config) {
+            match token.as_str() {
+                "{" => {
+                    if let [keyword, name] = words.as_slice()
+                        && keyword == "upstream"
+                    {
+                        upstream = Some(name.clone());
+                    }
+                    words.clear();
+                }
+                "}" => {
+                    upstream = None;
+                    words.clear();
+                }
+                ";" => {
+                    match (words.first().map(String::as_str), words.get(1)) {
+                        (Some("proxy_pass"), Some(target)) => {
+                            self.proxy_passes.push(target.clone());
+                        }
+                        (Some("server"), Some(address)) => {
+                            // `server` outside an upstream block is a virtual host
+                            if let Some(ref name) = upstream {
+                                let servers = self.upstreams.entry(name.clone()).or_default();
+                                servers.push(address.clone());
+                            }
+                        }
+                        (Some("include"), Some(path)) => includes.push(path.clone()),
+                        _ => {}
+                    }
+                    words.clear();
+                }
+                _ => words.push(token),
+            }
+        }
+        includes
+    }
+
+    /// `proxy_pass` targets, with upstream names replaced by their servers
+    fn resolve(&self) -> Vec<String> {
+        let mut resolved = Vec::new();
+        for target in &self.proxy_passes {
+            // Targets built from variables can't be resolved statically
+            if target.contains('$') {
+                continue;
+            }
+            let Some(host) = upstream_address(target) else {
+                continue;
+            };
+            match self.upstreams.get(&host) {
+                Some(servers) => {
+                    resolved.extend(servers.iter().filter_map(|s| upstream_address(s)))
+                }
+                None => resolved.push(host),
+            }
+        }
+        dedup_upstreams(resolved)
+    }
+}
+
+/// Upstreams of a sample nginx config, without following `include`s
+pub fn parse_nginx_upstreams(config: &str) -> Vec<String> {
+    let mut directives = NginxDirectives::default();
+    directives.collect(config);
+    directives.resolve()
+}
+
+/// Split nginx config into words and `;`, `{`, `}` tokens, dropping comments
+/// and the quotes around quoted words
+fn nginx_tokens(config: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for line in config.lines() {
+        let line = line.split_once('#').map_or(line, |(code, _)| code);
+        let mut word = String::new();
+        for c in line.chars() {
+            match c {
+                ';' | '{' | '}' => {
+                    if !word.is_empty() {
+                        tokens.push(std::mem::take(&mut word));
+                    }
+                    tokens.push(c.to_string());
+                }
+                '"' | '\'' => {}
+                c if c.is_whitespace() => {
+                    if !word.is_empty() {
+                        tokens.push(std::mem::take(&mut word));
+                    }
+                }
+                c => word.push(c),
+            }
+        }
+        if !word.is_empty() {
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+/// Upstreams of a Caddyfile: `reverse_proxy [matcher] upstreams...` and the
+/// `to` lines of `reverse_proxy { ... }` blocks
+pub fn parse_caddy_upstreams(config: &str) -> Vec<String> {
+    let mut upstreams = Vec::new();
+    let mut in_block = false;
+    for line in config.lines() {
+        let line = match line.find(" #") {
+            Some(comment) => &line[..comment],
+            None if line.trim_start().starts_with('#') => "",
+            None => line,
+        };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("reverse_proxy") => {
+                for word in words {
+                    if word == "{" {
+                        in_block = true;
+                        break;
+                    }
+                    // Matchers: named (`@api`), path (`/api/*`), or any (`*`)
+                    if !word.starts_with(['@', '/', '*']) {
+                        upstreams.extend(upstream_address(word));
+                    }
+                }
+            }
+            Some("to") if in_block => upstreams.extend(words.filter_map(upstream_address)),
+            Some("}") => in_block = false,
+            _ => {}
+        }
+    }
+    dedup_upstreams(upstreams)
+}
+
+/// `host:port` of a proxy target URL or address, e.g. "api:3000" for
+/// `http://api:3000/v1`. Caddy's `:3000` is on localhost; Unix sockets have
+/// no address.
+fn upstream_address(target: &str) -> Option<String> {
+    let address = target.split_once("://").map_or(target, |(_, rest)| rest);
+    let address = address.split('/').next().unwrap_or_default();
+    if address.is_empty() || address.starts_with("unix:") {
+        return None;
+    }
+    Some(match address.strip_prefix(':') {
+        Some(port) => format!("localhost:{}", port),
+        None => address.to_string(),
+    })
+}
+
+/// Drop repeated upstreams, keeping the first [`MAX_UPSTREAMS`] in order
+fn dedup_upstreams(upstreams: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for upstream in upstreams {
+        if !unique.contains(&upstream) {
+            unique.push(upstream);
+        }
+    }
+    unique.truncate(MAX_UPSTREAMS);
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("portkiller-proxy-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_nginx_proxy_pass() {
+        assert_eq!(
+            parse_nginx_upstreams("location / { proxy_pass http://api:3000; }"),
+            ["api:3000"]
+        );
+
+        let config = r#"
+            upstream backend {
+                server 127.0.0.1:8001 weight=2;
+                server 127.0.0.1:8002;
+            }
+            server {
+                listen 80;
+                # proxy_pass http://commented-out:1;
+                location /api/ { proxy_pass "http://backend/api/"; }
+                location /ws { proxy_pass http://127.0.0.1:8001; }
+                location /dyn { proxy_pass http://$host:9000; }
+                location /sock { proxy_pass http://unix:/tmp/app.sock:; }
+            }
+        "#;
+        assert_eq!(
+            parse_nginx_upstreams(config),
+            ["127.0.0.1:8001", "127.0.0.1:8002"]
+        );
+    }
+
+    #[test]
+    fn test_parse_caddy_reverse_proxy() {
+        let config = r#"
+            localhost:8080 {
+                reverse_proxy /api/* api:3000 # the API
+                reverse_proxy @static :5173
+                reverse_proxy {
+                    to http://worker-1:9000 worker-2:9000
+                    lb_policy first
+                }
+            }
+        "#;
+        assert_eq!(
+            parse_caddy_upstreams(config),
+            [
+                "api:3000",
+                "localhost:5173",
+                "worker-1:9000",
+                "worker-2:9000"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enrich_follows_config_flag_and_includes() {
+        let dir = temp_dir("nginx");
+        fs::create_dir_all(dir.join("servers")).unwrap();
+        fs::write(
+            dir.join("nginx.conf"),
+            "http { include servers/*.conf; include missing.conf; }",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("servers/shop.conf"),
+            "server { location / { proxy_pass http://api:3000; } }",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("servers/notes.txt"),
+            "proxy_pass http://ignored:1;",
+        )
+        .unwrap();
+
+        let mut ctx = AnalysisContext {
+            full_command: Some(format!(
+                "nginx: master process /opt/homebrew/bin/nginx -c {}",
+                dir.join("nginx.conf").display()
+            )),
+            ..AnalysisContext::new("nginx")
+        };
+        enrich_from_proxy_config(&mut ctx);
+        assert_eq!(ctx.proxy_upstreams, ["api:3000"]);
+        assert_eq!(
+            proxied_name("NGINX", &ctx.proxy_upstreams),
+            "NGINX -> api:3000"
+        );
+
+        // Missing configs and other commands are left alone
+        let mut ctx = AnalysisContext {
+            full_command: Some("nginx -c /nonexistent/nginx.conf".to_string()),
+            ..AnalysisContext::new("nginx")
+        };
+        enrich_from_proxy_config(&mut ctx);
+        assert!(ctx.proxy_upstreams.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_upstreams_cached_until_config_changes() {
+        let dir = temp_dir("cache");
+        let config = dir.join("Caddyfile");
+        let write = |upstream: &str, modified: SystemTime| {
+            fs::write(&config, format!("reverse_proxy {}", upstream)).unwrap();
+            File::options()
+                .write(true)
+                .open(&config)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let then = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+
+        write("api:3000", then);
+        assert_eq!(cached_upstreams(ProxyKind::Caddy, &config), ["api:3000"]);
+        // Same modification time: the cached result is used
+        write("api:4000", then);
+        assert_eq!(cached_upstreams(ProxyKind::Caddy, &config), ["api:3000"]);
+        // Changed: read again
+        write("api:4000", then + std::time::Duration::from_secs(60));
+        assert_eq!(cached_upstreams(ProxyKind::Caddy, &config), ["api:4000"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_config_flag_forms() {
+        assert_eq!(
+            config_flag("caddy run --config /etc/Caddyfile", &["--config"]),
+            Some("/etc/Caddyfile")
+        );
+        assert_eq!(
+            config_flag("caddy run --config=Caddyfile.dev", &["--config"]),
+            Some("Caddyfile.dev")
+        );
+        assert_eq!(config_flag("nginx -g daemon off;", &["-c"]), None);
+        assert_eq!(
+            proxied_name("Caddy", &["a:1".to_string(), "b:2".to_string()]),
+            "Caddy -> a:1 +1"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Scrubbing of prompt text before it is sent to a remote backend.
+
+use super::pattern::glob_match;
+use super::types::LearningConfig;
+
+/// Words dropped from prompts by default: credential-looking assignments
+/// (including `.env` style `KEY=value` lines), well-known token prefixes, and
+/// URLs with embedded credentials. Globs, matched case-insensitively.
+pub const DEFAULT_REDACT_PATTERNS: &[&str] = &[
+    "*token*=*",
+    "*secret*=*",
+    "*passw*=*",
+    "*api_key*=*",
+    "*apikey*=*",
+    "*_key=*",
+    "ghp_*",
+    "gho_*",
+    "github_pat_*",
+    "glpat-*",
+    "sk-*",
+    "xox?-*",
+    "akia*",
+    "*://*:*@*",
+];
+
+/// Placeholder left where a denylisted value was removed
+const REDACTED: &str = "[redacted]";
+
+/// Redaction applied to prompt text
+#[derive(Clone, Debug)]
+pub struct PromptRedaction {
+    home: Option<String>,
+    patterns: Vec<String>,
+}
+
+impl PromptRedaction {
+    pub fn new(home: Option<&str>, patterns: &[String]) -> Self {
+        Self {
+            home: home
+                .map(|home| home.trim_end_matches('/').to_string())
+                .filter(|home| !home.is_empty()),
+            patterns: patterns.iter().map(|p| p.to_lowercase()).collect(),
+        }
+    }
+
+    /// Redaction for the current user, or `None` when disabled in config
+    pub fn from_config(config: &LearningConfig) -> Option<Self> {
+        if !config.redact_prompts {
+            return None;
+        }
+        let home = std::env::var("HOME").ok();
+        Some(Self::new(home.as_deref(), &config.redact_patterns))
+    }
+
+    /// Replace the home directory with `~` and denylisted words with a placeholder
+    pub fn apply(&self, text: &str) -> String {
+        let text = match self.home {
+            Some(ref home) => replace_home(text, home),
+            None => text.to_string(),
+        };
+        text.lines()
+            .map(|line| {
+                line.split(' ')
+                    .map(|word| self.redact_word(word))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn redact_word<'a>(&self, word: &'a str) -> std::borrow::Cow<'a, str> {
+        let lower = word.to_lowercase();
+        if !self.patterns.iter().any(|p| glob_match(p, &lower)) {
+            return word.into();
+        }
+        // Keep the name of an assignment so the prompt still reads sensibly
+        match word.split_once('=') {
+            Some((key, _)) if !key.contains("://") => format!("{}={}", key, REDACTED).into(),
+            _ => REDACTED.into(),
+        }
+    }
+}
+
+/// Replace `home` with `~` where it is a whole path prefix, not part of a
+/// longer name (`/Users/al` must not rewrite `/Users/alice`)
+fn replace_home(text: &str, home: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(home) {
+        let after = &rest[index + home.len()..];
+        out.push_str(&rest[..index]);
+        let boundary = after
+            .chars()
+            .next()
+            .is_none_or(|c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')));
+        out.push_str(if boundary { "~" } else { home });
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_redaction(home: &str) -> PromptRedaction {
+        let patterns: Vec<String> = DEFAULT_REDACT_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        PromptRedaction::new(Some(home), &patterns)
+    }
+
+    #[test]
+    fn test_home_directory_becomes_tilde() {
+        let redaction = default_redaction("/Users/al/");
+        let prompt = "Executable: /Users/al/.nvm/bin/node\n\
+                      Working directory: /Users/al\n\
+                      Full command: node /Users/alice/server.js";
+
+        assert_eq!(
+            redaction.apply(prompt),
+            "Executable: ~/.nvm/bin/node\n\
+             Working directory: ~\n\
+             Full command: node /Users/alice/server.js"
+        );
+    }
+
+    #[test]
+    fn test_token_looking_strings_are_removed() {
+        let redaction = default_redaction("/Users/al");
+        let prompt = "Full command: node server.js --token=abc123 ghp_0123456789abcdef\n\
+                      Container command: env API_KEY=s3cret DATABASE_URL=postgres://u:pw@db/app";
+        let redacted = redaction.apply(prompt);
+
+        for secret in ["abc123", "ghp_0123456789abcdef", "s3cret", "pw@db"] {
+            assert!(
+                !redacted.contains(secret),
+                "{} leaked: {}",
+                secret,
+                redacted
+            );
+        }
+        assert!(redacted.contains("node server.js --token=[redacted]"));
+        assert!(redacted.contains("API_KEY=[redacted]"));
+    }
+
+    #[test]
+    fn test_disabled_in_config() {
+        let config = LearningConfig {
+            redact_prompts: false,
+            ..Default::default()
+        };
+        assert!(PromptRedaction::from_config(&config).is_none());
+        assert!(PromptRedaction::from_config(&LearningConfig::default()).is_some());
+    }
+}
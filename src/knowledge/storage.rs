@@ -1,13 +1,407 @@
+use std::collections::HashMap;
 use std::fs::{self, Permissions};
+use std::io::{Read, Write};
 use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
+use crossbeam_channel::Receiver;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 
-use super::types::KnowledgeBase;
+use super::learning::apply_result;
+use super::types::{KnowledgeBase, KnowledgeEntry, KnowledgeSource};
+use super::worker::KnowledgeEvent;
 
 const KNOWLEDGE_FILE: &str = ".portkiller-knowledge.json";
-const CURRENT_VERSION: u32 = 1;
+/// Project knowledge file looked up in the working directory and its ancestors
+pub const PROJECT_KNOWLEDGE_FILE: &str = KNOWLEDGE_FILE;
+const CURRENT_VERSION: u32 = 2;
+/// Leading bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Persistence backend for the knowledge base
+pub trait KnowledgeStore {
+    /// Load the knowledge base, creating a new one if none exists yet
+    fn load(&self) -> Result<KnowledgeBase>;
+
+    /// Persist the knowledge base
+    fn save(&self, kb: &KnowledgeBase) -> Result<()>;
+}
+
+/// Knowledge base stored as a JSON file on the local filesystem.
+///
+/// Gzip-compressed files are detected by their magic bytes and read
+/// transparently; writes are compressed when enabled or when the path ends
+/// in `.gz`. With a size cap, the lowest-value learned entries are left out
+/// of the file until it fits.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    compress: bool,
+    max_bytes: Option<u64>,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            compress: false,
+            max_bytes: None,
+        }
+    }
+
+    /// Gzip the file on save
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Keep the written file at most `max_bytes` long (`None` = no cap)
+    pub fn with_max_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether saves write gzip-compressed JSON
+    pub fn is_compressed(&self) -> bool {
+        self.compress || self.path.extension().is_some_and(|ext| ext == "gz")
+    }
+
+    /// Save `kb`, evicting the lowest-value entries from the written copy
+    /// while it exceeds the size cap. Returns the number of entries evicted;
+    /// `kb` itself is untouched.
+    pub fn save_pruned(&self, kb: &KnowledgeBase) -> Result<usize> {
+        let mut bytes = self.encode(kb)?;
+        let mut pruned = 0;
+        if let Some(max_bytes) = self.max_bytes
+            && bytes.len() as u64 > max_bytes
+        {
+            (bytes, pruned) = self.encode_within(kb, max_bytes)?;
+            log::info!(
+                "Pruned {} knowledge entries to keep {} under {} bytes",
+                pruned,
+                self.path.display(),
+                max_bytes
+            );
+        }
+        self.write(&bytes)?;
+        Ok(pruned)
+    }
+
+    /// Serialize `kb` as written to disk
+    fn encode(&self, kb: &KnowledgeBase) -> Result<Vec<u8>> {
+        let content =
+            serde_json::to_string_pretty(kb).context("failed to serialize knowledge base")?;
+        if self.is_compressed() {
+            gzip(content.as_bytes())
+        } else {
+            Ok(content.into_bytes())
+        }
+    }
+
+    /// Encode `kb` without the fewest evictable entries that brings it within
+    /// `max_bytes`, or without all of them if it never fits
+    fn encode_within(&self, kb: &KnowledgeBase, max_bytes: u64) -> Result<(Vec<u8>, usize)> {
+        let order = eviction_order(kb);
+        let without = |count: usize| {
+            let mut pruned = kb.clone();
+            for key in &order[..count] {
+                pruned.entries.remove(*key);
+            }
+            self.encode(&pruned)
+        };
+
+        // Binary search on the number evicted; the size only shrinks as it grows
+        let (mut low, mut high) = (1, order.len());
+        let mut best = None;
+        while low <= high {
+            let count = low + (high - low) / 2;
+            let bytes = without(count)?;
+            if bytes.len() as u64 <= max_bytes {
+                best = Some((bytes, count));
+                high = count - 1;
+            } else {
+                low = count + 1;
+            }
+        }
+        match best {
+            Some(best) => Ok(best),
+            None => {
+                log::warn!(
+                    "Knowledge base exceeds {} bytes even without learned entries",
+                    max_bytes
+                );
+                Ok((without(order.len())?, order.len()))
+            }
+        }
+    }
+
+    fn write(&self, bytes: &[u8]) -> Result<()> {
+        let mut file =
+            fs::File::create(&self.path).context("failed to write knowledge base file")?;
+        file.write_all(bytes)
+            .context("failed to write knowledge base file")?;
+        // Set secure permissions (owner read/write only)
+        fs::set_permissions(&self.path, Permissions::from_mode(0o600))
+            .context("failed to set knowledge base file permissions")?;
+        file.sync_all()
+            .context("failed to sync knowledge base file")
+    }
+}
+
+impl Default for FileStore {
+    fn default() -> Self {
+        Self::new(get_knowledge_path())
+    }
+}
+
+impl KnowledgeStore for FileStore {
+    fn load(&self) -> Result<KnowledgeBase> {
+        if self.path.exists() {
+            let bytes = fs::read(&self.path).context("failed to read knowledge base file")?;
+            let content = decode_contents(&bytes)?;
+            let kb: KnowledgeBase =
+                serde_json::from_str(&content).context("failed to parse knowledge base file")?;
+            finish_load(self, kb)
+        } else {
+            let kb = new_knowledge_base();
+            self.save(&kb)?;
+            Ok(kb)
+        }
+    }
+
+    fn save(&self, kb: &KnowledgeBase) -> Result<()> {
+        self.save_pruned(kb).map(|_| ())
+    }
+}
+
+/// Knowledge base kept in memory only (useful for tests)
+#[derive(Debug, Default)]
+pub struct InMemoryStore {
+    kb: Mutex<Option<KnowledgeBase>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a store that already holds the given knowledge base
+    pub fn with_knowledge_base(kb: KnowledgeBase) -> Self {
+        Self {
+            kb: Mutex::new(Some(kb)),
+        }
+    }
+
+    /// Snapshot of the last saved knowledge base, if any
+    pub fn snapshot(&self) -> Option<KnowledgeBase> {
+        self.kb.lock().ok()?.clone()
+    }
+}
+
+impl KnowledgeStore for InMemoryStore {
+    fn load(&self) -> Result<KnowledgeBase> {
+        match self.snapshot() {
+            Some(kb) => finish_load(self, kb),
+            None => {
+                let kb = new_knowledge_base();
+                self.save(&kb)?;
+                Ok(kb)
+            }
+        }
+    }
+
+    fn save(&self, kb: &KnowledgeBase) -> Result<()> {
+        let mut stored = self
+            .kb
+            .lock()
+            .map_err(|_| anyhow::anyhow!("in-memory knowledge store lock poisoned"))?;
+        *stored = Some(kb.clone());
+        Ok(())
+    }
+}
+
+/// Global knowledge base with an optional project-local layer on top.
+///
+/// Project-scoped entries (those whose fingerprint carries a project hash)
+/// live in the project layer and shadow global entries with the same
+/// fingerprint; everything else, including the analysis queues, stays in the
+/// global layer.
+#[derive(Debug)]
+pub struct LayeredStore<G: KnowledgeStore, P: KnowledgeStore> {
+    global: G,
+    project: Option<P>,
+}
+
+impl<G: KnowledgeStore, P: KnowledgeStore> LayeredStore<G, P> {
+    pub fn new(global: G, project: Option<P>) -> Self {
+        Self { global, project }
+    }
+
+    pub fn global(&self) -> &G {
+        &self.global
+    }
+
+    pub fn project(&self) -> Option<&P> {
+        self.project.as_ref()
+    }
+}
+
+impl LayeredStore<FileStore, FileStore> {
+    /// Global store plus the nearest project knowledge file at or above `dir`
+    pub fn discover(global: FileStore, dir: &Path) -> Self {
+        let project = find_project_knowledge(dir, global.path()).map(|path| {
+            log::info!("Using project knowledge base {}", path.display());
+            FileStore::new(path).with_compression(global.compress)
+        });
+        Self::new(global, project)
+    }
+}
+
+impl<G: KnowledgeStore, P: KnowledgeStore> KnowledgeStore for LayeredStore<G, P> {
+    fn load(&self) -> Result<KnowledgeBase> {
+        let mut kb = self.global.load()?;
+        if let Some(project) = &self.project {
+            let layer = project.load()?;
+            // Builtins are populated into every layer; only the global copy counts
+            kb.entries.extend(
+                layer
+                    .entries
+                    .into_iter()
+                    .filter(|(_, entry)| entry.source != KnowledgeSource::Builtin),
+            );
+        }
+        Ok(kb)
+    }
+
+    fn save(&self, kb: &KnowledgeBase) -> Result<()> {
+        let Some(project) = &self.project else {
+            return self.global.save(kb);
+        };
+        let (project_entries, global_entries) = kb
+            .entries
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.clone()))
+            .partition(|(_, entry)| is_project_scoped(entry));
+        project.save(&KnowledgeBase {
+            version: kb.version,
+            entries: project_entries,
+            ..Default::default()
+        })?;
+        self.global.save(&KnowledgeBase {
+            entries: global_entries,
+            ..kb.clone()
+        })
+    }
+}
+
+/// Coalesces save requests so a burst of learning writes the store at most
+/// once per interval. Pending changes are written by [`tick`](Self::tick)
+/// once the interval has passed, or immediately by [`flush`](Self::flush).
+#[derive(Debug)]
+pub struct DebouncedSaver<S: KnowledgeStore> {
+    store: S,
+    interval: Duration,
+    last_save: Option<Instant>,
+    dirty: bool,
+}
+
+impl<S: KnowledgeStore> DebouncedSaver<S> {
+    pub fn new(store: S, interval: Duration) -> Self {
+        Self {
+            store,
+            interval,
+            last_save: None,
+            dirty: false,
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Whether changes are waiting to be written
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Note that `kb` changed; writes now unless a write happened within the
+    /// interval. Returns whether a write happened.
+    pub fn request_save(&mut self, kb: &KnowledgeBase) -> Result<bool> {
+        self.dirty = true;
+        self.tick(kb)
+    }
+
+    /// Write pending changes if the interval since the last write has passed
+    pub fn tick(&mut self, kb: &KnowledgeBase) -> Result<bool> {
+        let due = self
+            .last_save
+            .is_none_or(|last_save| last_save.elapsed() >= self.interval);
+        if self.dirty && due {
+            self.save_now(kb)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Write pending changes immediately (e.g. on shutdown)
+    pub fn flush(&mut self, kb: &KnowledgeBase) -> Result<bool> {
+        if !self.dirty {
+            return Ok(false);
+        }
+        self.save_now(kb)?;
+        Ok(true)
+    }
+
+    /// Treat a knowledge event as a save request where appropriate
+    pub fn handle_event(&mut self, event: &KnowledgeEvent, kb: &KnowledgeBase) -> Result<bool> {
+        match event {
+            KnowledgeEvent::SaveKnowledgeBase => self.request_save(kb),
+            KnowledgeEvent::Maintenance => self.tick(kb),
+            KnowledgeEvent::AnalysisComplete(_) | KnowledgeEvent::EntryChanged(_) => Ok(false),
+        }
+    }
+
+    fn save_now(&mut self, kb: &KnowledgeBase) -> Result<()> {
+        // Even a failed write restarts the interval so errors aren't retried in a tight loop
+        self.last_save = Some(Instant::now());
+        self.store.save(kb)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Persist everything on the way out, once the learning worker has stopped
+/// (its request sender dropped and its thread joined) and nothing else reads
+/// `events`.
+///
+/// Results the worker sent on `events` are stored first, then the
+/// knowledge base is written through `saver` even if nothing looks dirty, so
+/// pending sightings and failure backoffs are kept too. Returns the number
+/// of results applied once the write has completed.
+pub fn shutdown<S: KnowledgeStore>(
+    kb: &mut KnowledgeBase,
+    saver: &mut DebouncedSaver<S>,
+    events: &Receiver<KnowledgeEvent>,
+) -> Result<usize> {
+    let mut applied = 0;
+    for event in events.try_iter() {
+        if let KnowledgeEvent::AnalysisComplete(result) = event {
+            apply_result(kb, *result);
+            applied += 1;
+        }
+    }
+    saver.save_now(kb)?;
+    Ok(applied)
+}
 
 /// Get the path to the knowledge base file
 pub fn get_knowledge_path() -> PathBuf {
@@ -15,59 +409,549 @@ pub fn get_knowledge_path() -> PathBuf {
     PathBuf::from(home).join(KNOWLEDGE_FILE)
 }
 
+/// Find the nearest project knowledge file at or above `dir`, ignoring the
+/// global file at `global` (which shares the same name in the home directory)
+pub fn find_project_knowledge(dir: &Path, global: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(PROJECT_KNOWLEDGE_FILE))
+        .find(|candidate| candidate != global && candidate.is_file())
+}
+
+/// Keys of the entries a size cap may evict, least valuable first: heuristic
+/// names (cheap to regenerate) before learned ones, then the least seen and
+/// least recently active. Builtin, user-defined, and hidden entries are kept.
+fn eviction_order(kb: &KnowledgeBase) -> Vec<&str> {
+    let mut evictable: Vec<(&String, &KnowledgeEntry)> = kb
+        .entries
+        .iter()
+        .filter(|(_, entry)| !entry.hidden)
+        .filter(|(_, entry)| {
+            matches!(
+                entry.source,
+                KnowledgeSource::Heuristic | KnowledgeSource::ApiLearned
+            )
+        })
+        .collect();
+    evictable.sort_by_key(|(key, entry)| {
+        (
+            entry.source != KnowledgeSource::Heuristic,
+            entry.sightings,
+            entry.last_active(),
+            *key,
+        )
+    });
+    evictable.into_iter().map(|(key, _)| key.as_str()).collect()
+}
+
+/// Whether an entry belongs in the project layer
+fn is_project_scoped(entry: &KnowledgeEntry) -> bool {
+    entry.fingerprint.project_hash.is_some()
+}
+
 /// Load the knowledge base from disk, creating a new one if it doesn't exist
 pub fn load_knowledge_base() -> Result<KnowledgeBase> {
-    let path = get_knowledge_path();
-
-    if path.exists() {
-        let content = fs::read_to_string(&path).context("failed to read knowledge base file")?;
-        let mut kb: KnowledgeBase =
-            serde_json::from_str(&content).context("failed to parse knowledge base file")?;
+    FileStore::default().load()
+}
 
-        // Handle version migrations if needed
-        if kb.version < CURRENT_VERSION {
-            kb = migrate_knowledge_base(kb)?;
-            save_knowledge_base(&kb)?;
-        }
+/// Save the knowledge base to disk
+pub fn save_knowledge_base(kb: &KnowledgeBase) -> Result<()> {
+    FileStore::default().save(kb)
+}
 
-        Ok(kb)
+/// Decode file contents as UTF-8 JSON, gunzipping first if needed
+fn decode_contents(bytes: &[u8]) -> Result<String> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut content = String::new();
+        GzDecoder::new(bytes)
+            .read_to_string(&mut content)
+            .context("failed to decompress knowledge base file")?;
+        Ok(content)
     } else {
-        // Create new knowledge base with builtins
-        let mut kb = KnowledgeBase::default();
-        kb.version = CURRENT_VERSION;
-        super::builtin::populate_builtins(&mut kb);
-        save_knowledge_base(&kb)?;
-        Ok(kb)
+        String::from_utf8(bytes.to_vec()).context("knowledge base file is not valid UTF-8")
     }
 }
 
-/// Save the knowledge base to disk
-pub fn save_knowledge_base(kb: &KnowledgeBase) -> Result<()> {
-    let path = get_knowledge_path();
-    let content =
-        serde_json::to_string_pretty(kb).context("failed to serialize knowledge base")?;
-    fs::write(&path, &content).context("failed to write knowledge base file")?;
-    // Set secure permissions (owner read/write only)
-    fs::set_permissions(&path, Permissions::from_mode(0o600))
-        .context("failed to set knowledge base file permissions")?;
-    Ok(())
+fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .context("failed to compress knowledge base")?;
+    encoder
+        .finish()
+        .context("failed to compress knowledge base")
+}
+
+/// Create a new knowledge base with builtins
+fn new_knowledge_base() -> KnowledgeBase {
+    let mut kb = KnowledgeBase {
+        version: CURRENT_VERSION,
+        ..Default::default()
+    };
+    super::builtin::populate_builtins(&mut kb);
+    kb
+}
+
+/// Apply version migrations and this release's builtins to a freshly loaded
+/// knowledge base, saving it if either changed anything
+fn finish_load(store: &impl KnowledgeStore, mut kb: KnowledgeBase) -> Result<KnowledgeBase> {
+    let mut changed = false;
+    // Handle version migrations if needed
+    if kb.version < CURRENT_VERSION {
+        kb = migrate_knowledge_base(kb)?;
+        changed = true;
+    }
+    // Builtins added or reworded since the file was written
+    changed |= super::builtin::populate_builtins(&mut kb);
+    if changed {
+        store.save(&kb)?;
+    }
+    Ok(kb)
 }
 
 /// Migrate knowledge base from older versions
 fn migrate_knowledge_base(mut kb: KnowledgeBase) -> Result<KnowledgeBase> {
-    // Future migrations can be added here
-    // For now, just update the version
+    // Future migrations can be added here. Keys are recomputed every time, so
+    // a version bump is all a change to `hash_key` needs.
+    rebuild_index(&mut kb);
     kb.version = CURRENT_VERSION;
     Ok(kb)
 }
 
+/// Re-key entries, pending analyses, and failure records by their
+/// fingerprint's current [`hash_key`](super::types::ProcessFingerprint::hash_key),
+/// for when the hashing changes. Of entries that end up sharing a key, the
+/// most recently updated one is kept. Returns the number of keys that changed.
+pub fn rebuild_index(kb: &mut KnowledgeBase) -> usize {
+    let mut renamed: HashMap<String, String> = HashMap::new();
+
+    let mut entries: HashMap<String, KnowledgeEntry> = HashMap::new();
+    for (old_key, entry) in kb.entries.drain() {
+        let key = entry.fingerprint.hash_key();
+        if key != old_key {
+            renamed.insert(old_key, key.clone());
+        }
+        match entries.get(&key) {
+            Some(kept) if kept.updated_at >= entry.updated_at => {}
+            _ => {
+                entries.insert(key, entry);
+            }
+        }
+    }
+    kb.entries = entries;
+
+    let pending = std::mem::take(&mut kb.pending_analysis);
+    for (old_key, entry) in pending {
+        let key = entry.fingerprint.hash_key();
+        if key != old_key {
+            renamed.insert(old_key, key.clone());
+        }
+        kb.pending_analysis.insert(key, entry);
+    }
+
+    // Failure records carry no fingerprint; follow the keys renamed above
+    let failed = std::mem::take(&mut kb.failed_analysis);
+    kb.failed_analysis = failed
+        .into_iter()
+        .map(|(key, record)| (renamed.get(&key).cloned().unwrap_or(key), record))
+        .collect();
+
+    renamed.len()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::knowledge::types::ProcessFingerprint;
 
     #[test]
     fn test_get_knowledge_path() {
         let path = get_knowledge_path();
         assert!(path.to_string_lossy().ends_with(KNOWLEDGE_FILE));
     }
+
+    #[test]
+    fn test_in_memory_load_creates_builtins() {
+        let store = InMemoryStore::new();
+        let kb = store.load().unwrap();
+
+        assert_eq!(kb.version, CURRENT_VERSION);
+        assert!(
+            kb.entries
+                .contains_key(&ProcessFingerprint::new("postgres").hash_key())
+        );
+        // The new knowledge base is persisted on first load
+        assert!(store.snapshot().is_some());
+    }
+
+    #[test]
+    fn test_in_memory_round_trip() {
+        let store = InMemoryStore::new();
+        let mut kb = store.load().unwrap();
+        let learned = ProcessFingerprint::new("my-tool");
+        let mut entry = kb.entries.values().next().unwrap().clone();
+        entry.fingerprint = learned.clone();
+        entry.source = KnowledgeSource::ApiLearned;
+        kb.entries.insert(learned.hash_key(), entry);
+        store.save(&kb).unwrap();
+
+        let reloaded = store.load().unwrap();
+        assert_eq!(reloaded.entries.len(), kb.entries.len());
+        assert!(reloaded.entries.contains_key(&learned.hash_key()));
+        assert_eq!(reloaded.version, CURRENT_VERSION);
+    }
+
+    #[test]
+    fn test_rebuild_index_rekeys_by_fingerprint() {
+        use crate::knowledge::types::{AnalysisContext, FailureRecord, PendingEntry};
+
+        let mut kb = InMemoryStore::new().load().unwrap();
+        let postgres = ProcessFingerprint::new("postgres").hash_key();
+        let builtin = kb.entries[&postgres].clone();
+        let builtins = kb.entries.len();
+
+        // Keys from an older hashing, one of them a stale duplicate
+        let tool = ProcessFingerprint::new("my-tool");
+        let mut entry = builtin.clone();
+        entry.fingerprint = tool.clone();
+        entry.display_name = "My Tool".to_string();
+        entry.updated_at = 200;
+        kb.entries.insert("old-tool".to_string(), entry.clone());
+        entry.display_name = "Stale".to_string();
+        entry.updated_at = 100;
+        kb.entries.insert("older-tool".to_string(), entry);
+
+        let other = ProcessFingerprint::new("other");
+        kb.pending_analysis.insert(
+            "old-other".to_string(),
+            PendingEntry {
+                fingerprint: other.clone(),
+                sightings: 1,
+                first_seen: 0,
+                last_seen: 0,
+                context: AnalysisContext::new("other"),
+            },
+        );
+        let failure = FailureRecord {
+            failures: 2,
+            last_attempt: 0,
+        };
+        kb.failed_analysis.insert("old-other".to_string(), failure);
+
+        assert_eq!(rebuild_index(&mut kb), 3);
+        assert_eq!(kb.entries.len(), builtins + 1);
+        assert_eq!(kb.entries[&tool.hash_key()].display_name, "My Tool");
+        assert_eq!(kb.entries[&postgres].display_name, builtin.display_name);
+        assert!(kb.pending_analysis.contains_key(&other.hash_key()));
+        assert_eq!(kb.failed_analysis[&other.hash_key()].failures, 2);
+
+        // Already current: nothing to do
+        assert_eq!(rebuild_index(&mut kb), 0);
+    }
+
+    #[test]
+    fn test_reload_adds_new_builtins_and_keeps_learned() {
+        let store = InMemoryStore::new();
+        let mut kb = store.load().unwrap();
+        let builtins = kb.entries.len();
+        // A file written before the postgres builtin existed, in which the
+        // user's redis has been learned
+        let postgres = ProcessFingerprint::new("postgres").hash_key();
+        let redis = ProcessFingerprint::new("redis-server").hash_key();
+        kb.entries.remove(&postgres);
+        let learned = kb.entries.get_mut(&redis).unwrap();
+        learned.source = KnowledgeSource::ApiLearned;
+        learned.display_name = "Shop Cache".to_string();
+        store.save(&kb).unwrap();
+
+        let reloaded = store.load().unwrap();
+        assert_eq!(reloaded.entries.len(), builtins);
+        assert!(reloaded.entries.contains_key(&postgres));
+        assert_eq!(reloaded.entries[&redis].display_name, "Shop Cache");
+        assert_eq!(reloaded.entries[&redis].source, KnowledgeSource::ApiLearned);
+        // The refreshed knowledge base is persisted
+        assert!(store.snapshot().unwrap().entries.contains_key(&postgres));
+    }
+
+    #[test]
+    fn test_file_store_compressed_round_trip() {
+        let dir = std::env::temp_dir().join(format!("portkiller-kb-gz-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("knowledge.json.gz");
+        let store = FileStore::new(&path);
+        assert!(store.is_compressed());
+
+        let kb = store.load().unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(&GZIP_MAGIC));
+
+        let reloaded = store.load().unwrap();
+        assert_eq!(
+            serde_json::to_value(&reloaded).unwrap(),
+            serde_json::to_value(&kb).unwrap()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_store_reads_plain_json_when_compressing() {
+        let dir = std::env::temp_dir().join(format!("portkiller-kb-plain-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("knowledge.json");
+        let kb = FileStore::new(&path).load().unwrap();
+        assert!(fs::read(&path).unwrap().starts_with(b"{"));
+
+        // Existing plain files still load, and are rewritten compressed
+        let store = FileStore::new(&path).with_compression(true);
+        let reloaded = store.load().unwrap();
+        assert_eq!(reloaded.entries.len(), kb.entries.len());
+        store.save(&reloaded).unwrap();
+        assert!(fs::read(&path).unwrap().starts_with(&GZIP_MAGIC));
+        assert_eq!(store.load().unwrap().entries.len(), kb.entries.len());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_size_cap_prunes_lowest_value_entries() {
+        let dir = std::env::temp_dir().join(format!("portkiller-kb-cap-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("knowledge.json");
+        let mut kb = FileStore::new(&path).load().unwrap();
+        let builtin_size = fs::metadata(&path).unwrap().len();
+
+        for i in 0..20 {
+            let fingerprint = ProcessFingerprint::new(&format!("worker-{}", i));
+            let mut entry = learned_entry(&kb, &fingerprint, &format!("Worker {}", i));
+            entry.sightings = i + 1;
+            kb.entries.insert(entry.hash_key(), entry);
+        }
+        let guess = ProcessFingerprint::new("mystery");
+        let mut heuristic = learned_entry(&kb, &guess, "Mystery");
+        heuristic.source = KnowledgeSource::Heuristic;
+        heuristic.sightings = 100;
+        kb.entries.insert(heuristic.hash_key(), heuristic);
+
+        let store = FileStore::new(&path);
+        store.save(&kb).unwrap();
+        let full_size = fs::metadata(&path).unwrap().len();
+
+        // Room for roughly half of the learned entries
+        let max_bytes = (builtin_size + full_size) / 2;
+        let store = store.with_max_bytes(Some(max_bytes));
+        let total = kb.entries.len();
+        let pruned = store.save_pruned(&kb).unwrap();
+        assert!(pruned > 1 && pruned < 21, "pruned {}", pruned);
+        assert!(fs::metadata(&path).unwrap().len() <= max_bytes);
+        // Only the written copy is pruned
+        assert_eq!(kb.entries.len(), total);
+
+        let saved = store.load().unwrap();
+        assert_eq!(saved.entries.len(), total - pruned);
+        // Heuristic guesses go first, then the least seen learned entries
+        let kept = |command: &str| {
+            let key = ProcessFingerprint::new(command).hash_key();
+            saved.entries.contains_key(&key)
+        };
+        assert!(!kept("mystery"));
+        assert!(!kept("worker-0"));
+        assert!(kept("worker-19"));
+        assert!(kept("postgres"));
+
+        // Under the cap nothing is pruned
+        assert_eq!(store.save_pruned(&saved).unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn learned_entry(
+        kb: &KnowledgeBase,
+        fingerprint: &ProcessFingerprint,
+        name: &str,
+    ) -> KnowledgeEntry {
+        let mut entry = kb.entries.values().next().unwrap().clone();
+        entry.fingerprint = fingerprint.clone();
+        entry.source = KnowledgeSource::ApiLearned;
+        entry.display_name = name.to_string();
+        entry
+    }
+
+    #[test]
+    fn test_project_layer_shadows_global_entry() {
+        let fingerprint = ProcessFingerprint::new("node").with_project_hash("shop");
+        let key = fingerprint.hash_key();
+        let mut global_kb = InMemoryStore::new().load().unwrap();
+        let global_entry = learned_entry(&global_kb, &fingerprint, "Node Server");
+        global_kb.entries.insert(key.clone(), global_entry);
+        let mut project_kb = KnowledgeBase::default();
+        let project_entry = learned_entry(&global_kb, &fingerprint, "Shop API");
+        project_kb.entries.insert(key.clone(), project_entry);
+
+        let store = LayeredStore::new(
+            InMemoryStore::with_knowledge_base(global_kb),
+            Some(InMemoryStore::with_knowledge_base(project_kb)),
+        );
+        let kb = store.load().unwrap();
+        assert_eq!(kb.entries[&key].display_name, "Shop API");
+        assert!(
+            kb.entries
+                .contains_key(&ProcessFingerprint::new("postgres").hash_key())
+        );
+    }
+
+    #[test]
+    fn test_layered_save_writes_project_entries_to_project_layer() {
+        let store = LayeredStore::new(InMemoryStore::new(), Some(InMemoryStore::new()));
+        let mut kb = store.load().unwrap();
+        let scoped = ProcessFingerprint::new("node").with_project_hash("shop");
+        let global = ProcessFingerprint::new("my-tool");
+        let scoped_entry = learned_entry(&kb, &scoped, "Shop API");
+        let global_entry = learned_entry(&kb, &global, "My Tool");
+        kb.entries.insert(scoped.hash_key(), scoped_entry);
+        kb.entries.insert(global.hash_key(), global_entry);
+        store.save(&kb).unwrap();
+
+        let project = store.project().unwrap().snapshot().unwrap();
+        let global_layer = store.global().snapshot().unwrap();
+        assert_eq!(project.entries.len(), 1);
+        assert!(project.entries.contains_key(&scoped.hash_key()));
+        assert!(!global_layer.entries.contains_key(&scoped.hash_key()));
+        assert!(global_layer.entries.contains_key(&global.hash_key()));
+        assert_eq!(store.load().unwrap().entries.len(), kb.entries.len());
+    }
+
+    #[test]
+    fn test_find_project_knowledge_skips_global_file() {
+        let root = std::env::temp_dir().join(format!("portkiller-kb-layer-{}", std::process::id()));
+        let cwd = root.join("shop/apps/api");
+        fs::create_dir_all(&cwd).unwrap();
+        let global = root.join(KNOWLEDGE_FILE);
+        fs::write(&global, "{}").unwrap();
+        assert_eq!(find_project_knowledge(&cwd, &global), None);
+
+        let project = root.join("shop").join(PROJECT_KNOWLEDGE_FILE);
+        fs::write(&project, "{}").unwrap();
+        assert_eq!(find_project_knowledge(&cwd, &global), Some(project));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// Counts writes instead of persisting anything
+    #[derive(Default)]
+    struct CountingStore {
+        saves: std::cell::Cell<usize>,
+    }
+
+    impl KnowledgeStore for CountingStore {
+        fn load(&self) -> Result<KnowledgeBase> {
+            Ok(KnowledgeBase::default())
+        }
+
+        fn save(&self, _kb: &KnowledgeBase) -> Result<()> {
+            self.saves.set(self.saves.get() + 1);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_debounced_saver_coalesces_rapid_requests() {
+        let kb = KnowledgeBase::default();
+        let mut saver = DebouncedSaver::new(CountingStore::default(), Duration::from_secs(60));
+
+        for _ in 0..50 {
+            saver
+                .handle_event(&KnowledgeEvent::SaveKnowledgeBase, &kb)
+                .unwrap();
+            saver.tick(&kb).unwrap();
+        }
+        assert_eq!(saver.store().saves.get(), 1);
+        assert!(saver.is_dirty());
+
+        // Shutdown writes what's pending right away
+        assert!(saver.flush(&kb).unwrap());
+        assert_eq!(saver.store().saves.get(), 2);
+        assert!(!saver.flush(&kb).unwrap());
+    }
+
+    #[test]
+    fn test_debounced_saver_writes_pending_after_interval() {
+        let kb = KnowledgeBase::default();
+        let mut saver = DebouncedSaver::new(CountingStore::default(), Duration::from_millis(20));
+
+        saver.request_save(&kb).unwrap();
+        saver.request_save(&kb).unwrap();
+        assert_eq!(saver.store().saves.get(), 1);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(saver.tick(&kb).unwrap());
+        assert_eq!(saver.store().saves.get(), 2);
+        // Nothing new to write
+        assert!(!saver.tick(&kb).unwrap());
+    }
+
+    #[test]
+    fn test_shutdown_persists_latest_state() {
+        use crate::knowledge::fallback::generate_fallback;
+        use crate::knowledge::learning::{lookup_entry, record_sighting};
+        use crate::knowledge::types::{AnalysisContext, LearningConfig};
+        use crate::knowledge::worker::{AnalysisResult, AnalysisTiming};
+
+        let store = InMemoryStore::new();
+        let mut kb = store.load().unwrap();
+        let mut saver = DebouncedSaver::new(store, Duration::from_secs(60));
+        saver.request_save(&kb).unwrap();
+
+        // Changes made inside the debounce window, never saved
+        let config = LearningConfig::default();
+        let pending = ProcessFingerprint::new("my-daemon");
+        let context = AnalysisContext::new("my-daemon");
+        record_sighting(&mut kb, pending.clone(), context, &config);
+        // A result the worker sent that the app hasn't handled yet
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let learned = ProcessFingerprint::new("uvicorn");
+        let result = AnalysisResult {
+            fingerprint: learned.clone(),
+            response: generate_fallback(&AnalysisContext::new("uvicorn")),
+            source: KnowledgeSource::Heuristic,
+            failed: true,
+            timing: AnalysisTiming::default(),
+        };
+        tx.send(KnowledgeEvent::Maintenance).unwrap();
+        tx.send(KnowledgeEvent::AnalysisComplete(Box::new(result)))
+            .unwrap();
+
+        assert_eq!(shutdown(&mut kb, &mut saver, &rx).unwrap(), 1);
+        let saved = saver.store().snapshot().unwrap();
+        assert!(lookup_entry(&saved, &learned).is_some());
+        assert!(saved.failed_analysis.contains_key(&learned.hash_key()));
+        assert!(saved.pending_analysis.contains_key(&pending.hash_key()));
+        assert_eq!(
+            serde_json::to_value(&saved).unwrap(),
+            serde_json::to_value(&kb).unwrap()
+        );
+        assert!(!saver.is_dirty());
+    }
+
+    #[test]
+    fn test_in_memory_migrates_old_version() {
+        let store = InMemoryStore::with_knowledge_base(KnowledgeBase::default());
+        let kb = store.load().unwrap();
+
+        assert_eq!(kb.version, CURRENT_VERSION);
+        assert_eq!(store.snapshot().unwrap().version, CURRENT_VERSION);
+    }
+
+    #[test]
+    fn test_load_rekeys_entries_from_older_hashing() {
+        let mut old = InMemoryStore::new().load().unwrap();
+        let tool = ProcessFingerprint::new("my-tool");
+        let mut entry = old.entries.values().next().unwrap().clone();
+        entry.fingerprint = tool.clone();
+        entry.source = KnowledgeSource::ApiLearned;
+        // Version 1 keyed entries with `DefaultHasher`
+        old.entries.insert("1f2e3d4c5b6a7988".to_string(), entry);
+        old.version = 1;
+
+        let store = InMemoryStore::with_knowledge_base(old);
+        let kb = store.load().unwrap();
+        assert_eq!(kb.version, CURRENT_VERSION);
+        assert!(kb.entries.contains_key(&tool.hash_key()));
+        assert!(!kb.entries.contains_key("1f2e3d4c5b6a7988"));
+    }
 }
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Unique identifier for a process based on its characteristics
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -13,6 +14,18 @@ pub struct ProcessFingerprint {
     pub project_hash: Option<String>,
     /// Docker container prefix (e.g., "dss" from "dss_app")
     pub container_prefix: Option<String>,
+    /// Hash of the executable path, separating e.g. the `python` of two venvs
+    #[serde(default)]
+    pub exe_hash: Option<String>,
+    /// Pattern matched against live commands instead of `command`: a glob
+    /// (`*`, `?`) or substring, case-insensitive. Lets one entry cover many
+    /// commands (e.g. every `*celery*` worker of a project).
+    #[serde(default)]
+    pub command_glob: Option<String>,
+    /// Short digest of a container's image, so an image upgrade is a new
+    /// process (see `LearningConfig::fingerprint_image_digest`)
+    #[serde(default)]
+    pub image_digest: Option<String>,
 }
 
 impl ProcessFingerprint {
@@ -22,6 +35,9 @@ impl ProcessFingerprint {
             default_port: None,
             project_hash: None,
             container_prefix: None,
+            exe_hash: None,
+            command_glob: None,
+            image_digest: None,
         }
     }
 
@@ -35,32 +51,197 @@ impl ProcessFingerprint {
         self
     }
 
+    /// Build the fingerprint for an (enriched) analysis context.
+    ///
+    /// Containers are told apart by `strategy`; other processes by their
+    /// project (see [`project_hash`]) and executable path, so e.g. the
+    /// `python` of two venvs get separate entries. (A container's executable
+    /// and directory are those of Docker's port proxy, which say nothing
+    /// about it.)
+    pub fn from_context(context: &AnalysisContext, strategy: FingerprintStrategy) -> Self {
+        let mut fingerprint = Self::new(super::builtin::canonical_command(&context.command));
+        if let Some(container_key) = strategy.container_key(context) {
+            return fingerprint.with_container_prefix(&container_key);
+        }
+        if let Some(ref dir) = context.working_directory {
+            fingerprint = fingerprint.with_project_dir(Path::new(dir));
+        }
+        if let Some(ref path) = context.executable_path {
+            fingerprint = fingerprint.with_executable_path(path);
+        }
+        fingerprint
+    }
+
+    /// [`from_context`](Self::from_context) as configured, pinning the image
+    /// digest of containers when `fingerprint_image_digest` is set
+    pub fn from_config(context: &AnalysisContext, config: &LearningConfig) -> Self {
+        let fingerprint = Self::from_context(context, config.fingerprint_strategy);
+        match context.docker_image_digest {
+            Some(ref digest) if config.fingerprint_image_digest => {
+                fingerprint.with_image_digest(digest)
+            }
+            _ => fingerprint,
+        }
+    }
+
+    /// Set the project hash from a working directory (see [`project_hash`])
+    pub fn with_project_dir(mut self, dir: &Path) -> Self {
+        self.project_hash = project_hash(dir);
+        self
+    }
+
     pub fn with_container_prefix(mut self, prefix: &str) -> Self {
         self.container_prefix = Some(prefix.to_string());
         self
     }
 
-    /// Generate a unique hash key for lookups
+    /// Set the executable hash directly, e.g. one read back from an export
+    pub fn with_exe_hash(mut self, hash: &str) -> Self {
+        self.exe_hash = Some(hash.to_string());
+        self
+    }
+
+    /// Distinguish this fingerprint by the process's executable path
+    pub fn with_executable_path(mut self, path: &str) -> Self {
+        self.exe_hash = Some(format!("{:016x}", fnv1a_64(path.as_bytes())));
+        self
+    }
+
+    /// Pin the image the container runs, by a short prefix of its digest
+    /// (e.g. "sha256:4f2a…" becomes "4f2a…", 12 hex digits)
+    pub fn with_image_digest(mut self, digest: &str) -> Self {
+        let hex = digest.strip_prefix("sha256:").unwrap_or(digest);
+        self.image_digest = Some(hex.chars().take(IMAGE_DIGEST_LEN).collect());
+        self
+    }
+
+    /// Match live commands against `pattern` rather than `command` exactly
+    pub fn with_command_glob(mut self, pattern: &str) -> Self {
+        self.command_glob = Some(pattern.to_string());
+        self
+    }
+
+    /// Whether this (stored) fingerprint describes the live `fingerprint`.
+    ///
+    /// The command must match canonically, or via `command_glob` when set.
+    /// Every other field this fingerprint pins must be equal; fields it leaves
+    /// unset match anything.
+    pub fn matches(&self, fingerprint: &ProcessFingerprint) -> bool {
+        fn pinned<T: PartialEq>(stored: &Option<T>, live: &Option<T>) -> bool {
+            stored.is_none() || stored == live
+        }
+
+        let command_matches = match self.command_glob {
+            Some(ref pattern) => super::pattern::matches_pattern(pattern, &fingerprint.command),
+            None => {
+                super::builtin::canonical_command(&self.command)
+                    == super::builtin::canonical_command(&fingerprint.command)
+            }
+        };
+        command_matches
+            && pinned(&self.default_port, &fingerprint.default_port)
+            && pinned(&self.project_hash, &fingerprint.project_hash)
+            && pinned(&self.container_prefix, &fingerprint.container_prefix)
+            && pinned(&self.exe_hash, &fingerprint.exe_hash)
+            && pinned(&self.image_digest, &fingerprint.image_digest)
+    }
+
+    /// Generate a unique hash key for lookups. Stable across Rust releases
+    /// and platforms, since keys are persisted.
     pub fn hash_key(&self) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        let mut hasher = DefaultHasher::new();
-        self.command.hash(&mut hasher);
-        self.default_port.hash(&mut hasher);
-        self.project_hash.hash(&mut hasher);
-        self.container_prefix.hash(&mut hasher);
-        format!("{:016x}", hasher.finish())
+        let port = self.default_port.map(|port| port.to_string());
+        let fields = [
+            (b'c', Some(self.command.as_str())),
+            (b'p', port.as_deref()),
+            (b'j', self.project_hash.as_deref()),
+            (b'k', self.container_prefix.as_deref()),
+            (b'e', self.exe_hash.as_deref()),
+            (b'g', self.command_glob.as_deref()),
+            (b'i', self.image_digest.as_deref()),
+        ];
+        // Each set field as its tag, value, and a terminator that can't
+        // occur in UTF-8, so no two fingerprints encode alike
+        let mut bytes = Vec::new();
+        for (tag, value) in fields {
+            if let Some(value) = value {
+                bytes.push(tag);
+                bytes.extend_from_slice(value.as_bytes());
+                bytes.push(0xff);
+            }
+        }
+        format!("{:016x}", fnv1a_64(&bytes))
     }
 }
 
-/// Category of process for grouping and display
-#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+/// Hex digits of an image digest kept in fingerprints
+const IMAGE_DIGEST_LEN: usize = 12;
+
+/// How container processes are distinguished in fingerprints
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FingerprintStrategy {
+    /// Container name prefix (e.g., "dss" from "dss_app"). The default, as
+    /// existing knowledge is stored under these keys.
+    #[default]
+    ContainerPrefix,
+    /// Compose project and service, falling back to the name prefix, so
+    /// re-created containers with fresh instance names share knowledge.
+    /// Switching to it re-learns containers already known by their prefix.
+    ComposeService,
+}
+
+impl FingerprintStrategy {
+    /// Container component of the fingerprint, if the context is a container
+    fn container_key(self, context: &AnalysisContext) -> Option<String> {
+        let compose_key = match (&context.docker_project, &context.docker_service) {
+            (Some(project), Some(service)) => Some(format!("{}/{}", project, service)),
+            (None, Some(service)) => Some(service.clone()),
+            _ => None,
+        };
+        match self {
+            Self::ContainerPrefix => context.container_prefix.clone(),
+            Self::ComposeService => compose_key.or_else(|| context.container_prefix.clone()),
+        }
+    }
+}
+
+/// Portable hash identifying the project a directory belongs to.
+///
+/// Derived from the git repository name (or the leaf directory name outside
+/// a repository) rather than the absolute path, so the same repo cloned at
+/// different locations or on different machines shares knowledge.
+pub fn project_hash(dir: &Path) -> Option<String> {
+    let root = dir
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(dir);
+    let name = root.file_name()?.to_string_lossy().to_lowercase();
+    Some(format!("{:016x}", fnv1a_64(name.as_bytes())))
+}
+
+/// FNV-1a; unlike `DefaultHasher` its output is stable across Rust releases
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Category of process for grouping and display; variants are ordered as
+/// they appear in the menu
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
 #[serde(rename_all = "lowercase")]
 pub enum ProcessCategory {
     Frontend,
     Backend,
     Database,
     Cache,
+    /// Search engines (e.g. Meilisearch, Typesense)
+    Search,
     Proxy,
+    /// The ICA prompt spells this `dev_tool`
+    #[serde(alias = "dev_tool")]
     DevTool,
     Infrastructure,
     Unknown,
@@ -72,6 +253,70 @@ impl Default for ProcessCategory {
     }
 }
 
+impl ProcessCategory {
+    /// Every category, in menu order
+    pub fn all() -> &'static [ProcessCategory] {
+        &[
+            Self::Frontend,
+            Self::Backend,
+            Self::Database,
+            Self::Cache,
+            Self::Search,
+            Self::Proxy,
+            Self::DevTool,
+            Self::Infrastructure,
+            Self::Unknown,
+        ]
+    }
+
+    /// Human-friendly name (e.g. "Dev Tool")
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Frontend => "Frontend",
+            Self::Backend => "Backend",
+            Self::Database => "Database",
+            Self::Cache => "Cache",
+            Self::Search => "Search",
+            Self::Proxy => "Proxy",
+            Self::DevTool => "Dev Tool",
+            Self::Infrastructure => "Infrastructure",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for ProcessCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Transport protocol of a listening socket
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    /// Parse a protocol name as reported by lsof (e.g. "TCP", "UDP")
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_uppercase().as_str() {
+            "TCP" => Some(Self::Tcp),
+            "UDP" => Some(Self::Udp),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Tcp => "TCP",
+            Self::Udp => "UDP",
+        }
+    }
+}
+
 /// Source of knowledge entry
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -82,6 +327,8 @@ pub enum KnowledgeSource {
     ApiLearned,
     /// Generated from heuristics (command name, project, etc.)
     Heuristic,
+    /// Declared in a project's `.portkiller.toml`; never relearned
+    UserDefined,
 }
 
 impl Default for KnowledgeSource {
@@ -90,6 +337,65 @@ impl Default for KnowledgeSource {
     }
 }
 
+/// Confidence level, always within 0.0-1.0. Out-of-range values (including
+/// deserialized ones) are clamped; NaN becomes 0.0. Serializes as a bare float.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct Confidence(f32);
+
+impl Confidence {
+    pub const ZERO: Self = Self(0.0);
+    pub const FULL: Self = Self(1.0);
+
+    pub fn new(value: f32) -> Self {
+        if value.is_nan() {
+            Self::ZERO
+        } else {
+            Self(value.clamp(0.0, 1.0))
+        }
+    }
+
+    pub fn get(self) -> f32 {
+        self.0
+    }
+}
+
+impl From<f32> for Confidence {
+    fn from(value: f32) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Confidence> for f32 {
+    fn from(confidence: Confidence) -> Self {
+        confidence.0
+    }
+}
+
+impl PartialEq<f32> for Confidence {
+    fn eq(&self, other: &f32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialOrd<f32> for Confidence {
+    fn partial_cmp(&self, other: &f32) -> Option<std::cmp::Ordering> {
+        self.0.partial_cmp(other)
+    }
+}
+
+impl std::fmt::Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl<'de> Deserialize<'de> for Confidence {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f32::deserialize(deserializer).map(Self::new)
+    }
+}
+
 /// A learned piece of knowledge about a process
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KnowledgeEntry {
@@ -104,19 +410,62 @@ pub struct KnowledgeEntry {
     /// Optional group identifier for related services
     pub group_id: Option<String>,
     /// Confidence level (0.0-1.0)
-    pub confidence: f32,
+    pub confidence: Confidence,
     /// How this knowledge was obtained
     pub source: KnowledgeSource,
     /// Number of times this process has been seen
     pub sightings: u32,
     /// Unix timestamp of last update
     pub updated_at: i64,
+    /// Unix timestamp of the first sighting (0 = never seen)
+    #[serde(default)]
+    pub first_seen: i64,
+    /// Unix timestamp of the most recent sighting (0 = never seen)
+    #[serde(default)]
+    pub last_seen: i64,
+    /// Signal that decided a heuristic name (e.g. "container prefix 'dss'")
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Working directory of a project-specific entry when it was analyzed,
+    /// so entries for deleted projects can be pruned
+    #[serde(default)]
+    pub project_dir: Option<String>,
+    /// Confidence in `category` alone (`None` = same as `confidence`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_confidence: Option<Confidence>,
+    /// Kept out of the menu; still learned and updated like any other entry
+    #[serde(default)]
+    pub hidden: bool,
 }
 
 impl KnowledgeEntry {
     pub fn hash_key(&self) -> String {
         self.fingerprint.hash_key()
     }
+
+    /// Confidence in the category, falling back to the overall confidence
+    pub fn resolved_category_confidence(&self) -> Confidence {
+        self.category_confidence.unwrap_or(self.confidence)
+    }
+
+    /// Whether this is a learned entry seen fewer than `threshold` times, so
+    /// its name may be a fluke. Builtin and user-defined entries never are.
+    pub fn is_provisional(&self, threshold: u32) -> bool {
+        matches!(
+            self.source,
+            KnowledgeSource::ApiLearned | KnowledgeSource::Heuristic
+        ) && self.sightings < threshold
+    }
+
+    /// Last time this process was seen, falling back to `updated_at` for
+    /// entries saved before sightings were timestamped
+    pub fn last_active(&self) -> i64 {
+        if self.last_seen > 0 {
+            self.last_seen
+        } else {
+            self.updated_at
+        }
+    }
 }
 
 /// The persistent knowledge base
@@ -129,6 +478,53 @@ pub struct KnowledgeBase {
     /// Pending analysis queue (fingerprint hashes -> sighting count)
     #[serde(default)]
     pub pending_analysis: HashMap<String, PendingEntry>,
+    /// Recent ICA failures (fingerprint hashes -> failure record)
+    #[serde(default)]
+    pub failed_analysis: HashMap<String, FailureRecord>,
+    /// Where stored results are logged, if auditing is enabled
+    #[serde(skip)]
+    pub audit_log: Option<super::audit::AuditLog>,
+    /// Channels told about entry changes; see [`subscribe`](super::learning::subscribe)
+    #[serde(skip)]
+    pub observers: Observers,
+}
+
+/// Subscribers of one knowledge base. A clone of the knowledge base starts
+/// with none, so changes to a snapshot aren't reported as the original's.
+#[derive(Debug, Default)]
+pub struct Observers(Vec<crossbeam_channel::Sender<super::worker::KnowledgeEvent>>);
+
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Observers {
+    pub fn push(&mut self, tx: crossbeam_channel::Sender<super::worker::KnowledgeEvent>) {
+        self.0.push(tx);
+    }
+
+    /// Keep only the subscribers `keep` returns true for
+    pub fn retain(
+        &mut self,
+        keep: impl FnMut(&crossbeam_channel::Sender<super::worker::KnowledgeEvent>) -> bool,
+    ) {
+        self.0.retain(keep);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Tracks repeated ICA failures for a fingerprint so it can be backed off
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FailureRecord {
+    /// Consecutive failed analyses
+    pub failures: u32,
+    /// Timestamp of the most recent analysis attempt
+    pub last_attempt: i64,
 }
 
 /// Entry waiting to be analyzed
@@ -142,6 +538,18 @@ pub struct PendingEntry {
     pub context: AnalysisContext,
 }
 
+/// Progress of a pending entry toward analysis, for display (e.g. "identifying (1/2)")
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PendingStatus {
+    /// Sightings accumulated so far
+    pub sightings: u32,
+    /// Sightings needed before analysis starts (`min_sightings`, or its
+    /// override for the inferred category)
+    pub required: u32,
+    /// Sightings still missing; 0 once the entry is queued for analysis
+    pub remaining: u32,
+}
+
 /// Context passed to ICA for analysis
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct AnalysisContext {
@@ -149,10 +557,16 @@ pub struct AnalysisContext {
     pub command: String,
     /// Port the process is listening on
     pub port: Option<u16>,
+    /// Protocol of the listening socket
+    #[serde(default)]
+    pub protocol: Option<Protocol>,
     /// Project directory name
     pub project_name: Option<String>,
     /// Docker container name (if containerized)
     pub container_name: Option<String>,
+    /// Docker container ID or ID prefix, used when the name is unknown
+    #[serde(default)]
+    pub container_id: Option<String>,
     /// Docker container prefix (e.g., "dss" from "dss_app")
     pub container_prefix: Option<String>,
 
@@ -178,6 +592,59 @@ pub struct AnalysisContext {
     pub docker_workdir: Option<String>,
     /// Docker container command
     pub docker_cmd: Option<String>,
+    /// Docker health check status ("healthy", "unhealthy", "starting"); unset
+    /// for containers without a health check
+    #[serde(default)]
+    pub docker_health: Option<String>,
+    /// Digest of the container's image (e.g. "sha256:4f2a…")
+    #[serde(default)]
+    pub docker_image_digest: Option<String>,
+    /// Framework/language detected from project files (e.g., "rust")
+    pub detected_framework: Option<String>,
+    /// Version of the interpreter running the process (e.g. "3.11" for
+    /// `python3.11`)
+    #[serde(default)]
+    pub runtime_version: Option<String>,
+    /// `Server` header returned by an HTTP probe of the port
+    pub http_server_header: Option<String>,
+    /// Service identifier from an HTTP probe's JSON body
+    pub http_service_name: Option<String>,
+    /// Group declared by the project's `.portkiller.toml`
+    pub project_group: Option<String>,
+    /// Homebrew service running the process (e.g. "postgresql@14")
+    #[serde(default)]
+    pub brew_service: Option<String>,
+    /// Target of the ssh port forward behind this port (e.g. "db:5432")
+    #[serde(default)]
+    pub ssh_forward: Option<String>,
+    /// Upstreams a reverse proxy forwards to, from its config (e.g.
+    /// "api:3000" for nginx's `proxy_pass http://api:3000`)
+    #[serde(default)]
+    pub proxy_upstreams: Vec<String>,
+    /// Other processes of the same stack (e.g. "api :3000"), so related
+    /// services can be given one group
+    #[serde(default)]
+    pub siblings: Vec<String>,
+    /// User owning the process
+    #[serde(default)]
+    pub process_user: Option<String>,
+    /// Unix timestamp at which the process started
+    #[serde(default)]
+    pub process_started_at: Option<i64>,
+    /// Parent process ID
+    #[serde(default)]
+    pub parent_pid: Option<u32>,
+    /// Master process this one was forked from (e.g. a gunicorn worker's
+    /// arbiter); the whole group shares one knowledge entry
+    #[serde(default)]
+    pub master_pid: Option<u32>,
+    /// Display name declared for this command by `.portkiller.toml`
+    pub display_name_override: Option<String>,
+    /// Description declared for this command by `.portkiller.toml`
+    pub description_override: Option<String>,
+    /// `.portkiller.toml` command glob the overrides came from, if any; the
+    /// learned entry then covers every command the glob matches
+    pub command_glob_override: Option<String>,
     /// Process ID (for additional lookups)
     pub pid: Option<u32>,
 }
@@ -190,13 +657,55 @@ impl AnalysisContext {
         }
     }
 
-    pub fn to_prompt(&self) -> String {
+    /// True when at least one of the enhanced context fields is populated
+    pub fn is_enriched(&self) -> bool {
+        self.executable_path.is_some()
+            || self.working_directory.is_some()
+            || self.full_command.is_some()
+            || self.macos_app_name.is_some()
+            || self.macos_app_kind.is_some()
+            || self.docker_service.is_some()
+            || self.docker_project.is_some()
+            || self.docker_image.is_some()
+            || self.docker_workdir.is_some()
+            || self.docker_cmd.is_some()
+            || self.docker_health.is_some()
+            || self.docker_image_digest.is_some()
+            || self.detected_framework.is_some()
+            || self.http_server_header.is_some()
+            || self.http_service_name.is_some()
+            || self.ssh_forward.is_some()
+            || !self.proxy_upstreams.is_empty()
+            || self.process_user.is_some()
+            || self.process_started_at.is_some()
+            || self.parent_pid.is_some()
+    }
+
+    /// Seconds the process had been running at `now`, if its start is known
+    pub fn process_age_secs(&self, now: i64) -> Option<i64> {
+        self.process_started_at
+            .map(|started| (now - started).max(0))
+    }
+
+    /// Run context enrichment unless it has already happened
+    pub fn ensure_enriched(&mut self, flags: &GathererFlags) {
+        if !self.is_enriched() {
+            super::context_gatherer::enrich_context(self, flags);
+        }
+    }
+
+    /// Prompt lines describing the process; `now` is the Unix time its
+    /// uptime is measured to
+    pub fn to_prompt(&self, now: i64) -> String {
         let mut lines = vec![];
         lines.push(format!("Command: {}", self.command));
 
         if let Some(port) = self.port {
             lines.push(format!("Port: {}", port));
         }
+        if let Some(protocol) = self.protocol {
+            lines.push(format!("Protocol: {}", protocol.as_str()));
+        }
         if let Some(ref path) = self.executable_path {
             lines.push(format!("Executable: {}", path));
         }
@@ -215,6 +724,15 @@ impl AnalysisContext {
         if let Some(ref project) = self.project_name {
             lines.push(format!("Project: {}", project));
         }
+        if let Some(ref group) = self.project_group {
+            lines.push(format!("Project group: {}", group));
+        }
+        if let Some(ref framework) = self.detected_framework {
+            lines.push(format!("Detected framework: {}", framework));
+        }
+        if let Some(ref version) = self.runtime_version {
+            lines.push(format!("Runtime version: {}", version));
+        }
 
         // macOS app info
         if let Some(ref app_name) = self.macos_app_name {
@@ -227,6 +745,8 @@ impl AnalysisContext {
         // Docker info
         if let Some(ref container) = self.container_name {
             lines.push(format!("Docker container: {}", container));
+        } else if let Some(ref id) = self.container_id {
+            lines.push(format!("Docker container ID: {}", id));
         }
         if let Some(ref service) = self.docker_service {
             lines.push(format!("Docker compose service: {}", service));
@@ -243,14 +763,60 @@ impl AnalysisContext {
         if let Some(ref cmd) = self.docker_cmd {
             lines.push(format!("Container command: {}", cmd));
         }
+        if let Some(ref health) = self.docker_health {
+            lines.push(format!("Container health: {}", health));
+        }
+        if let Some(ref server) = self.http_server_header {
+            lines.push(format!("HTTP Server header: {}", server));
+        }
+        if let Some(ref service) = self.http_service_name {
+            lines.push(format!("HTTP service name: {}", service));
+        }
         if let Some(ref prefix) = self.container_prefix {
             lines.push(format!("Container prefix: {}", prefix));
         }
+        if let Some(ref service) = self.brew_service {
+            lines.push(format!("Homebrew service: {}", service));
+        }
+        if let Some(ref target) = self.ssh_forward {
+            lines.push(format!("SSH forward to: {}", target));
+        }
+        if !self.proxy_upstreams.is_empty() {
+            lines.push(format!("Proxies to: {}", self.proxy_upstreams.join(", ")));
+        }
+        if !self.siblings.is_empty() {
+            lines.push(format!("Related processes: {}", self.siblings.join(", ")));
+        }
+        if let Some(ref user) = self.process_user {
+            lines.push(format!("Process user: {}", user));
+        }
+        if let Some(age) = self.process_age_secs(now) {
+            lines.push(format!("Process uptime: {}", format_uptime(age)));
+        }
 
         lines.join("\n")
     }
 }
 
+/// Current Unix time in seconds
+pub(crate) fn now_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Coarse human-readable duration (e.g. "45s", "12m", "3h 5m", "2d 4h")
+fn format_uptime(secs: i64) -> String {
+    let (days, hours, mins) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours, mins) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, _) => format!("{}m", mins),
+        (0, _, _) => format!("{}h {}m", hours, mins),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
 /// Response from ICA analysis
 #[derive(Clone, Debug, serde::Serialize, Deserialize)]
 pub struct IcaAnalysisResponse {
@@ -258,9 +824,71 @@ pub struct IcaAnalysisResponse {
     pub description: String,
     pub category: ProcessCategory,
     pub group_hint: Option<String>,
+    pub confidence: Confidence,
+    /// Confidence in the category alone, when the model reports one that
+    /// differs from its confidence in the name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_confidence: Option<Confidence>,
+    /// Signal that decided the name, for locally generated responses
+    #[serde(skip)]
+    pub reason: Option<String>,
+}
+
+impl IcaAnalysisResponse {
+    /// Confidence in the category, falling back to the overall confidence
+    pub fn resolved_category_confidence(&self) -> Confidence {
+        self.category_confidence.unwrap_or(self.confidence)
+    }
+}
+
+/// How an entry's display name was derived, for showing to users
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameExplanation {
+    pub source: KnowledgeSource,
     pub confidence: f32,
+    /// Short description of the deciding signal (e.g. "matched builtin 'postgres'")
+    pub reason: String,
+    /// How the entry was matched to the process
+    pub tier: MatchTier,
 }
 
+/// How a knowledge entry was matched to a live fingerprint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchTier {
+    /// The entry's fingerprint is the live one
+    Exact,
+    /// The entry leaves some of the live fingerprint's fields unpinned
+    /// (e.g. a builtin matching whatever the executable path)
+    Partial,
+    /// The entry matches the command through its `command_glob`
+    Glob,
+}
+
+/// ICA service name used when none is configured
+pub const DEFAULT_SERVICE_NAME: &str = "portkiller";
+
+/// Environment variable checked for the ICA service key when none is configured
+pub const DEFAULT_SERVICE_KEY_ENV: &str = "PORTKILLER_ICA_KEY";
+
+/// macOS system services that are never worth analyzing. Names are matched
+/// as substrings, so lsof's 9-character truncation (e.g. "ControlCe") still hits.
+pub const DEFAULT_IGNORE_COMMANDS: &[&str] = &[
+    "ControlCe",
+    "rapportd",
+    "sharingd",
+    "airportd",
+    "WiFiAgent",
+    "bluetoothd",
+    "locationd",
+    "identityservicesd",
+    "remoted",
+    "UserEvent",
+    "mDNSResp",
+    "SystemUIS",
+    "AirPlay",
+    "com.apple.*",
+];
+
 /// Learning configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
@@ -269,6 +897,9 @@ pub struct LearningConfig {
     pub enabled: bool,
     /// Minimum sightings before analysis
     pub min_sightings: u32,
+    /// Per-category overrides of `min_sightings`, keyed by the category
+    /// inferred from the command (e.g. `{"database": 1}`)
+    pub category_min_sightings: BTreeMap<ProcessCategory, u32>,
     /// Rate limit in seconds between API calls
     pub rate_limit_secs: u64,
     /// Maximum pending entries
@@ -277,6 +908,68 @@ pub struct LearningConfig {
     pub ica_url: String,
     /// Setec server URL for retrieving service key
     pub setec_url: String,
+    /// setec executable, looked up on PATH unless it is a path
+    pub setec_path: String,
+    /// setec secret holding the service key; defaults to `ica/service-key`,
+    /// scoped by `service_name` when that is not the default
+    pub setec_secret: Option<String>,
+    /// Extra arguments passed to setec before `get`
+    pub setec_args: Vec<String>,
+    /// ICA service name; scopes rate limits, prompts, and the setec key path
+    pub service_name: String,
+    /// Environment variable holding the ICA service key; checked before setec
+    /// (empty disables)
+    pub service_key_env: String,
+    /// Probe listening TCP ports over HTTP for identifying headers (opt-in)
+    pub probe_http: bool,
+    /// Per-request timeout for HTTP probes in milliseconds
+    pub probe_timeout_ms: u64,
+    /// Which context gatherers may run
+    pub gatherers: GathererFlags,
+    /// Number of recent ICA prompt/response pairs to keep (0 disables)
+    pub prompt_history_size: usize,
+    /// PEM client certificate presented to ICA (requires `client_key_path`)
+    pub client_cert_path: Option<PathBuf>,
+    /// PEM private key for `client_cert_path`
+    pub client_key_path: Option<PathBuf>,
+    /// PEM bundle of extra CAs trusted for ICA, in addition to the public roots
+    pub ca_bundle_path: Option<PathBuf>,
+    /// How container processes are fingerprinted
+    pub fingerprint_strategy: FingerprintStrategy,
+    /// Include the image digest in container fingerprints, so upgrading an
+    /// image re-triggers analysis
+    pub fingerprint_image_digest: bool,
+    /// Gzip the knowledge base file on save (plain JSON files still load)
+    pub compress: bool,
+    /// Largest the knowledge base file may grow, in bytes; beyond it the
+    /// least valuable learned entries are pruned on save (`None` = no cap)
+    pub max_knowledge_bytes: Option<u64>,
+    /// ICA results less confident than this are replaced by the heuristic fallback
+    pub min_apply_confidence: f32,
+    /// Commands never queued for analysis: globs (`*`, `?`) or substrings,
+    /// case-insensitive. Entries containing `/` match the executable path
+    /// instead; `~` and `$VAR` are expanded (e.g. `~/projects/*/node`)
+    pub ignore_commands: Vec<String>,
+    /// Replace the home directory with `~` and drop denylisted words from
+    /// prompts sent to remote backends
+    pub redact_prompts: bool,
+    /// Denylist for `redact_prompts`: globs matched case-insensitively
+    /// against each word of the prompt
+    pub redact_patterns: Vec<String>,
+    /// Append a JSON line for every stored learning result to this file
+    pub audit_log_path: Option<PathBuf>,
+    /// Longest analysis prompt sent to ICA, in characters (0 for no limit);
+    /// low-priority context fields are dropped to fit
+    pub max_prompt_chars: usize,
+    /// Seconds between the worker's housekeeping ticks, which also run when
+    /// idle (at least 1)
+    pub maintenance_interval_secs: u64,
+    /// Learned entries seen fewer times than this are provisional: shown as
+    /// tentative and outranked by better-established matches (0 disables)
+    pub provisional_threshold: u32,
+    /// List the other processes of the same compose project or project
+    /// directory in analysis prompts, for consistent group hints
+    pub include_siblings: bool,
 }
 
 impl Default for LearningConfig {
@@ -284,10 +977,391 @@ impl Default for LearningConfig {
         Self {
             enabled: true,
             min_sightings: 2,
+            category_min_sightings: BTreeMap::new(),
             rate_limit_secs: 5,
             max_pending: 20,
             ica_url: "https://ica.tailb726.ts.net".to_string(),
             setec_url: "https://setec.tailb726.ts.net".to_string(),
+            setec_path: "setec".to_string(),
+            setec_secret: None,
+            setec_args: Vec::new(),
+            service_name: DEFAULT_SERVICE_NAME.to_string(),
+            service_key_env: DEFAULT_SERVICE_KEY_ENV.to_string(),
+            probe_http: false,
+            probe_timeout_ms: 500,
+            gatherers: GathererFlags::default(),
+            prompt_history_size: 0,
+            client_cert_path: None,
+            client_key_path: None,
+            ca_bundle_path: None,
+            fingerprint_strategy: FingerprintStrategy::default(),
+            fingerprint_image_digest: false,
+            compress: false,
+            max_knowledge_bytes: None,
+            min_apply_confidence: 0.0,
+            ignore_commands: DEFAULT_IGNORE_COMMANDS
+                .iter()
+                .map(|command| command.to_string())
+                .collect(),
+            redact_prompts: true,
+            redact_patterns: super::redaction::DEFAULT_REDACT_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            audit_log_path: None,
+            max_prompt_chars: 4000,
+            maintenance_interval_secs: 60,
+            provisional_threshold: 0,
+            include_siblings: false,
+        }
+    }
+}
+
+impl LearningConfig {
+    /// Sightings needed before a process of `category` is analyzed
+    pub fn min_sightings_for(&self, category: &ProcessCategory) -> u32 {
+        self.category_min_sightings
+            .get(category)
+            .copied()
+            .unwrap_or(self.min_sightings)
+    }
+
+    /// Time between maintenance ticks; at least a second, as 0 would make
+    /// the worker spin
+    pub fn maintenance_interval(&self) -> Duration {
+        Duration::from_secs(self.maintenance_interval_secs.max(1))
+    }
+}
+
+/// Switches for the individual context gatherers; all enabled by default
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GathererFlags {
+    /// Read the full command line with `ps`
+    pub pid_ps: bool,
+    /// Read the working directory with `lsof`
+    pub lsof_cwd: bool,
+    /// Read macOS app bundle metadata with `mdls`
+    pub macos_mdls: bool,
+    /// Read container labels and config with `docker inspect`
+    pub docker_inspect: bool,
+    /// Match Homebrew executables to services with `brew services list`
+    pub brew_services: bool,
+    /// Read nginx/Caddy config files for the upstreams they proxy to
+    pub proxy_config: bool,
+    /// Parse interpreter versions from the command line, which names
+    /// (e.g. "Python 3.11 Server") and prompts then show
+    pub runtime_version: bool,
+}
+
+impl Default for GathererFlags {
+    fn default() -> Self {
+        Self {
+            pid_ps: true,
+            lsof_cwd: true,
+            macos_mdls: true,
+            docker_inspect: true,
+            brew_services: true,
+            proxy_config: true,
+            runtime_version: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("portkiller-types-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_confidence_clamps_on_construction() {
+        assert_eq!(Confidence::new(0.4), 0.4);
+        assert_eq!(Confidence::new(85.0), Confidence::FULL);
+        assert_eq!(Confidence::new(-0.2), Confidence::ZERO);
+        assert_eq!(Confidence::new(f32::NAN), Confidence::ZERO);
+        assert_eq!(Confidence::from(1.5).get(), 1.0);
+    }
+
+    #[test]
+    fn test_confidence_serde_round_trip() {
+        let json = serde_json::to_string(&Confidence::new(0.75)).unwrap();
+        assert_eq!(json, "0.75");
+        assert_eq!(serde_json::from_str::<Confidence>(&json).unwrap(), 0.75);
+
+        // Stored out-of-range values are clamped when loaded
+        assert_eq!(
+            serde_json::from_str::<Confidence>("85").unwrap(),
+            Confidence::FULL
+        );
+    }
+
+    #[test]
+    fn test_exe_hash_separates_executables() {
+        let venv = |path: &str| {
+            let context = AnalysisContext {
+                executable_path: Some(path.to_string()),
+                ..AnalysisContext::new("python")
+            };
+            ProcessFingerprint::from_context(&context, FingerprintStrategy::default())
+        };
+        let venv_a = venv("/proj-a/.venv/bin/python");
+        let venv_b = venv("/proj-b/.venv/bin/python");
+        assert_ne!(venv_a.hash_key(), venv_b.hash_key());
+        // Still canonically the same command as a bare `python`
+        assert!(ProcessFingerprint::new("python").matches(&venv_a));
+        assert_ne!(
+            venv_a.hash_key(),
+            ProcessFingerprint::new("python").hash_key()
+        );
+        assert_eq!(
+            venv_a.hash_key(),
+            ProcessFingerprint::new("python")
+                .with_executable_path("/proj-a/.venv/bin/python")
+                .hash_key()
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_deserializes_without_newer_fields() {
+        let fingerprint = ProcessFingerprint::new("python").with_container_prefix("dss");
+        // Entries saved before the fields existed still deserialize
+        let legacy: ProcessFingerprint = serde_json::from_str(
+            r#"{"command":"python","default_port":null,"project_hash":null,"container_prefix":"dss"}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy, fingerprint);
+    }
+
+    #[test]
+    fn test_command_glob_matches_many_commands() {
+        let worker = ProcessFingerprint::new("celery")
+            .with_command_glob("celery")
+            .with_project_hash("shop");
+        let live = |command: &str| ProcessFingerprint::new(command).with_project_hash("shop");
+
+        assert!(worker.matches(&live("celery")));
+        assert!(worker.matches(&live("Celery-beat")));
+        assert!(worker.matches(&live("py-celery-worker")));
+        assert!(!worker.matches(&live("flower")));
+        assert!(!worker.matches(&ProcessFingerprint::new("celery").with_project_hash("blog")));
+
+        let glob = ProcessFingerprint::new("gunicorn").with_command_glob("gunicorn*");
+        assert!(glob.matches(&ProcessFingerprint::new("gunicorn3")));
+        assert!(!glob.matches(&ProcessFingerprint::new("my-gunicorn")));
+        assert_ne!(
+            glob.hash_key(),
+            ProcessFingerprint::new("gunicorn").hash_key()
+        );
+    }
+
+    #[test]
+    fn test_project_hash_ignores_parent_directories() {
+        let a = Path::new("/Users/me/dev/dss");
+        let b = Path::new("/Users/teammate/code/dss");
+        assert_eq!(project_hash(a), project_hash(b));
+        assert_ne!(
+            project_hash(a),
+            project_hash(Path::new("/Users/me/dev/other"))
+        );
+    }
+
+    #[test]
+    fn test_project_hash_uses_repo_root_name() {
+        let base = temp_dir("repo");
+        let mine = base.join("me/dev/dss");
+        let theirs = base.join("teammate/code/dss");
+        for repo in [&mine, &theirs] {
+            fs::create_dir_all(repo.join(".git")).unwrap();
+            fs::create_dir_all(repo.join("services/api")).unwrap();
+        }
+
+        let hash = project_hash(&mine.join("services/api"));
+        assert_eq!(hash, project_hash(&theirs.join("services/api")));
+        assert_eq!(hash, project_hash(&mine));
+        let node_in = |dir: &Path| {
+            let context = AnalysisContext {
+                working_directory: Some(dir.to_string_lossy().into_owned()),
+                ..AnalysisContext::new("node")
+            };
+            ProcessFingerprint::from_context(&context, FingerprintStrategy::default())
+        };
+        let fingerprint = node_in(&mine.join("services/api"));
+        assert_eq!(fingerprint.project_hash, hash);
+        assert_eq!(fingerprint.hash_key(), node_in(&theirs).hash_key());
+        assert_ne!(
+            fingerprint.hash_key(),
+            ProcessFingerprint::new("node").hash_key()
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_project_hash_is_stable() {
+        // Persisted in knowledge files, so the value must never change
+        assert_eq!(
+            project_hash(Path::new("/Users/me/dev/dss")).as_deref(),
+            Some(format!("{:016x}", fnv1a_64(b"dss")).as_str())
+        );
+        assert_eq!(fnv1a_64(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    fn container_context(instance: &str, prefix: &str) -> AnalysisContext {
+        AnalysisContext {
+            command: "com.docker.backend".to_string(),
+            container_name: Some(instance.to_string()),
+            container_prefix: Some(prefix.to_string()),
+            docker_project: Some("myapp".to_string()),
+            docker_service: Some("web".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compose_service_shares_fingerprint_across_instances() {
+        let first = container_context("ci-7f3a_web_1", "ci-7f3a");
+        let second = container_context("ci-9b2c_web_1", "ci-9b2c");
+
+        let strategy = FingerprintStrategy::ComposeService;
+        assert_eq!(
+            ProcessFingerprint::from_context(&first, strategy).hash_key(),
+            ProcessFingerprint::from_context(&second, strategy).hash_key()
+        );
+
+        // Existing knowledge is keyed by prefix, so that stays the default
+        let strategy = FingerprintStrategy::default();
+        assert_eq!(strategy, FingerprintStrategy::ContainerPrefix);
+        assert_ne!(
+            ProcessFingerprint::from_context(&first, strategy).hash_key(),
+            ProcessFingerprint::from_context(&second, strategy).hash_key()
+        );
+    }
+
+    #[test]
+    fn test_from_context_without_compose_labels() {
+        let context = AnalysisContext {
+            command: "postgresql".to_string(),
+            container_prefix: Some("dss".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ProcessFingerprint::from_context(&context, FingerprintStrategy::ComposeService),
+            ProcessFingerprint::new("postgres").with_container_prefix("dss")
+        );
+    }
+
+    #[test]
+    fn test_image_digest_pinned_when_enabled() {
+        let mut first = container_context("shop_web_1", "shop");
+        first.docker_image_digest = Some("sha256:4f2a9c0e1b7d3a5f".to_string());
+        let mut second = first.clone();
+        second.docker_image_digest = Some("sha256:91bd22e07c4f8a6e".to_string());
+
+        let mut config = LearningConfig::default();
+        assert_eq!(
+            ProcessFingerprint::from_config(&first, &config),
+            ProcessFingerprint::from_config(&second, &config)
+        );
+
+        config.fingerprint_image_digest = true;
+        let pinned = ProcessFingerprint::from_config(&first, &config);
+        assert_eq!(pinned.image_digest.as_deref(), Some("4f2a9c0e1b7d"));
+        assert_ne!(
+            pinned.hash_key(),
+            ProcessFingerprint::from_config(&second, &config).hash_key()
+        );
+        // Non-container processes have no digest to pin
+        let plain = AnalysisContext::new("node");
+        assert_eq!(
+            ProcessFingerprint::from_config(&plain, &config),
+            ProcessFingerprint::new("node")
+        );
+    }
+
+    #[test]
+    fn test_hash_key_golden_values() {
+        // Keys index every persisted knowledge base; if this fails, bump the
+        // storage version so `rebuild_index` rekeys existing files
+        let cases = [
+            (ProcessFingerprint::new("postgres"), "ce062a41b03a54f6"),
+            (
+                ProcessFingerprint::new("node")
+                    .with_port(3000)
+                    .with_project_hash("0123456789abcdef")
+                    .with_container_prefix("dss"),
+                "4adf3dbb1bb0b4ea",
+            ),
+            (
+                ProcessFingerprint::new("python")
+                    .with_exe_hash("fedcba9876543210")
+                    .with_command_glob("*celery*")
+                    .with_image_digest("sha256:4f2a9c0e1b7d3a5f"),
+                "545aff0699c8f679",
+            ),
+        ];
+        for (fingerprint, expected) in cases {
+            assert_eq!(fingerprint.hash_key(), expected, "{:?}", fingerprint);
+        }
+
+        // The same value in another field is another key
+        assert_ne!(
+            ProcessFingerprint::new("dss").hash_key(),
+            ProcessFingerprint::new("")
+                .with_container_prefix("dss")
+                .hash_key()
+        );
+    }
+
+    #[test]
+    fn test_maintenance_interval_is_clamped() {
+        let config = |secs| LearningConfig {
+            maintenance_interval_secs: secs,
+            ..Default::default()
+        };
+        assert_eq!(config(0).maintenance_interval(), Duration::from_secs(1));
+        assert_eq!(config(90).maintenance_interval(), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(42), "42s");
+        assert_eq!(format_uptime(12 * 60 + 5), "12m");
+        assert_eq!(format_uptime(3 * 3600 + 5 * 60), "3h 5m");
+        assert_eq!(format_uptime(2 * 86_400 + 4 * 3600 + 59), "2d 4h");
+    }
+
+    #[test]
+    fn test_all_categories_listed_with_labels() {
+        // Exhaustive so a new variant fails to compile until it is listed
+        let listed = |category: &ProcessCategory| match category {
+            ProcessCategory::Frontend
+            | ProcessCategory::Backend
+            | ProcessCategory::Database
+            | ProcessCategory::Cache
+            | ProcessCategory::Search
+            | ProcessCategory::Proxy
+            | ProcessCategory::DevTool
+            | ProcessCategory::Infrastructure
+            | ProcessCategory::Unknown => ProcessCategory::all().contains(category),
+        };
+        assert_eq!(ProcessCategory::all().len(), 9);
+        assert!(
+            ProcessCategory::all()
+                .windows(2)
+                .all(|pair| pair[0] < pair[1])
+        );
+        for category in ProcessCategory::all() {
+            assert!(listed(category));
+            assert!(!category.label().is_empty());
         }
+        assert_eq!(ProcessCategory::DevTool.to_string(), "Dev Tool");
     }
 }
@@ -1,12 +1,54 @@
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
 
 use super::fallback::generate_fallback;
+use super::http_probe::enrich_from_http;
+#[cfg(feature = "ica")]
 use super::ica::IcaClient;
-use super::types::{AnalysisContext, IcaAnalysisResponse, KnowledgeSource, LearningConfig, ProcessFingerprint};
+use super::types::{
+    AnalysisContext, IcaAnalysisResponse, KnowledgeSource, LearningConfig, ProcessFingerprint,
+    Protocol,
+};
+
+/// Identifies processes from their context; ICA in production
+pub trait AnalysisBackend {
+    /// Whether the backend can currently take requests
+    fn is_available(&self) -> bool;
+
+    /// Analyze a process
+    fn analyze(&self, context: &AnalysisContext) -> anyhow::Result<IcaAnalysisResponse>;
+}
+
+#[cfg(feature = "ica")]
+impl AnalysisBackend for IcaClient {
+    fn is_available(&self) -> bool {
+        IcaClient::is_available(self)
+    }
+
+    fn analyze(&self, context: &AnalysisContext) -> anyhow::Result<IcaAnalysisResponse> {
+        IcaClient::analyze(self, context)
+    }
+}
+
+/// Stand-in backend when ICA is compiled out
+#[cfg(not(feature = "ica"))]
+struct HeuristicsOnly;
+
+#[cfg(not(feature = "ica"))]
+impl AnalysisBackend for HeuristicsOnly {
+    fn is_available(&self) -> bool {
+        false
+    }
+
+    fn analyze(&self, _context: &AnalysisContext) -> anyhow::Result<IcaAnalysisResponse> {
+        Err(anyhow::anyhow!("ICA support is not compiled in"))
+    }
+}
 
 /// Message sent to the learning worker
 #[derive(Debug)]
@@ -21,85 +63,928 @@ pub struct AnalysisResult {
     pub fingerprint: ProcessFingerprint,
     pub response: IcaAnalysisResponse,
     pub source: KnowledgeSource,
+    /// ICA was tried and failed; `response` is the heuristic fallback
+    pub failed: bool,
+    /// Time spent producing this result
+    pub timing: AnalysisTiming,
+}
+
+/// Where the worker spent its time on one request
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnalysisTiming {
+    /// Context enrichment, including any HTTP probe
+    pub enrich: Duration,
+    /// Backend call plus heuristic fallback
+    pub analyze: Duration,
 }
 
 /// User event for knowledge updates
 #[derive(Debug, Clone)]
 pub enum KnowledgeEvent {
-    AnalysisComplete(AnalysisResult),
+    AnalysisComplete(Box<AnalysisResult>),
     SaveKnowledgeBase,
+    /// Periodic housekeeping tick from the worker, sent even when idle
+    Maintenance,
+    /// An entry of a subscribed knowledge base changed
+    EntryChanged(KnowledgeChange),
+}
+
+/// What happened to a knowledge base entry, by fingerprint hash key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KnowledgeChange {
+    Inserted(String),
+    Updated(String),
+    Removed(String),
+}
+
+/// A prompt sent to ICA and the raw reply (or error) it produced
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PromptRecord {
+    pub command: String,
+    pub prompt: String,
+    pub response: String,
+}
+
+/// Ring buffer of recent prompt/response pairs for troubleshooting; a
+/// capacity of 0 disables recording. The worker records into one shared as
+/// `Arc<Mutex<PromptHistory>>` with the UI (see [`spawn_learning_worker`]).
+#[derive(Clone, Debug, Default)]
+pub struct PromptHistory {
+    capacity: usize,
+    records: VecDeque<PromptRecord>,
+}
+
+impl PromptHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Record a pair, evicting the oldest once the buffer is full
+    pub fn push(&mut self, record: PromptRecord) {
+        if !self.is_enabled() {
+            return;
+        }
+        while self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Recorded pairs, oldest first
+    pub fn records(&self) -> Vec<PromptRecord> {
+        self.records.iter().cloned().collect()
+    }
+}
+
+/// Lock-free view of when the worker will next call ICA, shared with the UI.
+/// Only set while a request is actually held back by the rate limit.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitState {
+    /// Unix timestamp in milliseconds of the held-back call (0 = none waiting)
+    next_call_at_ms: Arc<AtomicU64>,
+}
+
+impl RateLimitState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Unix timestamp in milliseconds at which the waiting analysis starts
+    pub fn next_call_at_ms(&self) -> u64 {
+        self.next_call_at_ms.load(Ordering::Relaxed)
+    }
+
+    /// Time left until the waiting analysis starts (zero if none is waiting)
+    pub fn remaining(&self) -> Duration {
+        Duration::from_millis(self.next_call_at_ms().saturating_sub(now_millis()))
+    }
+
+    fn set_next_call_at_ms(&self, timestamp_ms: u64) {
+        self.next_call_at_ms.store(timestamp_ms, Ordering::Relaxed);
+    }
+}
+
+/// Maximum fraction of the interval added or removed between calls, so
+/// several instances don't hit ICA on the same boundary
+const RATE_LIMIT_JITTER: f64 = 0.1;
+
+/// Source of jitter samples in `[-1.0, 1.0]`
+trait JitterSource: Send {
+    fn sample(&mut self) -> f64;
+}
+
+/// Uniformly random jitter
+struct RandomJitter;
+
+impl JitterSource for RandomJitter {
+    fn sample(&mut self) -> f64 {
+        fastrand::f64() * 2.0 - 1.0
+    }
+}
+
+/// Spaces out analysis calls and publishes when a held-back call will run
+struct RateLimiter {
+    interval: Duration,
+    /// Jittered gap required before the next call
+    next_interval: Duration,
+    last_call: Option<Instant>,
+    state: RateLimitState,
+    jitter: Box<dyn JitterSource>,
+    /// Disconnects (or receives) when the worker is asked to stop
+    stop: Receiver<()>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration, state: RateLimitState) -> Self {
+        Self::with_jitter(interval, state, Box::new(RandomJitter))
+    }
+
+    fn with_jitter(
+        interval: Duration,
+        state: RateLimitState,
+        jitter: Box<dyn JitterSource>,
+    ) -> Self {
+        Self {
+            interval,
+            next_interval: interval,
+            last_call: None, // Allow immediate first call
+            state,
+            jitter,
+            stop: crossbeam_channel::never(),
+        }
+    }
+
+    /// Cut waits short once `stop` disconnects or receives
+    fn with_stop(mut self, stop: Receiver<()>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Block until the next call is allowed, then record the call. The wait
+    /// is published for its duration only, so an idle queue shows none.
+    /// Returns false, without recording a call, if stopped while waiting.
+    fn acquire(&mut self) -> bool {
+        if let Some(last_call) = self.last_call {
+            let elapsed = last_call.elapsed();
+            if elapsed < self.next_interval {
+                let wait = self.next_interval - elapsed;
+                self.state
+                    .set_next_call_at_ms(now_millis() + wait.as_millis() as u64);
+                let stopped =
+                    !matches!(self.stop.recv_timeout(wait), Err(RecvTimeoutError::Timeout));
+                self.state.set_next_call_at_ms(0);
+                if stopped {
+                    return false;
+                }
+            }
+        }
+        self.last_call = Some(Instant::now());
+        self.next_interval = self.jittered_interval();
+        true
+    }
+
+    fn jittered_interval(&mut self) -> Duration {
+        let offset = RATE_LIMIT_JITTER * self.jitter.sample().clamp(-1.0, 1.0);
+        self.interval.mul_f64(1.0 + offset)
+    }
 }
 
-/// Spawn the background learning worker
+/// Source of the current time, injectable for tests
+trait Clock: Send {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Handle to the background learning worker
+pub struct LearningWorker {
+    handle: JoinHandle<()>,
+    /// Dropped to ask the worker to stop
+    stop: Sender<()>,
+}
+
+impl LearningWorker {
+    /// Ask the worker to stop and wait up to `timeout` for it. A rate-limit
+    /// wait ends at once, but an analysis in flight can't be interrupted, so
+    /// a worker still busy at the deadline is left to finish on its own.
+    /// Returns whether the worker stopped in time.
+    pub fn shutdown(self, timeout: Duration) -> bool {
+        drop(self.stop);
+        let deadline = Instant::now() + timeout;
+        while !self.handle.is_finished() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        if self.handle.join().is_err() {
+            log::warn!("Learning worker panicked");
+        }
+        true
+    }
+}
+
+/// Spawn the background learning worker. Results arrive as
+/// [`KnowledgeEvent::AnalysisComplete`], interleaved with a
+/// [`KnowledgeEvent::Maintenance`] tick every `maintenance_interval_secs`.
+/// Prompts sent to ICA are recorded in `prompt_history`.
 pub fn spawn_learning_worker(
     config: Arc<LearningConfig>,
     rx: Receiver<AnalysisRequest>,
-    result_tx: Sender<AnalysisResult>,
-) -> JoinHandle<()> {
-    thread::spawn(move || {
-        let client = IcaClient::new(&config);
-        let rate_limit = Duration::from_secs(config.rate_limit_secs);
-        let mut last_call = Instant::now() - rate_limit; // Allow immediate first call
+    events: Sender<KnowledgeEvent>,
+    rate_limit_state: RateLimitState,
+    prompt_history: Arc<Mutex<PromptHistory>>,
+) -> LearningWorker {
+    let (stop, stop_rx) = crossbeam_channel::bounded::<()>(0);
+    let handle = thread::spawn(move || {
+        #[cfg(feature = "ica")]
+        let backend = IcaClient::new(&config).with_prompt_history(prompt_history);
+        #[cfg(not(feature = "ica"))]
+        let backend = {
+            // Nothing is sent to ICA, so there is nothing to record
+            drop(prompt_history);
+            HeuristicsOnly
+        };
+        let mut rate_limiter = RateLimiter::new(
+            Duration::from_secs(config.rate_limit_secs),
+            rate_limit_state,
+        )
+        .with_stop(stop_rx);
 
+        #[cfg(feature = "ica")]
         log::info!(
             "Learning worker started (ICA available: {})",
-            client.is_available()
+            backend.is_available()
         );
+        #[cfg(not(feature = "ica"))]
+        log::info!("Learning worker started (ICA compiled out, using heuristics only)");
 
-        for request in rx {
-            // Rate limiting
-            let elapsed = last_call.elapsed();
-            if elapsed < rate_limit {
-                thread::sleep(rate_limit - elapsed);
-            }
-            last_call = Instant::now();
+        run_worker_loop(
+            &backend,
+            &config,
+            &rx,
+            &events,
+            &mut rate_limiter,
+            config.maintenance_interval(),
+            &SystemClock,
+        );
 
-            log::debug!(
-                "Analyzing process: {} (port: {:?})",
-                request.context.command,
-                request.context.port
-            );
+        log::info!("Learning worker shutting down");
+    });
+    LearningWorker { handle, stop }
+}
+
+/// Handle requests until the channel closes or the rate limiter's stop
+/// signal fires, waking every `maintenance_interval` to re-check the backend
+/// and emit a maintenance tick
+fn run_worker_loop(
+    backend: &impl AnalysisBackend,
+    config: &LearningConfig,
+    rx: &Receiver<AnalysisRequest>,
+    events: &Sender<KnowledgeEvent>,
+    rate_limiter: &mut RateLimiter,
+    maintenance_interval: Duration,
+    clock: &dyn Clock,
+) {
+    let stop = rate_limiter.stop.clone();
+    let mut next_maintenance = clock.now() + maintenance_interval;
+    let mut available = backend.is_available();
 
-            // Try ICA first, fall back to heuristics
-            let (response, source) = if client.is_available() {
-                match client.analyze(&request.context) {
-                    Ok(resp) => {
-                        log::info!(
-                            "ICA analysis successful: {} -> {}",
-                            request.context.command,
-                            resp.display_name
-                        );
-                        (resp, KnowledgeSource::ApiLearned)
-                    }
-                    Err(e) => {
-                        log::warn!(
-                            "ICA analysis failed for {}: {}, using fallback",
-                            request.context.command,
-                            e
-                        );
-                        (generate_fallback(&request.context), KnowledgeSource::Heuristic)
-                    }
+    loop {
+        let timeout = next_maintenance.saturating_duration_since(clock.now());
+        let received = crossbeam_channel::select! {
+            recv(rx) -> request => request.map_err(|_| RecvTimeoutError::Disconnected),
+            recv(stop) -> _ => return,
+            default(timeout) => Err(RecvTimeoutError::Timeout),
+        };
+        match received {
+            Ok(request) => {
+                // Rate limiting; stopping while waiting drops the request
+                if !rate_limiter.acquire() {
+                    return;
                 }
-            } else {
+
+                let result = process_request(backend, config, request);
                 log::debug!(
-                    "ICA not available, using heuristics for {}",
-                    request.context.command
+                    "Analysis of {} took {:?} enriching, {:?} analyzing",
+                    result.fingerprint.command,
+                    result.timing.enrich,
+                    result.timing.analyze
                 );
-                (generate_fallback(&request.context), KnowledgeSource::Heuristic)
-            };
 
-            // Send result back
-            let result = AnalysisResult {
-                fingerprint: request.fingerprint,
-                response,
-                source,
+                // Send result back
+                if let Err(e) = events.send(KnowledgeEvent::AnalysisComplete(Box::new(result))) {
+                    log::error!("Failed to send analysis result: {}", e);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let now = clock.now();
+        if now < next_maintenance {
+            continue;
+        }
+        next_maintenance = now + maintenance_interval;
+
+        // The backend can come and go while idle (e.g. a service key appearing)
+        let now_available = backend.is_available();
+        if now_available != available {
+            log::info!("Analysis backend available: {}", now_available);
+            available = now_available;
+        }
+        if events.send(KnowledgeEvent::Maintenance).is_err() {
+            return;
+        }
+    }
+}
+
+/// Enrich and analyze one request, timing each phase
+fn process_request(
+    backend: &impl AnalysisBackend,
+    config: &LearningConfig,
+    mut request: AnalysisRequest,
+) -> AnalysisResult {
+    #[cfg(feature = "tracing")]
+    let span = tracing::info_span!(
+        "analysis",
+        command = %request.context.command,
+        port = ?request.context.port,
+        source = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    )
+    .entered();
+
+    let enrich_start = Instant::now();
+
+    // Callers normally enrich before queueing; only fill in what's missing
+    request.context.ensure_enriched(&config.gatherers);
+
+    // Probing happens here, off the UI thread, and only when enabled
+    if config.probe_http
+        && request.context.protocol != Some(Protocol::Udp)
+        && request.context.http_server_header.is_none()
+        && request.context.http_service_name.is_none()
+    {
+        enrich_from_http(
+            &mut request.context,
+            Duration::from_millis(config.probe_timeout_ms),
+        );
+    }
+    let enrich = enrich_start.elapsed();
+
+    log::debug!(
+        "Analyzing process: {} (port: {:?})",
+        request.context.command,
+        request.context.port
+    );
+
+    let analyze_start = Instant::now();
+    let (response, source, failed) =
+        analyze_with(backend, &request.context, config.min_apply_confidence);
+
+    #[cfg(feature = "tracing")]
+    {
+        span.record("source", tracing::field::debug(&source));
+        span.record("outcome", if failed { "failed" } else { "ok" });
+    }
+
+    AnalysisResult {
+        fingerprint: request.fingerprint,
+        response,
+        source,
+        failed,
+        timing: AnalysisTiming {
+            enrich,
+            analyze: analyze_start.elapsed(),
+        },
+    }
+}
+
+/// Analyze a context immediately on the calling thread, bypassing the queue
+/// and rate limiter. ICA is only consulted when learning is enabled.
+pub fn analyze_now(
+    config: &LearningConfig,
+    context: &AnalysisContext,
+) -> (IcaAnalysisResponse, KnowledgeSource) {
+    if !config.enabled {
+        return (generate_fallback(context), KnowledgeSource::Heuristic);
+    }
+    #[cfg(feature = "ica")]
+    let backend = IcaClient::new(config);
+    #[cfg(not(feature = "ica"))]
+    let backend = HeuristicsOnly;
+    let (response, source, _) = analyze_with(&backend, context, config.min_apply_confidence);
+    (response, source)
+}
+
+/// [`analyze_now`] for async callers. The blocking work (ICA request, setec
+/// lookup) runs on tokio's blocking pool so the calling task never blocks.
+#[cfg(feature = "tokio")]
+pub async fn analyze_async(
+    config: &LearningConfig,
+    context: AnalysisContext,
+) -> anyhow::Result<IcaAnalysisResponse> {
+    let config = config.clone();
+    spawn_analysis(move || analyze_now(&config, &context).0).await
+}
+
+/// Run blocking analysis work on tokio's blocking pool and await it
+#[cfg(feature = "tokio")]
+async fn spawn_analysis(
+    work: impl FnOnce() -> IcaAnalysisResponse + Send + 'static,
+) -> anyhow::Result<IcaAnalysisResponse> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| anyhow::anyhow!("analysis task failed: {}", e))
+}
+
+/// Try the backend first, fall back to heuristics.
+/// Results below `min_confidence` are replaced by the heuristic fallback, and
+/// processes without a command name never reach the backend.
+/// Returns the response, its source, and whether a backend call failed.
+fn analyze_with(
+    backend: &impl AnalysisBackend,
+    context: &AnalysisContext,
+    min_confidence: f32,
+) -> (IcaAnalysisResponse, KnowledgeSource, bool) {
+    if backend.is_available() && !context.command.trim().is_empty() {
+        match backend.analyze(context) {
+            Ok(resp) if resp.confidence < min_confidence => {
+                log::info!(
+                    "ICA result for {} below confidence threshold ({:.2} < {:.2}), using fallback",
+                    context.command,
+                    resp.confidence,
+                    min_confidence
+                );
+                (
+                    generate_fallback(context),
+                    KnowledgeSource::Heuristic,
+                    false,
+                )
+            }
+            Ok(resp) => {
+                log::info!(
+                    "ICA analysis successful: {} -> {}",
+                    context.command,
+                    resp.display_name
+                );
+                (resp, KnowledgeSource::ApiLearned, false)
+            }
+            Err(e) => {
+                log::warn!(
+                    "ICA analysis failed for {}: {}, using fallback",
+                    context.command,
+                    e
+                );
+                (generate_fallback(context), KnowledgeSource::Heuristic, true)
+            }
+        }
+    } else {
+        log::debug!(
+            "ICA not available, using heuristics for {}",
+            context.command
+        );
+        (
+            generate_fallback(context),
+            KnowledgeSource::Heuristic,
+            false,
+        )
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::knowledge::types::{Confidence, ProcessCategory};
+
+    fn record(n: usize) -> PromptRecord {
+        PromptRecord {
+            command: format!("cmd{}", n),
+            prompt: format!("prompt {}", n),
+            response: format!("response {}", n),
+        }
+    }
+
+    #[test]
+    fn test_prompt_history_keeps_last_n() {
+        let mut history = PromptHistory::new(2);
+        for n in 0..5 {
+            history.push(record(n));
+        }
+        assert_eq!(history.records(), vec![record(3), record(4)]);
+    }
+
+    /// Backend returning a canned response
+    struct FakeBackend(IcaAnalysisResponse);
+
+    impl AnalysisBackend for FakeBackend {
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn analyze(&self, _context: &AnalysisContext) -> anyhow::Result<IcaAnalysisResponse> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn response_with_confidence(confidence: f32) -> IcaAnalysisResponse {
+        IcaAnalysisResponse {
+            display_name: "Maybe A Thing".to_string(),
+            description: "Low-effort guess".to_string(),
+            category: ProcessCategory::Backend,
+            group_hint: None,
+            confidence: Confidence::new(confidence),
+            category_confidence: None,
+            reason: None,
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_analyze_async_matches_sync_path() {
+        let json = |response: &IcaAnalysisResponse| serde_json::to_value(response).unwrap();
+        let context = AnalysisContext::new("node");
+        let backend = FakeBackend(response_with_confidence(0.9));
+        let (expected, source, _) = analyze_with(&backend, &context, 0.5);
+        assert_eq!(source, KnowledgeSource::ApiLearned);
+
+        let moved = context.clone();
+        let response = spawn_analysis(move || analyze_with(&backend, &moved, 0.5).0)
+            .await
+            .unwrap();
+        assert_eq!(json(&response), json(&expected));
+
+        // The public entry point agrees with `analyze_now`
+        let config = LearningConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let response = analyze_async(&config, context.clone()).await.unwrap();
+        assert_eq!(json(&response), json(&analyze_now(&config, &context).0));
+    }
+
+    /// Backend that takes a fixed time to answer
+    struct SlowBackend(Duration);
+
+    impl AnalysisBackend for SlowBackend {
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn analyze(&self, _context: &AnalysisContext) -> anyhow::Result<IcaAnalysisResponse> {
+            thread::sleep(self.0);
+            Ok(response_with_confidence(0.9))
+        }
+    }
+
+    /// Advances by a fixed step every time it is read
+    struct SteppingClock {
+        now: std::sync::Mutex<Instant>,
+        step: Duration,
+    }
+
+    impl Clock for SteppingClock {
+        fn now(&self) -> Instant {
+            let mut now = self.now.lock().unwrap();
+            *now += self.step;
+            *now
+        }
+    }
+
+    #[test]
+    fn test_maintenance_runs_when_idle() {
+        let (request_tx, request_rx) = crossbeam_channel::unbounded::<AnalysisRequest>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let worker = thread::spawn(move || {
+            let clock = SteppingClock {
+                now: std::sync::Mutex::new(Instant::now()),
+                step: Duration::from_millis(5),
             };
+            let mut rate_limiter = RateLimiter::new(Duration::ZERO, RateLimitState::new());
+            run_worker_loop(
+                &FakeBackend(response_with_confidence(0.9)),
+                &LearningConfig::default(),
+                &request_rx,
+                &event_tx,
+                &mut rate_limiter,
+                Duration::from_millis(20),
+                &clock,
+            );
+        });
+
+        for _ in 0..2 {
+            let event = event_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert!(matches!(event, KnowledgeEvent::Maintenance));
+        }
+
+        // Closing the request channel stops the worker
+        drop(request_tx);
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_interrupts_rate_limit_wait() {
+        let (request_tx, request_rx) = crossbeam_channel::unbounded::<AnalysisRequest>();
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let (stop, stop_rx) = crossbeam_channel::bounded::<()>(0);
+        let handle = thread::spawn(move || {
+            let mut rate_limiter =
+                RateLimiter::new(Duration::from_secs(60), RateLimitState::new()).with_stop(stop_rx);
+            run_worker_loop(
+                &FakeBackend(response_with_confidence(0.9)),
+                &LearningConfig::default(),
+                &request_rx,
+                &event_tx,
+                &mut rate_limiter,
+                Duration::from_secs(60),
+                &SystemClock,
+            );
+        });
+        let request = || AnalysisRequest {
+            fingerprint: ProcessFingerprint::new("node"),
+            context: AnalysisContext {
+                working_directory: Some("/srv/app".to_string()),
+                ..node_context()
+            },
+        };
+
+        // The first analysis runs at once; the second waits out the minute
+        request_tx.send(request()).unwrap();
+        request_tx.send(request()).unwrap();
+        let first = event_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(first, KnowledgeEvent::AnalysisComplete(_)));
+
+        let worker = LearningWorker { handle, stop };
+        let start = Instant::now();
+        assert!(worker.shutdown(Duration::from_secs(5)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(event_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_shutdown_gives_up_on_a_busy_worker() {
+        let (stop, stop_rx) = crossbeam_channel::bounded::<()>(0);
+        let handle = thread::spawn(move || {
+            // An analysis that ignores the stop signal, like an HTTP call
+            thread::sleep(Duration::from_millis(500));
+            drop(stop_rx);
+        });
+
+        let worker = LearningWorker { handle, stop };
+        let start = Instant::now();
+        assert!(!worker.shutdown(Duration::from_millis(50)));
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_process_request_reports_analyze_time() {
+        let delay = Duration::from_millis(50);
+        let request = AnalysisRequest {
+            fingerprint: ProcessFingerprint::new("node"),
+            context: AnalysisContext {
+                // Already enriched, so no gatherer runs
+                working_directory: Some("/srv/app".to_string()),
+                ..node_context()
+            },
+        };
+
+        let result = process_request(&SlowBackend(delay), &LearningConfig::default(), request);
+
+        assert_eq!(result.source, KnowledgeSource::ApiLearned);
+        assert!(result.timing.analyze >= delay);
+        assert!(result.timing.enrich < delay);
+    }
 
-            if let Err(e) = result_tx.send(result) {
-                log::error!("Failed to send analysis result: {}", e);
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_successful_analysis_emits_span() {
+        let recorder = spans::SpanRecorder::default();
+        let request = AnalysisRequest {
+            fingerprint: ProcessFingerprint::new("node"),
+            context: AnalysisContext {
+                working_directory: Some("/srv/app".to_string()),
+                ..node_context()
+            },
+        };
+
+        let backend = FakeBackend(response_with_confidence(0.9));
+        tracing::subscriber::with_default(recorder.clone(), || {
+            process_request(&backend, &LearningConfig::default(), request)
+        });
+
+        let spans = recorder.spans.lock().unwrap();
+        let (_, fields) = spans
+            .iter()
+            .find(|(name, _)| *name == "analysis")
+            .expect("analysis span");
+        for expected in [
+            ("command", "node"),
+            ("port", "Some(3000)"),
+            ("source", "ApiLearned"),
+            ("outcome", "ok"),
+        ] {
+            let field = (expected.0.to_string(), expected.1.to_string());
+            assert!(fields.contains(&field), "missing {:?}", expected);
+        }
+    }
+
+    /// Minimal subscriber recording span names and fields
+    #[cfg(feature = "tracing")]
+    mod spans {
+        use std::fmt;
+        use std::sync::{Arc, Mutex};
+
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        type Fields = Vec<(String, String)>;
+
+        #[derive(Clone, Default)]
+        pub struct SpanRecorder {
+            pub spans: Arc<Mutex<Vec<(&'static str, Fields)>>>,
+        }
+
+        struct FieldVisitor<'a>(&'a mut Fields);
+
+        impl Visit for FieldVisitor<'_> {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.push((field.name().to_string(), value.to_string()));
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0
+                    .push((field.name().to_string(), format!("{:?}", value)));
             }
         }
 
-        log::info!("Learning worker shutting down");
-    })
+        impl Subscriber for SpanRecorder {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+                let mut spans = self.spans.lock().unwrap();
+                let mut fields = Vec::new();
+                attributes.record(&mut FieldVisitor(&mut fields));
+                spans.push((attributes.metadata().name(), fields));
+                Id::from_u64(spans.len() as u64)
+            }
+
+            fn record(&self, span: &Id, values: &Record<'_>) {
+                let mut spans = self.spans.lock().unwrap();
+                let index = span.into_u64() as usize - 1;
+                values.record(&mut FieldVisitor(&mut spans[index].1));
+            }
+
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+            fn event(&self, _event: &Event<'_>) {}
+
+            fn enter(&self, _span: &Id) {}
+
+            fn exit(&self, _span: &Id) {}
+        }
+    }
+
+    fn node_context() -> AnalysisContext {
+        AnalysisContext {
+            command: "node".to_string(),
+            port: Some(3000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_low_confidence_result_falls_back_to_heuristics() {
+        let backend = FakeBackend(response_with_confidence(0.3));
+        let (response, source, failed) = analyze_with(&backend, &node_context(), 0.5);
+
+        assert_eq!(source, KnowledgeSource::Heuristic);
+        assert!(!failed);
+        assert_eq!(
+            response.display_name,
+            generate_fallback(&node_context()).display_name
+        );
+    }
+
+    #[test]
+    fn test_confident_result_is_applied() {
+        let backend = FakeBackend(response_with_confidence(0.9));
+        let (response, source, failed) = analyze_with(&backend, &node_context(), 0.5);
+
+        assert_eq!(source, KnowledgeSource::ApiLearned);
+        assert!(!failed);
+        assert_eq!(response.display_name, "Maybe A Thing");
+    }
+
+    #[test]
+    fn test_empty_command_skips_backend() {
+        let backend = FakeBackend(response_with_confidence(0.9));
+        let context = AnalysisContext {
+            port: Some(7000),
+            ..AnalysisContext::new("")
+        };
+        let (response, source, failed) = analyze_with(&backend, &context, 0.5);
+
+        assert_eq!(source, KnowledgeSource::Heuristic);
+        assert!(!failed);
+        assert_eq!(response.display_name, "Unknown process on :7000");
+    }
+
+    /// Always returns the same jitter sample
+    struct FixedJitter(f64);
+
+    impl JitterSource for FixedJitter {
+        fn sample(&mut self) -> f64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_state_starts_ready() {
+        let state = RateLimitState::new();
+        assert_eq!(state.remaining(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_nothing_published_while_queue_is_empty() {
+        let state = RateLimitState::new();
+        let mut limiter = RateLimiter::with_jitter(
+            Duration::from_secs(5),
+            state.clone(),
+            Box::new(FixedJitter(0.0)),
+        );
+
+        // A call just ran and nothing else is queued
+        limiter.acquire();
+        assert_eq!(state.next_call_at_ms(), 0);
+        assert_eq!(state.remaining(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_wait_published_while_request_is_held_back() {
+        let state = RateLimitState::new();
+        let mut limiter = RateLimiter::with_jitter(
+            Duration::from_millis(300),
+            state.clone(),
+            Box::new(FixedJitter(0.0)),
+        );
+        limiter.acquire();
+
+        let before = now_millis();
+        let waiting = thread::spawn(move || limiter.acquire());
+        thread::sleep(Duration::from_millis(100));
+        let next_call = state.next_call_at_ms();
+        assert!(next_call > before && next_call <= before + 300);
+        assert!(!state.remaining().is_zero());
+
+        waiting.join().unwrap();
+        assert_eq!(state.remaining(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_jitter_widens_interval() {
+        let mut limiter = RateLimiter::with_jitter(
+            Duration::from_millis(100),
+            RateLimitState::new(),
+            Box::new(FixedJitter(1.0)),
+        );
+        limiter.acquire();
+
+        let start = Instant::now();
+        limiter.acquire();
+        assert!(start.elapsed() >= Duration::from_millis(105));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(10), RateLimitState::new());
+        for _ in 0..100 {
+            let interval = limiter.jittered_interval();
+            assert!(interval >= Duration::from_secs(9) && interval <= Duration::from_secs(11));
+        }
+    }
 }
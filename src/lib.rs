@@ -12,9 +12,11 @@ pub mod process {
     pub mod ports;
 }
 pub mod ui {
+    pub mod describe;
     pub mod icon;
     pub mod menu;
     pub mod process_icons;
+    pub mod theme;
 }
 pub mod integrations {
     pub mod brew;
@@ -1,4 +1,15 @@
 fn main() -> anyhow::Result<()> {
     env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("analyze") {
+        let config = portkiller::config::load_or_create_config()?;
+        println!(
+            "{}",
+            portkiller::knowledge::analyze_cli(&args[1..], &config.learning)?
+        );
+        return Ok(());
+    }
+
     portkiller::run()
 }
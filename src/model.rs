@@ -1,9 +1,12 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use nix::errno::Errno;
 
-use crate::knowledge::{AnalysisResult, KnowledgeBase};
+use crate::knowledge::{
+    AnalysisContext, AnalysisResult, KnowledgeBase, PromptHistory, Protocol, RateLimitState,
+};
 use crate::update::UpdateInfo;
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -11,8 +14,12 @@ pub struct ProcessInfo {
     pub port: u16,
     pub pid: i32,
     pub command: String,
+    pub protocol: Protocol,
 }
 
+// Events are moved into the event loop one at a time, so the size of the
+// largest variant (a reloaded config or an analysis result) doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug)]
 pub enum UserEvent {
     ProcessesUpdated(Vec<ProcessInfo>),
@@ -23,6 +30,7 @@ pub enum UserEvent {
     ConfigReloadFailed(String),
     UpdateCheckResult(Option<UpdateInfo>),
     KnowledgeUpdated(AnalysisResult),
+    KnowledgeMaintenance,
 }
 
 #[derive(Clone, Debug)]
@@ -98,6 +106,18 @@ pub struct AppState {
     pub brew_services_map: HashMap<String, String>, // service_name -> status
     pub available_update: Option<UpdateInfo>,
     pub knowledge_base: KnowledgeBase,
+    /// Listening master of each listening worker process, from the last
+    /// learning pass; the menu lists each group once under its master
+    pub process_masters: HashMap<i32, i32>,
+    /// Enriched context of each listening (pid, port) from the last learning
+    /// pass, as fingerprinted for its group; the menu looks knowledge up with
+    /// the same fingerprints learning stores it under
+    pub process_contexts: HashMap<(i32, u16), AnalysisContext>,
+    /// When the learning worker may next call ICA (for "identifying in N s" hints)
+    pub learning_rate_limit: RateLimitState,
+    /// Recent ICA prompts and replies recorded by the learning worker
+    /// (`learning.prompt_history_size`; empty when 0)
+    pub prompt_history: Arc<Mutex<PromptHistory>>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -114,6 +134,8 @@ pub struct ProjectInfo {
     pub name: String,
     #[allow(dead_code)]
     pub path: PathBuf,
+    /// Framework detected from project files (e.g., "rust")
+    pub framework: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -121,4 +143,6 @@ pub struct DockerContainerInfo {
     pub name: String,
     #[allow(dead_code)]
     pub id: String,
+    /// Health check status, if the container has a health check
+    pub health: Option<String>,
 }
@@ -1,32 +1,151 @@
 use std::collections::HashSet;
+use std::io::ErrorKind;
 use std::process::Command;
 
 use anyhow::{Context, Result, anyhow};
 
+use crate::knowledge::Protocol;
 use crate::model::ProcessInfo;
 
 pub fn scan_ports(port_ranges: &[(u16, u16)]) -> Result<Vec<ProcessInfo>> {
-    fn in_ranges(port: u16, ranges: &[(u16, u16)]) -> bool {
-        ranges.iter().any(|(s, e)| port >= *s && port <= *e)
+    // lsof ORs the -i selections: TCP listeners plus bound UDP sockets
+    // (`-sTCP:LISTEN` only filters the TCP ones)
+    let output = match Command::new("lsof")
+        .args(["-nP", "-iTCP", "-sTCP:LISTEN", "-iUDP", "-FpcnPT"])
+        .output()
+    {
+        // Many Linux installs ship `ss` (iproute2) but not lsof
+        Err(e) if e.kind() == ErrorKind::NotFound => return scan_ports_with_ss(port_ranges),
+        result => result.context("failed to execute lsof sweep")?,
+    };
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "lsof sweep failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
     }
 
-    let output = Command::new("lsof")
-        .args(["-nP", "-iTCP", "-sTCP:LISTEN", "-FpcnPT"])
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_lsof_listeners(&stdout, port_ranges))
+}
+
+/// Sweep listening TCP and UDP sockets with `ss`, for systems without lsof.
+fn scan_ports_with_ss(port_ranges: &[(u16, u16)]) -> Result<Vec<ProcessInfo>> {
+    let output = Command::new("ss")
+        .args(["-tulnpH"])
         .output()
-        .context("failed to execute lsof sweep")?;
+        .context("failed to execute lsof or ss sweep")?;
 
     if !output.status.success() {
         return Err(anyhow!(
-            "lsof sweep failed: {}",
+            "ss sweep failed: {}",
             String::from_utf8_lossy(&output.stderr)
         ));
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_ss_listeners(&stdout, port_ranges))
+}
+
+fn in_ranges(port: u16, ranges: &[(u16, u16)]) -> bool {
+    ranges.iter().any(|(s, e)| port >= *s && port <= *e)
+}
+
+/// Start of the ports handed out to unbound sockets (Linux uses 32768 on,
+/// macOS 49152 on). UDP sockets there are clients (DNS lookups, QUIC, mDNS
+/// responders), not services.
+const EPHEMERAL_PORT_START: u16 = 32768;
+
+/// Whether a socket on `port` is worth listing: every TCP listener, but only
+/// UDP sockets bound to a fixed port
+fn is_service_port(port: u16, protocol: Protocol) -> bool {
+    protocol == Protocol::Tcp || port < EPHEMERAL_PORT_START
+}
+
+/// Parse `ss -tulnp` output into listening processes within the given port ranges.
+///
+/// Lines look like
+/// `tcp LISTEN 0 128 *:3000 *:* users:(("node",pid=1234,fd=20))`; sockets
+/// shared by several processes list each of them in `users:(...)`.
+fn parse_ss_listeners(stdout: &str, port_ranges: &[(u16, u16)]) -> Vec<ProcessInfo> {
+    let mut results: Vec<ProcessInfo> = Vec::new();
+    let mut seen: HashSet<(u16, i32, Protocol)> = HashSet::new();
+
+    for line in stdout.lines() {
+        // The process column comes last and its names may contain spaces,
+        // so it is cut off before splitting the rest
+        let Some(users_at) = line.find("users:(") else {
+            continue;
+        };
+        let (columns, users) = line.split_at(users_at);
+        let fields: Vec<&str> = columns.split_whitespace().collect();
+        // Netid, State, Recv-Q, Send-Q, Local, Peer
+        let (Some(protocol), Some(local), Some(peer)) = (
+            fields.first().and_then(|netid| Protocol::parse(netid)),
+            fields.get(4),
+            fields.get(5),
+        ) else {
+            continue;
+        };
+        // A UDP socket with a connected peer is a client
+        if !peer.ends_with(":*") {
+            continue;
+        }
+        let Some(port) = parse_port_from_lsof(local) else {
+            continue;
+        };
+        if !in_ranges(port, port_ranges) || !is_service_port(port, protocol) {
+            continue;
+        }
+        for (pid, command) in parse_ss_users(users) {
+            if seen.insert((port, pid, protocol)) {
+                results.push(ProcessInfo {
+                    port,
+                    pid,
+                    command,
+                    protocol,
+                });
+            }
+        }
+    }
+
+    results.sort();
+    results
+}
+
+/// Extract `(pid, command)` pairs from an `ss` process column such as
+/// `users:(("nginx",pid=10,fd=6),("nginx",pid=11,fd=6))`. Each name is
+/// taken whole from between its quotes, spaces and commas included.
+fn parse_ss_users(field: &str) -> Vec<(i32, String)> {
+    let Some(mut rest) = field.trim().strip_prefix("users:(") else {
+        return Vec::new();
+    };
+    let mut users = Vec::new();
+    while let Some(start) = rest.find("(\"") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("\",pid=") else {
+            break;
+        };
+        let command = &rest[..end];
+        rest = &rest[end + "\",pid=".len()..];
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if let Ok(pid) = rest[..digits].parse() {
+            users.push((pid, command.to_string()));
+        }
+    }
+    users
+}
+
+/// Parse `lsof -F` field output into listening processes within the given port ranges.
+fn parse_lsof_listeners(stdout: &str, port_ranges: &[(u16, u16)]) -> Vec<ProcessInfo> {
     let mut current_pid: Option<i32> = None;
     let mut current_cmd: Option<String> = None;
+    let mut current_protocol: Option<Protocol> = None;
     let mut results: Vec<ProcessInfo> = Vec::new();
-    let mut seen: HashSet<(u16, i32)> = HashSet::new();
+    let mut seen: HashSet<(u16, i32, Protocol)> = HashSet::new();
 
     for line in stdout.lines() {
         if line.is_empty() {
@@ -37,20 +156,32 @@ pub fn scan_ports(port_ranges: &[(u16, u16)]) -> Result<Vec<ProcessInfo>> {
             "p" => {
                 current_pid = val.trim().parse::<i32>().ok();
                 current_cmd = None;
+                current_protocol = None;
             }
             "c" => {
                 current_cmd = Some(val.trim().to_string());
             }
+            "f" => {
+                // New file descriptor section; protocol is reported per file
+                current_protocol = None;
+            }
+            "P" => {
+                current_protocol = Protocol::parse(val);
+            }
             "n" => {
+                let protocol = current_protocol.unwrap_or(Protocol::Tcp);
+                // Connected sockets (`local->peer`) yield no port
                 if let (Some(pid), Some(cmd)) = (current_pid, current_cmd.as_ref())
                     && let Some(port) = parse_port_from_lsof(val.trim())
                     && in_ranges(port, port_ranges)
-                    && seen.insert((port, pid))
+                    && is_service_port(port, protocol)
+                    && seen.insert((port, pid, protocol))
                 {
                     results.push(ProcessInfo {
                         port,
                         pid,
                         command: cmd.clone(),
+                        protocol,
                     });
                 }
             }
@@ -59,10 +190,10 @@ pub fn scan_ports(port_ranges: &[(u16, u16)]) -> Result<Vec<ProcessInfo>> {
     }
 
     results.sort();
-    Ok(results)
+    results
 }
 
-/// Verify that a PID is still associated with a TCP listener.
+/// Verify that a PID is still associated with a TCP listener or UDP socket.
 /// Used to mitigate TOCTOU race conditions before killing a process.
 pub fn verify_pid_is_listener(pid: i32) -> bool {
     let output = Command::new("lsof")
@@ -72,6 +203,7 @@ pub fn verify_pid_is_listener(pid: i32) -> bool {
             &pid.to_string(),
             "-iTCP",
             "-sTCP:LISTEN",
+            "-iUDP",
             "-Fn",
         ])
         .output();
@@ -83,10 +215,33 @@ pub fn verify_pid_is_listener(pid: i32) -> bool {
                 .lines()
                 .any(|line| line.starts_with('n'))
         }
+        Err(e) if e.kind() == ErrorKind::NotFound => scan_ports_with_ss(&[(0, u16::MAX)])
+            .map(|listeners| listeners.iter().any(|info| info.pid == pid))
+            .unwrap_or(false),
         _ => false,
     }
 }
 
+/// Verify that a PID is still a listener, or the master of forked workers
+/// that are (e.g. a gunicorn arbiter whose workers hold the sockets).
+pub fn verify_pid_leads_listener(pid: i32) -> bool {
+    verify_pid_is_listener(pid) || child_pids(pid).into_iter().any(verify_pid_is_listener)
+}
+
+/// Direct children of a process, from `pgrep -P`
+fn child_pids(pid: i32) -> Vec<i32> {
+    match Command::new("pgrep")
+        .args(["-P", &pid.to_string()])
+        .output()
+    {
+        Ok(out) => String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
 // Extract a port number from an lsof name field.
 // Handles "*:3000", "127.0.0.1:5173", and "[::1]:8000".
 pub fn parse_port_from_lsof(name: &str) -> Option<u16> {
@@ -115,7 +270,7 @@ pub fn parse_port_from_lsof(name: &str) -> Option<u16> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_port_from_lsof;
+    use super::{Protocol, parse_lsof_listeners, parse_port_from_lsof, parse_ss_listeners};
 
     #[test]
     fn parses_ipv4_wildcard() {
@@ -138,4 +293,81 @@ mod tests {
         assert_eq!(parse_port_from_lsof("127.0.0.1->192.168.0.1:1234"), None);
         assert_eq!(parse_port_from_lsof("garbage"), None);
     }
+
+    #[test]
+    fn parses_protocol_per_file() {
+        let output =
+            "p100\ncnode\nf20\nPTCP\nn*:3000\nTST=LISTEN\np200\ncdnsmasq\nf5\nPUDP\nn*:5353\n";
+        let results = parse_lsof_listeners(output, &[(3000, 6000)]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].port, 3000);
+        assert_eq!(results[0].protocol, Protocol::Tcp);
+        assert_eq!(results[1].port, 5353);
+        assert_eq!(results[1].protocol, Protocol::Udp);
+    }
+
+    #[test]
+    fn keeps_tcp_and_udp_on_the_same_port() {
+        let output = "p300\ncdnsd\nf4\nPTCP\nn*:5353\nTST=LISTEN\nf5\nPUDP\nn*:5353\n";
+        let results = parse_lsof_listeners(output, &[(5000, 6000)]);
+        let protocols: Vec<Protocol> = results.iter().map(|info| info.protocol).collect();
+        assert_eq!(protocols, vec![Protocol::Tcp, Protocol::Udp]);
+    }
+
+    #[test]
+    fn skips_client_udp_sockets() {
+        // A resolver's connected socket, a browser's ephemeral QUIC socket,
+        // and a TCP listener that happens to use a high port
+        let output = "p400\ncmDNSRespo\nf6\nPUDP\nn192.168.1.5:5353->224.0.0.251:5353\n\
+                      p500\ncfirefox\nf9\nPUDP\nn*:52144\nf10\nPTCP\nn*:52144\nTST=LISTEN\n";
+        let results = parse_lsof_listeners(output, &[(1, 65535)]);
+        let found: Vec<(u16, Protocol)> = results
+            .iter()
+            .map(|info| (info.port, info.protocol))
+            .collect();
+        assert_eq!(found, vec![(52144, Protocol::Tcp)]);
+
+        let output = "\
+udp   ESTAB  0      0    10.0.0.2:5353      10.0.0.1:5353  users:((\"resolver\",pid=70,fd=4))
+udp   UNCONN 0      0     0.0.0.0:41234      0.0.0.0:*     users:((\"chrome\",pid=71,fd=9))
+udp   UNCONN 0      0     0.0.0.0:5353       0.0.0.0:*     users:((\"avahi-daemon\",pid=640,fd=12))
+";
+        let results = parse_ss_listeners(output, &[(1, 65535)]);
+        let pids: Vec<i32> = results.iter().map(|info| info.pid).collect();
+        assert_eq!(pids, vec![640]);
+    }
+
+    #[test]
+    fn parses_ss_listeners() {
+        let output = "\
+tcp   LISTEN 0      511          *:3000            *:*    users:((\"node\",pid=1234,fd=20))
+tcp   LISTEN 0      128    0.0.0.0:22        0.0.0.0:*    users:((\"sshd\",pid=812,fd=3))
+tcp   LISTEN 0      511    0.0.0.0:8080      0.0.0.0:*    users:((\"nginx\",pid=11,fd=6),(\"nginx\",pid=10,fd=6))
+tcp   LISTEN 0      4096    [::1]:5432          [::]:*    users:((\"postgres\",pid=977,fd=7))
+tcp   LISTEN 0      128  127.0.0.1:6379      0.0.0.0:*
+udp   UNCONN 0      0      0.0.0.0:5353      0.0.0.0:*    users:((\"avahi-daemon\",pid=640,fd=12))
+tcp   LISTEN 0      128  127.0.0.1:9222      0.0.0.0:*    users:((\"Web Content, 2\",pid=501,fd=9))
+";
+        let results = parse_ss_listeners(output, &[(1000, 10000)]);
+        let tuples: Vec<(u16, i32, &str)> = results
+            .iter()
+            .map(|info| (info.port, info.pid, info.command.as_str()))
+            .collect();
+        assert_eq!(
+            tuples,
+            vec![
+                (3000, 1234, "node"),
+                (5353, 640, "avahi-daemon"),
+                (5432, 977, "postgres"),
+                (8080, 10, "nginx"),
+                (8080, 11, "nginx"),
+                (9222, 501, "Web Content, 2"),
+            ]
+        );
+        assert!(
+            results
+                .iter()
+                .all(|info| (info.protocol == Protocol::Udp) == (info.port == 5353))
+        );
+    }
 }
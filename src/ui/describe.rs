@@ -0,0 +1,167 @@
+//! One-call description of a listening port for display.
+//!
+//! Ties together context enrichment, fingerprinting, knowledge base lookup,
+//! and the heuristic fallback, so a caller holding only what `lsof` reported
+//! gets everything needed to render the process.
+
+use crate::knowledge::fallback::generate_fallback;
+use crate::knowledge::project_config::user_defined_response;
+use crate::knowledge::{
+    AnalysisContext, Confidence, KnowledgeBase, KnowledgeEntry, KnowledgeSource, LearningConfig,
+    ProcessCategory, ProcessFingerprint, enrich_context, lookup_best_with, proxied_name,
+};
+use crate::ui::process_icons::{ProcessIconType, icon_type_for_entry, icon_type_from_context};
+
+/// Everything needed to show a listening process
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortDescription {
+    pub display_name: String,
+    pub description: String,
+    pub category: ProcessCategory,
+    pub icon_type: ProcessIconType,
+    /// Where the name came from; `Heuristic` when nothing is known yet
+    pub source: KnowledgeSource,
+    pub confidence: Confidence,
+    /// Group shared with related services, if any
+    pub group: Option<String>,
+    /// The name was learned from too few sightings to be trusted (see
+    /// `LearningConfig::provisional_threshold`)
+    pub provisional: bool,
+}
+
+/// Describe the process `pid` (running `command`) listening on `port`.
+///
+/// The context is enriched with the gatherers `config` enables, then the
+/// best matching knowledge entry is used. Processes the knowledge base
+/// doesn't know yet are described by their project override or the
+/// heuristic fallback; ICA is never called, so this is cheap enough for the
+/// UI thread apart from enrichment.
+pub fn describe_port(
+    kb: &KnowledgeBase,
+    command: &str,
+    port: u16,
+    pid: Option<u32>,
+    config: &LearningConfig,
+) -> PortDescription {
+    let mut context = AnalysisContext {
+        port: Some(port),
+        pid,
+        ..AnalysisContext::new(command)
+    };
+    enrich_context(&mut context, &config.gatherers);
+    describe_context(kb, &context, config)
+}
+
+/// [`describe_port`] for a context that is already enriched
+pub fn describe_context(
+    kb: &KnowledgeBase,
+    context: &AnalysisContext,
+    config: &LearningConfig,
+) -> PortDescription {
+    let fingerprint = ProcessFingerprint::from_config(context, config);
+    let icon_type = icon_type_from_context(context);
+
+    if let Some(entry) = lookup_best_with(kb, &fingerprint, config.provisional_threshold) {
+        return PortDescription {
+            display_name: known_display_name(entry, context),
+            description: entry.description.clone(),
+            category: entry.category.clone(),
+            icon_type: match icon_type {
+                // Fall back to the learned category (e.g. a custom database gateway)
+                ProcessIconType::Generic => icon_type_for_entry(entry),
+                icon_type => icon_type,
+            },
+            source: entry.source.clone(),
+            confidence: entry.confidence,
+            group: entry.group_id.clone(),
+            provisional: entry.is_provisional(config.provisional_threshold),
+        };
+    }
+
+    let (response, source) = match user_defined_response(context) {
+        Some(response) => (response, KnowledgeSource::UserDefined),
+        None => (generate_fallback(context), KnowledgeSource::Heuristic),
+    };
+    PortDescription {
+        display_name: response.display_name,
+        description: response.description,
+        category: response.category,
+        icon_type,
+        source,
+        confidence: response.confidence,
+        group: response.group_hint,
+        provisional: false,
+    }
+}
+
+/// Name of a process with a knowledge entry. Builtin names are generic
+/// ("NGINX"), so a reverse proxy's also say where it leads.
+pub(crate) fn known_display_name(entry: &KnowledgeEntry, context: &AnalysisContext) -> String {
+    match entry.source {
+        KnowledgeSource::Builtin => proxied_name(&entry.display_name, &context.proxy_upstreams),
+        _ => entry.display_name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::knowledge::builtin::populate_builtins;
+    use crate::knowledge::store_result;
+    use crate::knowledge::types::IcaAnalysisResponse;
+
+    #[test]
+    fn test_describe_port_end_to_end() {
+        let config = LearningConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let mut kb = KnowledgeBase::default();
+        populate_builtins(&mut kb);
+
+        // Builtin
+        let postgres = describe_port(&kb, "postgres", 5432, None, &config);
+        assert_eq!(postgres.display_name, "PostgreSQL Database");
+        assert_eq!(postgres.category, ProcessCategory::Database);
+        assert_eq!(postgres.icon_type, ProcessIconType::PostgreSQL);
+        assert_eq!(postgres.source, KnowledgeSource::Builtin);
+
+        // Builtin proxies name their upstreams
+        let nginx = AnalysisContext {
+            port: Some(8080),
+            proxy_upstreams: vec!["api:3000".to_string()],
+            ..AnalysisContext::new("nginx")
+        };
+        let nginx = describe_context(&kb, &nginx, &config);
+        assert_eq!(nginx.display_name, "NGINX -> api:3000");
+        assert_eq!(nginx.source, KnowledgeSource::Builtin);
+
+        // Unknown: heuristic fallback, nothing stored
+        let unknown = describe_port(&kb, "db-gateway", 7000, None, &config);
+        assert_eq!(unknown.source, KnowledgeSource::Heuristic);
+        assert_eq!(unknown.icon_type, ProcessIconType::Generic);
+        assert!(!unknown.display_name.is_empty());
+        assert!(lookup_best_with(&kb, &ProcessFingerprint::new("db-gateway"), 0).is_none());
+
+        // Once learned, the entry is used; its category alone picks the
+        // neutral icon (tinted by category in the menu)
+        let response = IcaAnalysisResponse {
+            display_name: "Shop DB Gateway".to_string(),
+            description: "Pools connections to the shop database".to_string(),
+            category: ProcessCategory::Database,
+            group_hint: Some("shop".to_string()),
+            confidence: Confidence::new(0.9),
+            category_confidence: None,
+            reason: None,
+        };
+        let fingerprint = ProcessFingerprint::new("db-gateway");
+        store_result(&mut kb, fingerprint, response, KnowledgeSource::ApiLearned);
+        let learned = describe_port(&kb, "db-gateway", 7000, None, &config);
+        assert_eq!(learned.display_name, "Shop DB Gateway");
+        assert_eq!(learned.icon_type, ProcessIconType::Generic);
+        assert_eq!(learned.category, ProcessCategory::Database);
+        assert_eq!(learned.source, KnowledgeSource::ApiLearned);
+        assert_eq!(learned.group.as_deref(), Some("shop"));
+        assert!(!learned.provisional);
+    }
+}
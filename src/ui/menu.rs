@@ -1,14 +1,19 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::process::Command;
+use std::time::Duration;
 
 use anyhow::Result;
 use tray_icon::menu::{IconMenuItem, Menu, MenuId, MenuItem, PredefinedMenuItem, Submenu};
 
-use crate::knowledge::{lookup_display_name, KnowledgeBase, ProcessFingerprint};
+use crate::knowledge::{
+    lookup_best_with, lookup_entry, proxied_name, AnalysisContext, KnowledgeBase, KnowledgeEntry,
+    LearningConfig, ProcessFingerprint,
+};
 use crate::model::{AppState, FeedbackSeverity, KillFeedback, ProcessInfo};
+use crate::ui::describe::known_display_name;
 use crate::ui::process_icons::{
-    get_process_icon, icon_type_for_brew, icon_type_for_docker, icon_type_from_command,
-    ProcessIconType,
+    classify_command, composite_icon, get_category_icon, get_process_icon, icon_type_for_brew,
+    icon_type_for_docker, icon_type_for_entry, icon_type_from_context, BadgeKind, ProcessIconType,
 };
 
 const MAX_TOOLTIP_ENTRIES: usize = 5;
@@ -27,6 +32,7 @@ const MENU_ID_PROCESS_PREFIX: &str = "process_";
 const MENU_ID_DOCKER_STOP_PREFIX: &str = "docker_stop_";
 const MENU_ID_BREW_STOP_PREFIX: &str = "brew_stop_";
 const MENU_ID_EMPTY: &str = "empty";
+const MENU_ID_RATE_LIMIT: &str = "rate_limit";
 
 /// Extract project prefix from container name (e.g., "dss_app" -> ("dss", "app"))
 fn parse_container_prefix(name: &str) -> (String, String) {
@@ -100,13 +106,64 @@ fn is_known_system_process(command: &str) -> bool {
     )
 }
 
-/// Get display name for a process from knowledge base, or fall back to command
-fn get_process_display_name(command: &str, container_prefix: Option<&str>, kb: &KnowledgeBase) -> Option<String> {
-    let mut fingerprint = ProcessFingerprint::new(command);
-    if let Some(prefix) = container_prefix {
-        fingerprint = fingerprint.with_container_prefix(prefix);
+/// Context of a listed process: the enriched one from the last learning
+/// pass, else one built from the command (or container) name alone
+fn process_context(
+    state: &AppState,
+    process: &ProcessInfo,
+    command: &str,
+    container_prefix: Option<&str>,
+) -> AnalysisContext {
+    state
+        .process_contexts
+        .get(&(process.pid, process.port))
+        .cloned()
+        .unwrap_or_else(|| AnalysisContext {
+            container_prefix: container_prefix.map(str::to_string),
+            ..AnalysisContext::new(command)
+        })
+}
+
+/// Knowledge entry for a process, fingerprinted from its context the same
+/// way learning stores it. When nothing is stored under exactly that
+/// fingerprint, the best less specific entry is used, e.g. one learned before
+/// a new image digest was pinned.
+fn process_entry<'a>(
+    kb: &'a KnowledgeBase,
+    context: &AnalysisContext,
+    config: &LearningConfig,
+) -> Option<&'a KnowledgeEntry> {
+    let fingerprint = ProcessFingerprint::from_config(context, config);
+    lookup_entry(kb, &fingerprint)
+        .or_else(|| lookup_best_with(kb, &fingerprint, config.provisional_threshold))
+}
+
+/// Learned name of a container. Learning knows it by its listening process
+/// and compose labels; names keyed by the container (or service) name itself
+/// are used when that finds nothing.
+fn container_display_name(
+    state: &AppState,
+    process: &ProcessInfo,
+    name: &str,
+    prefix: Option<&str>,
+) -> Option<String> {
+    let kb = &state.knowledge_base;
+    let learning = &state.config.learning;
+    let by_name = AnalysisContext {
+        container_prefix: prefix.map(str::to_string),
+        ..AnalysisContext::new(name)
+    };
+    process_entry(kb, &process_context(state, process, name, prefix), learning)
+        .or_else(|| process_entry(kb, &by_name, learning))
+        .map(|entry| entry.display_name.clone())
+}
+
+/// Append a container's health check status to its label unless it's healthy
+fn with_docker_health(label: String, health: Option<&str>) -> String {
+    match health {
+        Some(health) if health != "healthy" => format!("{} ({})", label, health),
+        _ => label,
     }
-    lookup_display_name(kb, &fingerprint)
 }
 
 /// Maps common container names to friendly display names
@@ -126,6 +183,7 @@ fn friendly_container_name(raw_name: &str) -> String {
         "nginx" => "Nginx".to_string(),
         "rabbitmq" => "RabbitMQ".to_string(),
         "elasticsearch" => "Elasticsearch".to_string(),
+        "clickhouse" | "clickhouse-server" => "ClickHouse".to_string(),
         "memcached" => "Memcached".to_string(),
         _ => {
             // Capitalize first letter of unknown containers
@@ -141,6 +199,8 @@ fn friendly_container_name(raw_name: &str) -> String {
 pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
     let menu = Menu::new();
     let processes = &state.processes;
+    let kb = &state.knowledge_base;
+    let learning = &state.config.learning;
 
     if processes.is_empty() {
         let item = MenuItem::with_id(MENU_ID_EMPTY, "No dev ports listening", false, None);
@@ -153,6 +213,10 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
         let mut regular_processes: Vec<&ProcessInfo> = Vec::new();
 
         for process in processes {
+            let context = process_context(state, process, &process.command, None);
+            if process_entry(kb, &context, learning).is_some_and(|entry| entry.hidden) {
+                continue;
+            }
             if let Some(dc) = state.docker_port_map.get(&process.port) {
                 docker_items.push((process, dc));
             } else if let Some(service) = crate::integrations::brew::get_brew_managed_service(
@@ -174,12 +238,12 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
         if !regular_processes.is_empty() {
             has_any_section = true;
 
-            // Group by PID to count unique processes
-            let mut by_pid: BTreeMap<i32, (String, Vec<u16>)> = BTreeMap::new();
+            // Group by PID to count unique processes, folding forked workers
+            // into their master
+            let mut by_pid: BTreeMap<i32, (&ProcessInfo, Vec<u16>)> = BTreeMap::new();
             for p in &regular_processes {
-                let entry = by_pid
-                    .entry(p.pid)
-                    .or_insert_with(|| (p.command.clone(), Vec::new()));
+                let pid = state.process_masters.get(&p.pid).copied().unwrap_or(p.pid);
+                let entry = by_pid.entry(pid).or_insert_with(|| (p, Vec::new()));
                 if !entry.1.contains(&p.port) {
                     entry.1.push(p.port);
                 }
@@ -194,15 +258,28 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
             menu.append(&header)?;
 
             // Create clickable menu item for each process (grouped by PID)
-            for (pid, (command, ports)) in &mut by_pid {
+            for (pid, (process, ports)) in &mut by_pid {
                 ports.sort();
+                let command = &process.command;
 
-                // Get project name for this PID
-                let project_name = state.project_cache.get(pid).map(|pi| pi.name.clone());
+                // Get project name for this PID, or for the listening worker
+                // if the master's hasn't been resolved yet
+                let project = state
+                    .project_cache
+                    .get(pid)
+                    .or_else(|| state.project_cache.get(&process.pid));
+                let project_name = project.map(|pi| pi.name.clone());
 
                 // Try to get display name from knowledge base
-                let display_name = get_process_display_name(command, None, &state.knowledge_base)
+                let context = process_context(state, process, command, None);
+                let known = process_entry(kb, &context, learning);
+                let mut display_name = known
+                    .map(|entry| known_display_name(entry, &context))
                     .unwrap_or_else(|| command.clone());
+                // Mark names learned from too few sightings as tentative
+                if known.is_some_and(|entry| entry.is_provisional(learning.provisional_threshold)) {
+                    display_name.push('?');
+                }
 
                 // Build main menu label: "ports · display_name · project"
                 let ports_str = ports
@@ -218,8 +295,26 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
                 };
 
                 // Create clickable menu item with process icon
-                let icon_type = icon_type_from_command(command);
-                let icon = get_process_icon(icon_type);
+                let mut icon_type = icon_type_from_context(&AnalysisContext {
+                    command: command.clone(),
+                    detected_framework: project.and_then(|pi| pi.framework.clone()),
+                    ..Default::default()
+                });
+                // Fall back to the learned category (e.g. a custom database gateway)
+                let mut category = None;
+                if icon_type == ProcessIconType::Generic
+                    && let Some(entry) = known
+                {
+                    icon_type = icon_type_for_entry(entry);
+                    category = Some(entry.category.clone());
+                }
+                // Anything still generic is tinted with its category's theme color
+                let icon = if icon_type == ProcessIconType::Generic {
+                    let category = category.unwrap_or_else(|| classify_command(command).1);
+                    get_category_icon(&category, &state.config.theme)
+                } else {
+                    get_process_icon(icon_type)
+                };
                 let process_item = IconMenuItem::with_id(
                     MenuId::new(process_menu_id(*pid, ports[0])),
                     main_label,
@@ -247,7 +342,13 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
 
             // Group by container name, storing (container_name, ports)
             let mut by_container: BTreeMap<String, Vec<u16>> = BTreeMap::new();
+            let mut health_by_container: HashMap<&str, &str> = HashMap::new();
+            let mut process_by_container: HashMap<&str, &ProcessInfo> = HashMap::new();
             for (process, dc) in &docker_items {
+                if let Some(health) = dc.health.as_deref() {
+                    health_by_container.insert(&dc.name, health);
+                }
+                process_by_container.entry(&dc.name).or_insert(process);
                 by_container
                     .entry(dc.name.clone())
                     .or_default()
@@ -258,6 +359,8 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
             let mut by_prefix: BTreeMap<String, Vec<(String, Vec<u16>)>> = BTreeMap::new();
             for (container_name, mut ports) in by_container {
                 ports.sort();
+                // A port bound over both TCP and UDP is listed once
+                ports.dedup();
                 let (prefix, _service) = parse_container_prefix(&container_name);
                 by_prefix
                     .entry(prefix)
@@ -282,11 +385,17 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
                     // No prefix or single container - render flat
                     for (container_name, ports) in containers {
                         // Try knowledge base first, fall back to friendly name
-                        let display_name = get_process_display_name(
+                        let display_name = container_display_name(
+                            state,
+                            process_by_container[container_name.as_str()],
                             container_name,
-                            if prefix.is_empty() { None } else { Some(prefix) },
-                            &state.knowledge_base,
-                        ).unwrap_or_else(|| friendly_container_name(container_name));
+                            (!prefix.is_empty()).then_some(prefix.as_str()),
+                        )
+                        .unwrap_or_else(|| friendly_container_name(container_name));
+                        let display_name = with_docker_health(
+                            display_name,
+                            health_by_container.get(container_name.as_str()).copied(),
+                        );
 
                         let ports_str = ports
                             .iter()
@@ -313,11 +422,17 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
                     for (container_name, ports) in containers {
                         let (_prefix, service) = parse_container_prefix(container_name);
                         // Try knowledge base first, fall back to friendly name
-                        let display_name = get_process_display_name(
+                        let display_name = container_display_name(
+                            state,
+                            process_by_container[container_name.as_str()],
                             &service,
                             Some(prefix),
-                            &state.knowledge_base,
-                        ).unwrap_or_else(|| friendly_container_name(&service));
+                        )
+                        .unwrap_or_else(|| friendly_container_name(&service));
+                        let display_name = with_docker_health(
+                            display_name,
+                            health_by_container.get(container_name.as_str()).copied(),
+                        );
 
                         let ports_str = ports
                             .iter()
@@ -362,11 +477,13 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
 
             // Group by service name
             let mut by_service: BTreeMap<String, Vec<u16>> = BTreeMap::new();
+            let mut process_by_service: HashMap<&str, &ProcessInfo> = HashMap::new();
             for (process, service) in &brew_items {
                 by_service
                     .entry(service.clone())
                     .or_default()
                     .push(process.port);
+                process_by_service.entry(service).or_insert(process);
             }
 
             let header = MenuItem::with_id(
@@ -383,6 +500,7 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
             // Create clickable menu item for each service
             for (service_name, mut ports) in by_service {
                 ports.sort();
+                ports.dedup();
 
                 // Build label: "ports · service_name"
                 let ports_str = ports
@@ -390,11 +508,17 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
                     .map(|p| p.to_string())
                     .collect::<Vec<_>>()
                     .join(", ");
-                let main_label = format!("{} · {}", ports_str, service_name);
-
-                // Create clickable menu item with Brew/service icon
+                // A proxy service also says where it leads
+                let process = process_by_service[service_name.as_str()];
+                let context = process_context(state, process, &process.command, None);
+                let name = proxied_name(&service_name, &context.proxy_upstreams);
+                let main_label = format!("{} · {}", ports_str, name);
+
+                // Create clickable menu item with Brew/service icon; recognized
+                // services keep their own icon with a Homebrew badge
                 let icon_type = icon_type_for_brew(&service_name);
-                let icon = get_process_icon(icon_type);
+                let badge = (icon_type != ProcessIconType::Homebrew).then_some(BadgeKind::Brew);
+                let icon = composite_icon(icon_type, badge);
                 let service_item = IconMenuItem::with_id(
                     format!("{}{}", MENU_ID_BREW_STOP_PREFIX, service_name),
                     main_label,
@@ -421,11 +545,9 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
             }
 
             // Group by PID to count unique system processes
-            let mut by_pid: BTreeMap<i32, (String, Vec<u16>)> = BTreeMap::new();
+            let mut by_pid: BTreeMap<i32, (&ProcessInfo, Vec<u16>)> = BTreeMap::new();
             for p in &system_processes {
-                let entry = by_pid
-                    .entry(p.pid)
-                    .or_insert_with(|| (p.command.clone(), Vec::new()));
+                let entry = by_pid.entry(p.pid).or_insert_with(|| (p, Vec::new()));
                 if !entry.1.contains(&p.port) {
                     entry.1.push(p.port);
                 }
@@ -437,12 +559,14 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
                 true,
             );
 
-            for (pid, (command, ports)) in &mut by_pid {
+            for (pid, (process, ports)) in &mut by_pid {
                 ports.sort();
 
                 // Try to get display name from knowledge base
-                let display_name = get_process_display_name(command, None, &state.knowledge_base)
-                    .unwrap_or_else(|| command.clone());
+                let context = process_context(state, process, &process.command, None);
+                let display_name = process_entry(kb, &context, learning)
+                    .map(|entry| entry.display_name.clone())
+                    .unwrap_or_else(|| process.command.clone());
 
                 let ports_str = ports
                     .iter()
@@ -470,6 +594,14 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
 
     menu.append(&PredefinedMenuItem::separator())?;
 
+    // While the learning worker waits out its rate limit, say when it resumes
+    if state.config.learning.enabled
+        && let Some(label) = rate_limit_label(state.learning_rate_limit.remaining())
+    {
+        let rate_limit_item = MenuItem::with_id(MENU_ID_RATE_LIMIT, label, false, None);
+        menu.append(&rate_limit_item)?;
+    }
+
     // Settings submenu
     let settings_submenu = Submenu::new("Settings", true);
 
@@ -527,6 +659,16 @@ pub fn build_menu_with_context(state: &AppState) -> Result<Menu> {
     Ok(menu)
 }
 
+/// "Identifying processes in 12s" while analysis is rate limited, rounding
+/// up so the last second isn't shown as 0s
+fn rate_limit_label(remaining: Duration) -> Option<String> {
+    if remaining.is_zero() {
+        return None;
+    }
+    let secs = remaining.as_millis().div_ceil(1000);
+    Some(format!("Identifying processes in {}s", secs))
+}
+
 pub fn process_menu_id(pid: i32, port: u16) -> String {
     format!("{}{}_{}", MENU_ID_PROCESS_PREFIX, pid, port)
 }
@@ -634,12 +776,18 @@ pub fn format_command_label(command: &str, ports: &[u16]) -> String {
     label
 }
 
-pub fn collect_targets_for_all(processes: &[ProcessInfo]) -> Vec<crate::model::KillTarget> {
+/// One kill target per process, with forked workers folded into their
+/// master (see `AppState::process_masters`) so it isn't left to respawn them
+pub fn collect_targets_for_all(
+    processes: &[ProcessInfo],
+    masters: &HashMap<i32, i32>,
+) -> Vec<crate::model::KillTarget> {
     let mut map: BTreeMap<i32, (String, Vec<u16>)> = BTreeMap::new();
 
     for process in processes {
+        let pid = masters.get(&process.pid).copied().unwrap_or(process.pid);
         let entry = map
-            .entry(process.pid)
+            .entry(pid)
             .or_insert_with(|| (process.command.clone(), Vec::new()));
         if !entry.1.contains(&process.port) {
             entry.1.push(process.port);
@@ -664,6 +812,7 @@ pub fn collect_targets_for_all(processes: &[ProcessInfo]) -> Vec<crate::model::K
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::knowledge::Protocol;
     use crate::model::MenuAction;
 
     #[test]
@@ -716,25 +865,71 @@ mod tests {
         assert_eq!(format_command_label("", &[]), "Unknown");
     }
 
+    #[test]
+    fn docker_label_shows_unhealthy_status() {
+        assert_eq!(
+            with_docker_health("API".into(), Some("unhealthy")),
+            "API (unhealthy)"
+        );
+        assert_eq!(with_docker_health("API".into(), Some("healthy")), "API");
+        assert_eq!(with_docker_health("API".into(), None), "API");
+    }
+
+    #[test]
+    fn rate_limit_label_rounds_up() {
+        assert_eq!(rate_limit_label(Duration::ZERO), None);
+        assert_eq!(
+            rate_limit_label(Duration::from_millis(200)).as_deref(),
+            Some("Identifying processes in 1s")
+        );
+        assert_eq!(
+            rate_limit_label(Duration::from_secs(12)).as_deref(),
+            Some("Identifying processes in 12s")
+        );
+    }
+
+    #[test]
+    fn rate_limit_label_hidden_with_empty_queue() {
+        // The worker publishes nothing unless a request is held back
+        let state = crate::knowledge::RateLimitState::new();
+        assert_eq!(rate_limit_label(state.remaining()), None);
+    }
+
     #[test]
     fn collect_targets_groups_by_pid() {
         let p1 = ProcessInfo {
             port: 3000,
             pid: 111,
             command: "node".into(),
+            protocol: Protocol::Tcp,
         };
         let p2 = ProcessInfo {
             port: 3001,
             pid: 111,
             command: "node".into(),
+            protocol: Protocol::Tcp,
         };
         let p3 = ProcessInfo {
             port: 5173,
             pid: 222,
             command: "vite".into(),
+            protocol: Protocol::Tcp,
         };
-        let targets = collect_targets_for_all(&[p1, p2, p3]);
-        assert_eq!(targets.len(), 2);
+        let worker = ProcessInfo {
+            port: 8000,
+            pid: 334,
+            command: "gunicorn".into(),
+            protocol: Protocol::Tcp,
+        };
+        // The gunicorn master (333) isn't listening itself
+        let masters = HashMap::from([(334, 333)]);
+        let targets = collect_targets_for_all(&[p1, p2, p3, worker], &masters);
+        assert_eq!(targets.len(), 3);
+        assert!(
+            targets
+                .iter()
+                .any(|t| t.pid == 333 && t.label.contains("8000"))
+        );
         assert!(
             targets
                 .iter()
@@ -746,4 +941,108 @@ mod tests {
                 .any(|t| t.pid == 222 && t.label.contains("5173"))
         );
     }
+
+    #[test]
+    fn menu_resolves_compose_keyed_names() {
+        use crate::knowledge::types::IcaAnalysisResponse;
+        use crate::knowledge::{
+            Confidence, FingerprintStrategy, KnowledgeSource, ProcessCategory, record_sighting,
+            store_result,
+        };
+
+        let process = ProcessInfo {
+            port: 8080,
+            pid: 4242,
+            command: "com.docker.backend".into(),
+            protocol: Protocol::Tcp,
+        };
+        let context = AnalysisContext {
+            port: Some(8080),
+            pid: Some(4242),
+            container_name: Some("shop-api-1".to_string()),
+            docker_project: Some("shop".to_string()),
+            docker_service: Some("api".to_string()),
+            ..AnalysisContext::new("com.docker.backend")
+        };
+        let mut state = AppState::default();
+        state.config.learning.fingerprint_strategy = FingerprintStrategy::ComposeService;
+        let fingerprint = ProcessFingerprint::from_config(&context, &state.config.learning);
+        assert!(fingerprint.container_prefix.is_some());
+        record_sighting(
+            &mut state.knowledge_base,
+            fingerprint.clone(),
+            context.clone(),
+            &state.config.learning,
+        );
+        let response = IcaAnalysisResponse {
+            display_name: "Shop API".to_string(),
+            description: "Storefront backend".to_string(),
+            category: ProcessCategory::Backend,
+            group_hint: None,
+            confidence: Confidence::new(0.9),
+            category_confidence: None,
+            reason: None,
+        };
+        store_result(
+            &mut state.knowledge_base,
+            fingerprint,
+            response,
+            KnowledgeSource::ApiLearned,
+        );
+
+        // The bare command alone doesn't find the entry
+        assert_eq!(
+            container_display_name(&state, &process, "shop-api-1", None),
+            None
+        );
+        state
+            .process_contexts
+            .insert((process.pid, process.port), context);
+        assert_eq!(
+            container_display_name(&state, &process, "shop-api-1", None).as_deref(),
+            Some("Shop API")
+        );
+    }
+
+    #[test]
+    fn menu_finds_entries_learned_before_the_image_digest() {
+        use crate::knowledge::types::IcaAnalysisResponse;
+        use crate::knowledge::{
+            Confidence, FingerprintStrategy, KnowledgeSource, ProcessCategory, store_result,
+        };
+
+        let mut state = AppState::default();
+        state.config.learning.fingerprint_strategy = FingerprintStrategy::ComposeService;
+        state.config.learning.fingerprint_image_digest = true;
+        let context = AnalysisContext {
+            docker_project: Some("shop".to_string()),
+            docker_service: Some("db".to_string()),
+            ..AnalysisContext::new("com.docker.backend")
+        };
+        let response = IcaAnalysisResponse {
+            display_name: "Shop Database".to_string(),
+            description: "Orders".to_string(),
+            category: ProcessCategory::Database,
+            group_hint: None,
+            confidence: Confidence::new(0.9),
+            category_confidence: None,
+            reason: None,
+        };
+        store_result(
+            &mut state.knowledge_base,
+            ProcessFingerprint::from_config(&context, &state.config.learning),
+            response,
+            KnowledgeSource::ApiLearned,
+        );
+
+        let upgraded = AnalysisContext {
+            docker_image_digest: Some("sha256:4f2a9c0b1d2e3f40".to_string()),
+            ..context
+        };
+        let entry = process_entry(&state.knowledge_base, &upgraded, &state.config.learning);
+        assert_eq!(
+            entry.map(|e| e.display_name.as_str()),
+            Some("Shop Database")
+        );
+    }
 }
@@ -4,12 +4,20 @@
 //! to help users quickly identify what's running on each port.
 
 use std::collections::HashMap;
-use std::sync::OnceLock;
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
 
-use anyhow::{Result, anyhow};
-use png::Decoder;
+use png::{Decoder, DecodingError};
 use tray_icon::menu::Icon;
 
+use crate::knowledge::{
+    AnalysisContext, KnowledgeEntry, ProcessCategory, has_token, has_token_prefix,
+    infer_category_from_command, tokenize_command, wrapped_tool,
+};
+use crate::ui::theme::CategoryTheme;
+
 // Embed all process icons at compile time
 static ICON_NODEJS: &[u8] = include_bytes!("../../assets/process-icons/generated/nodejs@2x.png");
 static ICON_PYTHON: &[u8] = include_bytes!("../../assets/process-icons/generated/python@2x.png");
@@ -22,6 +30,10 @@ static ICON_POSTGRESQL: &[u8] = include_bytes!("../../assets/process-icons/gener
 static ICON_MYSQL: &[u8] = include_bytes!("../../assets/process-icons/generated/mysql@2x.png");
 static ICON_MONGODB: &[u8] = include_bytes!("../../assets/process-icons/generated/mongodb@2x.png");
 static ICON_REDIS: &[u8] = include_bytes!("../../assets/process-icons/generated/redis@2x.png");
+static ICON_CLICKHOUSE: &[u8] = include_bytes!("../../assets/process-icons/generated/clickhouse@2x.png");
+static ICON_QDRANT: &[u8] = include_bytes!("../../assets/process-icons/generated/qdrant@2x.png");
+static ICON_MEILISEARCH: &[u8] = include_bytes!("../../assets/process-icons/generated/meilisearch@2x.png");
+static ICON_TYPESENSE: &[u8] = include_bytes!("../../assets/process-icons/generated/typesense@2x.png");
 static ICON_DOCKER: &[u8] = include_bytes!("../../assets/process-icons/generated/docker@2x.png");
 static ICON_HOMEBREW: &[u8] = include_bytes!("../../assets/process-icons/generated/homebrew@2x.png");
 static ICON_GENERIC: &[u8] = include_bytes!("../../assets/process-icons/generated/generic@2x.png");
@@ -40,113 +52,326 @@ pub enum ProcessIconType {
     MySQL,
     MongoDB,
     Redis,
+    ClickHouse,
+    Qdrant,
+    Meilisearch,
+    Typesense,
     Docker,
     Homebrew,
     Generic,
 }
 
+/// Small marker drawn over the bottom-right corner of a process icon to show
+/// how the process is managed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BadgeKind {
+    Docker,
+    Brew,
+}
+
+impl BadgeKind {
+    /// Icon the badge is drawn from, at half size
+    fn icon_type(self) -> ProcessIconType {
+        match self {
+            Self::Docker => ProcessIconType::Docker,
+            Self::Brew => ProcessIconType::Homebrew,
+        }
+    }
+}
+
+/// Why an icon could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IconError {
+    /// Valid PNG in a color type we don't convert (e.g. indexed)
+    UnsupportedColorType(png::ColorType),
+    /// Malformed or truncated PNG data
+    Decode(String),
+    /// Reading the PNG data failed
+    Io(String),
+}
+
+impl fmt::Display for IconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedColorType(color_type) => {
+                write!(f, "unsupported PNG color type: {:?}", color_type)
+            }
+            Self::Decode(msg) => write!(f, "failed to decode PNG: {}", msg),
+            Self::Io(msg) => write!(f, "failed to read PNG: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for IconError {}
+
+impl From<DecodingError> for IconError {
+    fn from(err: DecodingError) -> Self {
+        match err {
+            DecodingError::IoError(e) => Self::Io(e.to_string()),
+            other => Self::Decode(other.to_string()),
+        }
+    }
+}
+
 /// Cached decoded icon data
+#[derive(Clone)]
 struct CachedIconData {
     rgba: Vec<u8>,
     width: u32,
     height: u32,
 }
 
+/// Decoded icons, deduplicated by PNG content so identical bytes decode once
+struct IconCache {
+    /// Icon type -> content hash of its PNG data
+    types: HashMap<ProcessIconType, u64>,
+    /// Content hash -> decoded icon data
+    decoded: HashMap<u64, CachedIconData>,
+}
+
+impl IconCache {
+    fn build(icons: &[(ProcessIconType, &[u8])]) -> Self {
+        let mut types = HashMap::new();
+        let mut decoded = HashMap::new();
+
+        for &(icon_type, data) in icons {
+            let hash = content_hash(data);
+            if let Entry::Vacant(slot) = decoded.entry(hash) {
+                match decode_png_to_rgba(data) {
+                    Ok(cached) => {
+                        slot.insert(cached);
+                    }
+                    Err(err @ IconError::UnsupportedColorType(_)) => {
+                        // Falls back to the generic icon
+                        log::warn!("Skipping {:?} icon: {}", icon_type, err);
+                        continue;
+                    }
+                    Err(err) => {
+                        log::error!("Bad {:?} icon data: {}", icon_type, err);
+                        continue;
+                    }
+                }
+            }
+            types.insert(icon_type, hash);
+        }
+
+        Self { types, decoded }
+    }
+
+    fn get(&self, icon_type: ProcessIconType) -> Option<&CachedIconData> {
+        self.types
+            .get(&icon_type)
+            .and_then(|hash| self.decoded.get(hash))
+    }
+}
+
 /// Global icon cache
-static ICON_CACHE: OnceLock<HashMap<ProcessIconType, CachedIconData>> = OnceLock::new();
+static ICON_CACHE: OnceLock<IconCache> = OnceLock::new();
+
+/// Badged icons, composited once per base and badge
+static BADGED_ICON_CACHE: OnceLock<Mutex<HashMap<(ProcessIconType, BadgeKind), CachedIconData>>> =
+    OnceLock::new();
+
+/// Generic icons tinted per color, so theme changes need no invalidation
+static TINTED_ICON_CACHE: OnceLock<Mutex<HashMap<[u8; 3], CachedIconData>>> = OnceLock::new();
+
+/// Commands remembered by [`classify_command`] before the cache starts over
+const COMMAND_CACHE_CAPACITY: usize = 256;
+
+/// Memoized command classification
+static COMMAND_CACHE: OnceLock<Mutex<CommandCache>> = OnceLock::new();
+
+/// Bounded map of command -> (icon type, category). A menu refresh asks about
+/// the same few commands once per port, so when full it is simply cleared.
+struct CommandCache {
+    capacity: usize,
+    entries: HashMap<String, (ProcessIconType, ProcessCategory)>,
+}
+
+impl CommandCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get_or_classify(
+        &mut self,
+        command: &str,
+        classify: impl FnOnce(&str) -> (ProcessIconType, ProcessCategory),
+    ) -> (ProcessIconType, ProcessCategory) {
+        if let Some(cached) = self.entries.get(command) {
+            return cached.clone();
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.clear();
+        }
+        let classified = classify(command);
+        self.entries.insert(command.to_string(), classified.clone());
+        classified
+    }
+}
 
-/// Determine icon type from command name with fuzzy matching
+/// Icon type and inferred category for a command, memoized
+pub fn classify_command(command: &str) -> (ProcessIconType, ProcessCategory) {
+    let classify = |command: &str| {
+        (
+            scan_icon_type(command),
+            infer_category_from_command(command),
+        )
+    };
+    let cache = COMMAND_CACHE.get_or_init(|| Mutex::new(CommandCache::new(COMMAND_CACHE_CAPACITY)));
+    match cache.lock() {
+        Ok(mut cache) => cache.get_or_classify(command, classify),
+        Err(_) => classify(command),
+    }
+}
+
+/// Forget memoized classifications, e.g. after the icon mappings change
+pub fn clear_command_cache() {
+    if let Some(cache) = COMMAND_CACHE.get()
+        && let Ok(mut cache) = cache.lock()
+    {
+        cache.entries.clear();
+    }
+}
+
+/// Determine icon type from command name by matching whole command tokens
 pub fn icon_type_from_command(command: &str) -> ProcessIconType {
-    let cmd_lower = command.to_lowercase();
+    classify_command(command).0
+}
+
+fn scan_icon_type(command: &str) -> ProcessIconType {
+    let tokens = tokenize_command(command);
 
     // Node.js variants
-    if cmd_lower.contains("node")
-        || cmd_lower.contains("npm")
-        || cmd_lower.contains("yarn")
-        || cmd_lower.contains("pnpm")
-        || cmd_lower.contains("bun")
-        || cmd_lower.contains("deno")
-        || cmd_lower.contains("vite")
-        || cmd_lower.contains("next")
-        || cmd_lower.contains("nuxt")
-        || cmd_lower.contains("esbuild")
-        || cmd_lower.contains("webpack")
-        || cmd_lower.contains("rollup")
+    if has_token_prefix(
+        &tokens,
+        &[
+            "node", "npm", "yarn", "pnpm", "deno", "vite", "nuxt", "esbuild", "webpack", "rollup",
+        ],
+    ) || has_token(&tokens, &["bun", "next"])
     {
         return ProcessIconType::NodeJs;
     }
 
     // Python variants
-    if cmd_lower.contains("python")
-        || cmd_lower.contains("uvicorn")
-        || cmd_lower.contains("gunicorn")
-        || cmd_lower.contains("flask")
-        || cmd_lower.contains("django")
-        || cmd_lower.contains("celery")
-        || cmd_lower.contains("fastapi")
-        || cmd_lower.contains("hypercorn")
-    {
+    if has_token_prefix(
+        &tokens,
+        &[
+            "python",
+            "uvicorn",
+            "gunicorn",
+            "flask",
+            "django",
+            "celery",
+            "fastapi",
+            "hypercorn",
+        ],
+    ) {
         return ProcessIconType::Python;
     }
 
     // Ruby variants
-    if cmd_lower.contains("ruby")
-        || cmd_lower.contains("rails")
-        || cmd_lower.contains("puma")
-        || cmd_lower.contains("unicorn")
-        || cmd_lower.contains("sidekiq")
-        || cmd_lower.contains("resque")
-    {
+    if has_token_prefix(
+        &tokens,
+        &["ruby", "rails", "puma", "unicorn", "sidekiq", "resque"],
+    ) {
         return ProcessIconType::Ruby;
     }
 
     // Go (be careful with short names)
-    if cmd_lower == "go" || cmd_lower.starts_with("go ") || cmd_lower.contains("golang") {
+    if has_token(&tokens, &["go"]) || has_token_prefix(&tokens, &["golang"]) {
         return ProcessIconType::Go;
     }
 
     // Rust
-    if cmd_lower.contains("cargo") || cmd_lower.contains("rustc") {
+    if has_token_prefix(&tokens, &["cargo", "rustc"]) {
         return ProcessIconType::Rust;
     }
 
     // Java variants
-    if cmd_lower.contains("java")
-        || cmd_lower.contains("gradle")
-        || cmd_lower.contains("maven")
-        || cmd_lower.contains("kotlin")
-        || cmd_lower.contains("spring")
-        || cmd_lower.contains("tomcat")
-    {
+    if has_token_prefix(
+        &tokens,
+        &["java", "gradle", "maven", "kotlin", "spring", "tomcat"],
+    ) {
         return ProcessIconType::Java;
     }
 
     // PHP variants
-    if cmd_lower.contains("php")
-        || cmd_lower.contains("artisan")
-        || cmd_lower.contains("composer")
-        || cmd_lower.contains("laravel")
-    {
+    if has_token_prefix(&tokens, &["php", "artisan", "composer", "laravel"]) {
         return ProcessIconType::Php;
     }
 
     // Databases
-    if cmd_lower.contains("postgres") {
+    if has_token_prefix(&tokens, &["postgres"]) {
         return ProcessIconType::PostgreSQL;
     }
-    if cmd_lower.contains("mysql") || cmd_lower.contains("mariadb") {
+    if has_token_prefix(&tokens, &["mysql", "mariadb"]) {
         return ProcessIconType::MySQL;
     }
-    if cmd_lower.contains("mongo") {
+    if has_token_prefix(&tokens, &["mongo"]) {
         return ProcessIconType::MongoDB;
     }
-    if cmd_lower.contains("redis") {
+    if has_token_prefix(&tokens, &["redis"]) {
         return ProcessIconType::Redis;
     }
+    if has_token_prefix(&tokens, &["clickhouse"]) {
+        return ProcessIconType::ClickHouse;
+    }
+    if has_token_prefix(&tokens, &["qdrant"]) {
+        return ProcessIconType::Qdrant;
+    }
+
+    // Search engines
+    if has_token_prefix(&tokens, &["meilisearch"]) {
+        return ProcessIconType::Meilisearch;
+    }
+    if has_token_prefix(&tokens, &["typesense"]) {
+        return ProcessIconType::Typesense;
+    }
 
     ProcessIconType::Generic
 }
 
+/// Determine icon type from an analysis context, falling back to the
+/// detected framework when the command alone is not recognized
+pub fn icon_type_from_context(context: &AnalysisContext) -> ProcessIconType {
+    if let Some(ref service) = context.brew_service {
+        return icon_type_for_brew(service);
+    }
+
+    // An interpreter running a recognized tool shows the tool's icon
+    if let Some(tool) = wrapped_tool(context) {
+        let icon_type = icon_type_from_command(&tool);
+        if icon_type != ProcessIconType::Generic {
+            return icon_type;
+        }
+    }
+
+    let icon_type = icon_type_from_command(&context.command);
+    if icon_type != ProcessIconType::Generic {
+        return icon_type;
+    }
+
+    match context.detected_framework.as_deref() {
+        Some("rust") => ProcessIconType::Rust,
+        _ => ProcessIconType::Generic,
+    }
+}
+
+/// Determine icon type for a knowledge entry from its command, then its
+/// display name (e.g. "MySQL (shop)"). Entries recognized by category alone
+/// (a custom `db-gateway` categorized as a database) stay generic, so they
+/// get the category-tinted icon rather than some other product's logo.
+pub fn icon_type_for_entry(entry: &KnowledgeEntry) -> ProcessIconType {
+    match icon_type_from_command(&entry.fingerprint.command) {
+        ProcessIconType::Generic => icon_type_from_command(&entry.display_name),
+        icon_type => icon_type,
+    }
+}
+
 /// Get icon type for Docker containers (always Docker whale)
 pub fn icon_type_for_docker() -> ProcessIconType {
     ProcessIconType::Docker
@@ -166,17 +391,64 @@ pub fn icon_type_for_brew(service_name: &str) -> ProcessIconType {
     } else if svc_lower.contains("redis") {
         ProcessIconType::Redis
     } else {
-        ProcessIconType::Homebrew
+        // Language runtimes and tools (e.g. "php@8.2") get their own icon
+        match icon_type_from_command(svc_lower.split('@').next().unwrap_or_default()) {
+            ProcessIconType::Generic => ProcessIconType::Homebrew,
+            icon_type => icon_type,
+        }
     }
 }
 
 /// Get a menu Icon for the given ProcessIconType
 /// Returns None if icon loading fails (graceful degradation)
 pub fn get_process_icon(icon_type: ProcessIconType) -> Option<Icon> {
-    let cache = ICON_CACHE.get_or_init(|| {
-        let mut map = HashMap::new();
+    let cached = icon_data(icon_type)?;
+    Icon::from_rgba(cached.rgba.clone(), cached.width, cached.height)
+        .map_err(|e| log::warn!("Failed to create {:?} icon: {}", icon_type, e))
+        .ok()
+}
+
+/// Icon for `base` with `badge` composited into its bottom-right quadrant.
+/// Without a badge (or if the badge can't be loaded) this is the plain icon.
+pub fn composite_icon(base: ProcessIconType, badge: Option<BadgeKind>) -> Option<Icon> {
+    let Some(badge) = badge else {
+        return get_process_icon(base);
+    };
+    let Some(badge_data) = icon_data(badge.icon_type()) else {
+        return get_process_icon(base);
+    };
+    let base_data = icon_data(base)?;
+
+    let cache = BADGED_ICON_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cache = cache.lock().ok()?;
+    let composited = cache
+        .entry((base, badge))
+        .or_insert_with(|| composite_badge(base_data, &downscale_half(badge_data)));
+    Icon::from_rgba(composited.rgba.clone(), composited.width, composited.height)
+        .map_err(|e| log::warn!("Failed to create badged {:?} icon: {}", base, e))
+        .ok()
+}
+
+/// Generic icon tinted with the theme's color for `category`, for processes
+/// without an icon of their own
+pub fn get_category_icon(category: &ProcessCategory, theme: &CategoryTheme) -> Option<Icon> {
+    let color = theme.rgb(category);
+    let generic = icon_data(ProcessIconType::Generic)?;
+
+    let cache = TINTED_ICON_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cache = cache.lock().ok()?;
+    let tinted = cache
+        .entry(color)
+        .or_insert_with(|| tint_icon(generic, color));
+    Icon::from_rgba(tinted.rgba.clone(), tinted.width, tinted.height)
+        .map_err(|e| log::warn!("Failed to create {:?} category icon: {}", category, e))
+        .ok()
+}
 
-        let icons: [(ProcessIconType, &[u8]); 14] = [
+/// Decoded icon data for a type, falling back to the generic icon
+fn icon_data(icon_type: ProcessIconType) -> Option<&'static CachedIconData> {
+    let cache = ICON_CACHE.get_or_init(|| {
+        IconCache::build(&[
             (ProcessIconType::NodeJs, ICON_NODEJS),
             (ProcessIconType::Python, ICON_PYTHON),
             (ProcessIconType::Ruby, ICON_RUBY),
@@ -188,36 +460,100 @@ pub fn get_process_icon(icon_type: ProcessIconType) -> Option<Icon> {
             (ProcessIconType::MySQL, ICON_MYSQL),
             (ProcessIconType::MongoDB, ICON_MONGODB),
             (ProcessIconType::Redis, ICON_REDIS),
+            (ProcessIconType::ClickHouse, ICON_CLICKHOUSE),
+            (ProcessIconType::Qdrant, ICON_QDRANT),
+            (ProcessIconType::Meilisearch, ICON_MEILISEARCH),
+            (ProcessIconType::Typesense, ICON_TYPESENSE),
             (ProcessIconType::Docker, ICON_DOCKER),
             (ProcessIconType::Homebrew, ICON_HOMEBREW),
             (ProcessIconType::Generic, ICON_GENERIC),
-        ];
+        ])
+    });
+
+    cache
+        .get(icon_type)
+        .or_else(|| cache.get(ProcessIconType::Generic))
+}
 
-        for (icon_type, data) in icons {
-            if let Ok(cached) = decode_png_to_rgba(data) {
-                map.insert(icon_type, cached);
+/// Halve an icon's size, averaging each 2x2 block weighted by alpha
+fn downscale_half(icon: &CachedIconData) -> CachedIconData {
+    let (width, height) = (icon.width / 2, icon.height / 2);
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sums = [0u32; 4];
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let i = (((y * 2 + dy) * icon.width + x * 2 + dx) * 4) as usize;
+                let alpha = u32::from(icon.rgba[i + 3]);
+                for (sum, &channel) in sums.iter_mut().zip(&icon.rgba[i..i + 3]) {
+                    *sum += u32::from(channel) * alpha;
+                }
+                sums[3] += alpha;
             }
+            let alpha = sums[3];
+            for sum in &sums[..3] {
+                rgba.push(sum.checked_div(alpha).unwrap_or(0) as u8);
+            }
+            rgba.push((alpha / 4) as u8);
         }
+    }
+    CachedIconData {
+        rgba,
+        width,
+        height,
+    }
+}
 
-        map
-    });
+/// Recolor a single-color glyph, keeping its shape (alpha) untouched
+fn tint_icon(icon: &CachedIconData, color: [u8; 3]) -> CachedIconData {
+    let mut out = icon.clone();
+    for pixel in out.rgba.chunks_exact_mut(4) {
+        pixel[..3].copy_from_slice(&color);
+    }
+    out
+}
 
-    cache.get(&icon_type).and_then(|cached| {
-        Icon::from_rgba(cached.rgba.clone(), cached.width, cached.height).ok()
-    })
+/// Draw `badge` over the bottom-right corner of `base` ("source over")
+fn composite_badge(base: &CachedIconData, badge: &CachedIconData) -> CachedIconData {
+    let mut out = base.clone();
+    let left = base.width.saturating_sub(badge.width);
+    let top = base.height.saturating_sub(badge.height);
+    for y in 0..badge.height.min(base.height) {
+        for x in 0..badge.width.min(base.width) {
+            let src = ((y * badge.width + x) * 4) as usize;
+            let dst = (((top + y) * base.width + left + x) * 4) as usize;
+            let src_alpha = f32::from(badge.rgba[src + 3]) / 255.0;
+            if src_alpha == 0.0 {
+                continue;
+            }
+            let dst_alpha = f32::from(out.rgba[dst + 3]) / 255.0;
+            let alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+            for c in 0..3 {
+                let color = (f32::from(badge.rgba[src + c]) * src_alpha
+                    + f32::from(out.rgba[dst + c]) * dst_alpha * (1.0 - src_alpha))
+                    / alpha;
+                out.rgba[dst + c] = color.round() as u8;
+            }
+            out.rgba[dst + 3] = (alpha * 255.0).round() as u8;
+        }
+    }
+    out
+}
+
+/// Hash PNG bytes to identify identical icon content
+fn content_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
 }
 
 /// Decode PNG data to RGBA format
-fn decode_png_to_rgba(png_data: &[u8]) -> Result<CachedIconData> {
+fn decode_png_to_rgba(png_data: &[u8]) -> Result<CachedIconData, IconError> {
     let decoder = Decoder::new(png_data);
-    let mut reader = decoder
-        .read_info()
-        .map_err(|e| anyhow!("failed to read PNG header: {e}"))?;
+    let mut reader = decoder.read_info()?;
 
     let mut buf = vec![0; reader.output_buffer_size()];
-    let info = reader
-        .next_frame(&mut buf)
-        .map_err(|e| anyhow!("failed to decode PNG: {e}"))?;
+    let info = reader.next_frame(&mut buf)?;
 
     let width = info.width;
     let height = info.height;
@@ -250,7 +586,7 @@ fn decode_png_to_rgba(png_data: &[u8]) -> Result<CachedIconData> {
             rgba
         }
         png::ColorType::Indexed => {
-            return Err(anyhow!("indexed PNG not supported"));
+            return Err(IconError::UnsupportedColorType(info.color_type));
         }
     };
 
@@ -292,17 +628,327 @@ mod tests {
         assert_eq!(icon_type_from_command("mysqld"), ProcessIconType::MySQL);
     }
 
+    #[test]
+    fn test_search_and_analytics_stores() {
+        use ProcessCategory::{Database, Search};
+        let cases = [
+            ("meilisearch", ProcessIconType::Meilisearch, Search),
+            ("typesense-server", ProcessIconType::Typesense, Search),
+            ("qdrant", ProcessIconType::Qdrant, Database),
+            ("clickhouse", ProcessIconType::ClickHouse, Database),
+            ("clickhouse-server", ProcessIconType::ClickHouse, Database),
+        ];
+        let generic = icon_data(ProcessIconType::Generic).unwrap();
+        for (command, icon_type, category) in cases {
+            assert_eq!(icon_type_from_command(command), icon_type);
+            assert_eq!(infer_category_from_command(command), category);
+            // Each has its own artwork rather than the generic fallback
+            assert!(!std::ptr::eq(icon_data(icon_type).unwrap(), generic));
+        }
+        assert_eq!(
+            icon_type_for_entry(&entry("docs-index", Search)),
+            ProcessIconType::Generic
+        );
+        assert_eq!(
+            icon_type_for_brew("meilisearch"),
+            ProcessIconType::Meilisearch
+        );
+    }
+
     #[test]
     fn test_fallback() {
         assert_eq!(icon_type_from_command("unknown-app"), ProcessIconType::Generic);
         assert_eq!(icon_type_from_command("my-custom-server"), ProcessIconType::Generic);
     }
 
+    #[test]
+    fn test_matches_whole_tokens() {
+        assert_eq!(
+            icon_type_from_command("offspring"),
+            ProcessIconType::Generic
+        );
+        assert_eq!(icon_type_from_command("spring-boot"), ProcessIconType::Java);
+        assert_eq!(icon_type_from_command("cargo-watch"), ProcessIconType::Rust);
+        assert_eq!(
+            icon_type_from_command("/usr/local/bin/go"),
+            ProcessIconType::Go
+        );
+        assert_eq!(icon_type_from_command("mongosh"), ProcessIconType::MongoDB);
+    }
+
+    #[test]
+    fn test_context_uses_detected_framework() {
+        let rust_binary = AnalysisContext {
+            command: "myapp".to_string(),
+            detected_framework: Some("rust".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(icon_type_from_context(&rust_binary), ProcessIconType::Rust);
+
+        // A recognized command wins over the project framework
+        let node_in_rust_repo = AnalysisContext {
+            command: "node".to_string(),
+            ..rust_binary
+        };
+        assert_eq!(
+            icon_type_from_context(&node_in_rust_repo),
+            ProcessIconType::NodeJs
+        );
+    }
+
+    #[test]
+    fn test_context_uses_wrapped_tool() {
+        let celery = AnalysisContext {
+            full_command: Some("/usr/bin/python3 -m celery worker".to_string()),
+            ..AnalysisContext::new("python3")
+        };
+        assert_eq!(icon_type_from_context(&celery), ProcessIconType::Python);
+
+        let next = AnalysisContext {
+            full_command: Some("node node_modules/.bin/next dev".to_string()),
+            ..AnalysisContext::new("node")
+        };
+        assert_eq!(icon_type_from_context(&next), ProcessIconType::NodeJs);
+
+        // Only the wrapped tool names the service: a path-qualified script
+        // after interpreter flags, under a versioned interpreter
+        let redis_proxy = AnalysisContext {
+            full_command: Some(
+                "/opt/homebrew/bin/python3.11 -X dev /srv/shop/tools/redis_proxy.py --port 6380"
+                    .to_string(),
+            ),
+            ..AnalysisContext::new("python3.11")
+        };
+        assert_eq!(
+            icon_type_from_command("python3.11"),
+            ProcessIconType::Python
+        );
+        assert_eq!(icon_type_from_context(&redis_proxy), ProcessIconType::Redis);
+
+        // The tool's icon wins over the interpreter's
+        let mongo_shell = AnalysisContext {
+            full_command: Some("node /opt/tools/mongosh.js".to_string()),
+            ..AnalysisContext::new("node")
+        };
+        assert_eq!(
+            icon_type_from_context(&mongo_shell),
+            ProcessIconType::MongoDB
+        );
+    }
+
+    #[test]
+    fn test_classify_command_is_consistent() {
+        for command in ["node", "postgres", "my-custom-server", "node"] {
+            let expected = (
+                scan_icon_type(command),
+                infer_category_from_command(command),
+            );
+            assert_eq!(classify_command(command), expected);
+            assert_eq!(classify_command(command), expected);
+        }
+    }
+
+    #[test]
+    fn test_command_cache_hit_on_second_call() {
+        let mut cache = CommandCache::new(2);
+        let mut scans = 0;
+        let mut lookup = |cache: &mut CommandCache, command: &str| {
+            cache.get_or_classify(command, |command| {
+                scans += 1;
+                (scan_icon_type(command), ProcessCategory::Unknown)
+            })
+        };
+
+        assert_eq!(lookup(&mut cache, "node").0, ProcessIconType::NodeJs);
+        assert_eq!(lookup(&mut cache, "node").0, ProcessIconType::NodeJs);
+        lookup(&mut cache, "redis-server");
+        // Full: the next new command starts the cache over
+        lookup(&mut cache, "python");
+        lookup(&mut cache, "python");
+        lookup(&mut cache, "node");
+        assert_eq!(scans, 4);
+    }
+
+    fn solid(width: u32, height: u32, pixel: [u8; 4]) -> CachedIconData {
+        CachedIconData {
+            rgba: pixel.repeat((width * height) as usize),
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_custom_theme_color_tints_icon() {
+        let theme = CategoryTheme {
+            database: "#FF8800".to_string(),
+            ..Default::default()
+        };
+        let generic = icon_data(ProcessIconType::Generic).unwrap();
+        let tinted = tint_icon(generic, theme.rgb(&ProcessCategory::Database));
+        assert_eq!(
+            (tinted.width, tinted.height),
+            (generic.width, generic.height)
+        );
+        let mut visible = 0;
+        for (out, original) in tinted.rgba.chunks(4).zip(generic.rgba.chunks(4)) {
+            assert_eq!(out[..3], [0xFF, 0x88, 0x00]);
+            assert_eq!(out[3], original[3]);
+            visible += usize::from(out[3] > 0);
+        }
+        assert!(visible > 0);
+        assert!(get_category_icon(&ProcessCategory::Database, &theme).is_some());
+    }
+
+    #[test]
+    fn test_badge_changes_only_bottom_right_quadrant() {
+        let base = icon_data(ProcessIconType::Python).unwrap();
+        let badge = downscale_half(icon_data(BadgeKind::Docker.icon_type()).unwrap());
+        assert_eq!(
+            (badge.width, badge.height),
+            (base.width / 2, base.height / 2)
+        );
+
+        let badged = composite_badge(base, &badge);
+        let mut badge_changed = false;
+        for y in 0..base.height {
+            for x in 0..base.width {
+                let i = ((y * base.width + x) * 4) as usize;
+                let in_badge = x >= base.width / 2 && y >= base.height / 2;
+                if in_badge {
+                    badge_changed |= badged.rgba[i..i + 4] != base.rgba[i..i + 4];
+                } else {
+                    assert_eq!(badged.rgba[i..i + 4], base.rgba[i..i + 4], "({}, {})", x, y);
+                }
+            }
+        }
+        assert!(badge_changed);
+    }
+
+    #[test]
+    fn test_composite_blends_by_alpha() {
+        let base = solid(4, 4, [255, 0, 0, 255]);
+        let mut badge = solid(2, 2, [0, 0, 255, 255]);
+        // One transparent and one half-transparent badge pixel
+        badge.rgba[3] = 0;
+        badge.rgba[4..8].copy_from_slice(&[0, 0, 255, 128]);
+
+        let out = composite_badge(&base, &badge);
+        let pixel = |x: u32, y: u32| {
+            let i = ((y * 4 + x) * 4) as usize;
+            out.rgba[i..i + 4].to_vec()
+        };
+        assert_eq!(pixel(2, 2), vec![255, 0, 0, 255]);
+        assert_eq!(pixel(3, 2), vec![127, 0, 128, 255]);
+        assert_eq!(pixel(3, 3), vec![0, 0, 255, 255]);
+        assert_eq!(pixel(1, 1), vec![255, 0, 0, 255]);
+    }
+
+    fn entry(command: &str, category: ProcessCategory) -> KnowledgeEntry {
+        KnowledgeEntry {
+            fingerprint: crate::knowledge::ProcessFingerprint::new(command),
+            display_name: command.to_string(),
+            description: String::new(),
+            category,
+            group_id: None,
+            confidence: crate::knowledge::Confidence::new(0.9),
+            source: crate::knowledge::KnowledgeSource::ApiLearned,
+            sightings: 1,
+            updated_at: 0,
+            first_seen: 0,
+            last_seen: 0,
+            reason: None,
+            project_dir: None,
+            category_confidence: None,
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn test_entry_category_fallback() {
+        // A category alone gets the neutral (category-tinted) icon, not
+        // another product's logo
+        assert_eq!(
+            icon_type_for_entry(&entry("db-gateway", ProcessCategory::Database)),
+            ProcessIconType::Generic
+        );
+        assert_eq!(
+            icon_type_for_entry(&entry("sessionstore", ProcessCategory::Cache)),
+            ProcessIconType::Generic
+        );
+        // A recognized command or display name keeps its logo
+        assert_eq!(
+            icon_type_for_entry(&entry("mysqld", ProcessCategory::Database)),
+            ProcessIconType::MySQL
+        );
+        let named = KnowledgeEntry {
+            display_name: "Redis (sessions)".to_string(),
+            ..entry("sessionstore", ProcessCategory::Cache)
+        };
+        assert_eq!(icon_type_for_entry(&named), ProcessIconType::Redis);
+    }
+
+    #[test]
+    fn test_identical_icons_decode_once() {
+        let cache = IconCache::build(&[
+            (ProcessIconType::Generic, ICON_GENERIC),
+            (ProcessIconType::Homebrew, ICON_GENERIC),
+            (ProcessIconType::Docker, ICON_DOCKER),
+        ]);
+
+        assert_eq!(cache.decoded.len(), 2);
+        assert!(std::ptr::eq(
+            cache.get(ProcessIconType::Generic).unwrap(),
+            cache.get(ProcessIconType::Homebrew).unwrap()
+        ));
+        assert!(cache.get(ProcessIconType::Docker).is_some());
+        assert!(cache.get(ProcessIconType::Redis).is_none());
+    }
+
+    fn encode_indexed_png() -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut data, 1, 1);
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_palette(vec![255, 0, 0]);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0]).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn test_indexed_png_is_unsupported_color_type() {
+        assert_eq!(
+            decode_png_to_rgba(&encode_indexed_png()).err(),
+            Some(IconError::UnsupportedColorType(png::ColorType::Indexed))
+        );
+    }
+
+    #[test]
+    fn test_truncated_png_is_not_color_type_error() {
+        let truncated = &ICON_GENERIC[..ICON_GENERIC.len() / 2];
+        assert!(matches!(
+            decode_png_to_rgba(truncated),
+            Err(IconError::Decode(_) | IconError::Io(_))
+        ));
+    }
+
     #[test]
     fn test_brew_service_mapping() {
         assert_eq!(icon_type_for_brew("postgresql"), ProcessIconType::PostgreSQL);
         assert_eq!(icon_type_for_brew("postgresql@14"), ProcessIconType::PostgreSQL);
         assert_eq!(icon_type_for_brew("redis"), ProcessIconType::Redis);
         assert_eq!(icon_type_for_brew("nginx"), ProcessIconType::Homebrew);
+        assert_eq!(icon_type_for_brew("php@8.2"), ProcessIconType::Php);
+
+        let context = AnalysisContext {
+            brew_service: Some("postgresql@14".to_string()),
+            ..AnalysisContext::new("postmaster")
+        };
+        assert_eq!(
+            icon_type_from_context(&context),
+            ProcessIconType::PostgreSQL
+        );
     }
 }
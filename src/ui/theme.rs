@@ -0,0 +1,143 @@
+//! Category colors used to tint icons and category badges in the menu.
+//!
+//! Colors are `#RRGGBB` (or shorthand `#RGB`) hex strings, kept as written
+//! so the config file round-trips unchanged; they are validated when the
+//! config is loaded.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::knowledge::ProcessCategory;
+
+/// Color for each process category
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CategoryTheme {
+    pub frontend: String,
+    pub backend: String,
+    pub database: String,
+    pub cache: String,
+    pub search: String,
+    pub proxy: String,
+    pub dev_tool: String,
+    pub infrastructure: String,
+    pub unknown: String,
+}
+
+impl Default for CategoryTheme {
+    fn default() -> Self {
+        // macOS system colors, which read well in both light and dark menus
+        Self {
+            frontend: "#007AFF".to_string(),
+            backend: "#34C759".to_string(),
+            database: "#AF52DE".to_string(),
+            cache: "#FF3B30".to_string(),
+            search: "#FF2D55".to_string(),
+            proxy: "#FF9500".to_string(),
+            dev_tool: "#5AC8FA".to_string(),
+            infrastructure: "#A2845E".to_string(),
+            unknown: "#8E8E93".to_string(),
+        }
+    }
+}
+
+impl CategoryTheme {
+    /// Hex color configured for `category`
+    pub fn color(&self, category: &ProcessCategory) -> &str {
+        match category {
+            ProcessCategory::Frontend => &self.frontend,
+            ProcessCategory::Backend => &self.backend,
+            ProcessCategory::Database => &self.database,
+            ProcessCategory::Cache => &self.cache,
+            ProcessCategory::Search => &self.search,
+            ProcessCategory::Proxy => &self.proxy,
+            ProcessCategory::DevTool => &self.dev_tool,
+            ProcessCategory::Infrastructure => &self.infrastructure,
+            ProcessCategory::Unknown => &self.unknown,
+        }
+    }
+
+    /// RGB color for `category`; invalid colors (only possible if the theme
+    /// was never validated) fall back to the default theme's
+    pub fn rgb(&self, category: &ProcessCategory) -> [u8; 3] {
+        parse_hex_color(self.color(category))
+            .or_else(|| parse_hex_color(CategoryTheme::default().color(category)))
+            .unwrap_or([0x80, 0x80, 0x80])
+    }
+
+    /// Check every color is a valid hex color
+    pub fn validate(&self) -> Result<()> {
+        for category in ProcessCategory::all() {
+            let color = self.color(category);
+            if parse_hex_color(color).is_none() {
+                bail!(
+                    "invalid {} theme color {:?} (expected #RRGGBB)",
+                    category,
+                    color
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse `#RRGGBB` or `#RGB` into RGB components
+pub fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.trim().strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match hex.len() {
+        6 => Some([
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        ]),
+        // Shorthand: each digit is doubled ("#f80" is "#ff8800")
+        3 => {
+            let mut rgb = [0; 3];
+            for (out, digit) in rgb.iter_mut().zip(hex.chars()) {
+                *out = channel(&digit.to_string())? * 0x11;
+            }
+            Some(rgb)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#FF8800"), Some([0xFF, 0x88, 0x00]));
+        assert_eq!(parse_hex_color("#ff8800"), Some([0xFF, 0x88, 0x00]));
+        assert_eq!(parse_hex_color("#f80"), Some([0xFF, 0x88, 0x00]));
+        assert_eq!(parse_hex_color("FF8800"), None);
+        assert_eq!(parse_hex_color("#FF880"), None);
+        assert_eq!(parse_hex_color("#GG8800"), None);
+        assert_eq!(parse_hex_color("#+F+F+F"), None);
+        assert_eq!(parse_hex_color("#ÿÿ"), None);
+    }
+
+    #[test]
+    fn test_theme_validation() {
+        assert!(CategoryTheme::default().validate().is_ok());
+
+        let theme: CategoryTheme =
+            serde_json::from_str(r##"{"database": "#123", "dev_tool": "#abcdef"}"##).unwrap();
+        assert!(theme.validate().is_ok());
+        assert_eq!(theme.rgb(&ProcessCategory::Database), [0x11, 0x22, 0x33]);
+        // Unset categories keep their defaults
+        assert_eq!(theme.frontend, CategoryTheme::default().frontend);
+
+        let theme = CategoryTheme {
+            cache: "red".to_string(),
+            ..Default::default()
+        };
+        let err = theme.validate().unwrap_err();
+        assert!(err.to_string().contains("Cache"), "{}", err);
+    }
+}
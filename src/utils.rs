@@ -1,5 +1,9 @@
+use std::io::{self, Read};
 use std::path::Path;
+use std::process::{Command, Output, Stdio};
 use std::sync::OnceLock;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 /// Find an executable in common Homebrew locations, falling back to PATH.
 /// Results are cached for efficiency.
@@ -38,3 +42,67 @@ fn find_in_paths(name: &str, prefix_paths: &[&str]) -> &'static str {
     // Fallback to PATH lookup
     Box::leak(name.to_string().into_boxed_str())
 }
+
+/// Run a command to completion like [`Command::output`], killing it if it
+/// hasn't exited within `timeout`. Returns `Ok(None)` on timeout.
+pub fn output_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<Option<Output>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drain both pipes so a chatty child can't block on a full buffer
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Some(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Decode command output as UTF-8, logging when invalid bytes had to be replaced
+pub fn decode_lossy(bytes: &[u8], what: &str) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(e) => {
+            log::warn!("{} is not valid UTF-8, replacing bytes: {}", what, e);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
+
+/// Render a path for display, logging when it isn't valid UTF-8
+pub fn display_path(path: &Path) -> String {
+    match path.to_str() {
+        Some(text) => text.to_string(),
+        None => {
+            log::warn!("Path {:?} is not valid UTF-8, replacing bytes", path);
+            path.to_string_lossy().into_owned()
+        }
+    }
+}